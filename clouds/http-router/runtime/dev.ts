@@ -1,5 +1,5 @@
 import { relative as relativePath } from "https://deno.land/std@0.190.0/path/mod.ts";
-import { join } from "https://deno.land/std@0.190.0/path/posix.ts";
+import { join, normalize } from "https://deno.land/std@0.190.0/path/posix.ts";
 
 import {
   BaseServer,
@@ -14,10 +14,12 @@ import { HTTPResponse } from "./response.ts";
 import { EntryController } from "./types.ts";
 import { HTTP_HOOKS } from "./hooks.ts";
 
+// Paths on development, the imported modules on production (`densky build`)
+type Controller = string | EntryController;
 type ControllerResolved = {
-  middlewares: Array<string>;
-  fallbacks: Array<string>;
-  controller: string;
+  middlewares: Array<Controller>;
+  fallbacks: Array<Controller>;
+  controller: Controller;
 };
 type ManifestResolver = (req: HTTPRequest) => ControllerResolved | null;
 
@@ -27,6 +29,13 @@ const logger_cache = new Logger("HTTP:CACHE");
 let manifestResolver: ManifestResolver | null = null;
 const cache = new ServerCache<EntryController>();
 
+// Sent by the CLI, the output folder can be changed on the config file
+function manifestPath(): string {
+  const path = Deno.env.get("DENSKY_MANIFEST") ??
+    join(Globals.cwd, ".densky/manifest.ts");
+  return normalize(path);
+}
+
 function importWithoutCache(url: string): Promise<unknown> {
   logger_cache.debug("import ", "./" + stripPrefix(url, Globals.cwd));
   return import(
@@ -53,10 +62,12 @@ async function importController(url: string): Promise<EntryController | null> {
 }
 
 async function handleController(
-  path: string,
+  path: Controller,
   req: HTTPRequest,
 ): Promise<Response | null> {
-  const controller = await importController(path);
+  const controller = typeof path === "string"
+    ? await importController(path)
+    : path;
   if (!controller) {
     return new Response("Controller doesn't exist: " + path, {
       status: 500,
@@ -74,11 +85,13 @@ async function handleController(
   return HTTPResponse.toResponse(req, out);
 }
 
-DevServer.hooks.registerHook("beforeWatchUpdate", () => {
-  manifestResolver = null;
-});
-
 DevServer.hooks.registerHook("watchUpdate", (kind, path) => {
+  // The CLI reports the manifest only when it changed
+  if (normalize(path) === manifestPath()) {
+    manifestResolver = null;
+    return;
+  }
+
   cache.get(path) &&
     logger.info(
       kind.toUpperCase(),
@@ -100,9 +113,9 @@ BaseServer.hooks.registerHook("request", async (r) => {
   if (!manifestResolver) {
     type ManifestResolverModule = { default: ManifestResolver };
 
-    manifestResolver = await importWithoutCache(
-      join(Globals.cwd, ".densky/manifest.ts"),
-    ).then((m) => (m as ManifestResolverModule).default);
+    manifestResolver = await importWithoutCache(manifestPath()).then((m) =>
+      (m as ManifestResolverModule).default
+    );
   }
 
   const resolvedController = manifestResolver!(raw);
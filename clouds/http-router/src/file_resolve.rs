@@ -1,7 +1,9 @@
-use densky_adapter::{ErrorContext, Result};
+use densky_adapter::{anyhow, ErrorContext, Result};
 use std::path::PathBuf;
 
-use densky_adapter::{context::CloudContextRaw, CloudFile, CloudFileResolve};
+use densky_adapter::{
+    context::CloudContextRaw, CloudFile, CloudFileResolve, ParamConstraint, SegmentPattern,
+};
 
 #[no_mangle]
 pub fn cloud_file_resolve(file: CloudFile, _context: CloudContextRaw) -> Result<CloudFileResolve> {
@@ -26,10 +28,26 @@ pub fn cloud_file_resolve(file: CloudFile, _context: CloudContextRaw) -> Result<
         },
         _ => {
             let path_segments: Vec<&std::ffi::OsStr> = relative_path.iter().collect();
+
+            // Route groups: "(admin)/users.ts"
+            let is_group = |part: &str| part.starts_with('(') && part.ends_with(')');
+            let has_groups = path_segments
+                .iter()
+                .any(|part| is_group(&part.to_string_lossy().replace(".ts", "")));
+            if has_groups {
+                let url_path: Vec<String> = path_segments
+                    .iter()
+                    .map(|part| part.to_string_lossy().replace(".ts", ""))
+                    .filter(|part| !is_group(part))
+                    .collect();
+
+                return Ok(CloudFileResolve::Group(url_path.join("/")));
+            }
+
             let dynamic_part = path_segments
                 .iter()
                 .enumerate()
-                .find(|f| (**f.1).to_string_lossy().starts_with('$'));
+                .find(|f| (**f.1).to_string_lossy().contains('$'));
 
             if let Some(dynamic_part) = dynamic_part {
                 let mut prefix: Vec<String> = vec![];
@@ -43,10 +61,69 @@ pub fn cloud_file_resolve(file: CloudFile, _context: CloudContextRaw) -> Result<
                     }
                 }
 
+                let prefix = prefix.join("/").replace(".ts", "");
+                let varname = dynamic_part.1.to_string_lossy().replace(".ts", "");
+
+                if !varname.starts_with('$') {
+                    // Mixed segment: "report-$year.csv", "v$version"
+                    let pattern = SegmentPattern::parse(&varname).map_err(|err| {
+                        anyhow!(
+                            "Invalid param in segment {varname:?} ({err}): {}",
+                            relative_path.display()
+                        )
+                    })?;
+
+                    return Ok(CloudFileResolve::Partial(
+                        prefix,
+                        pattern,
+                        suffix.join("/").replace(".ts", ""),
+                    ));
+                }
+
+                if let Some(name) = varname.strip_prefix("$...") {
+                    if name.is_empty() {
+                        return Err(anyhow!(
+                            "Catch-all segment {varname:?} doesn't have a name: {}",
+                            relative_path.display()
+                        ));
+                    }
+
+                    if !suffix.is_empty() {
+                        return Err(anyhow!(
+                            "Catch-all segment {varname:?} should be the last part of the path: {}",
+                            relative_path.display()
+                        ));
+                    }
+
+                    return Ok(CloudFileResolve::CatchAll(prefix, varname));
+                }
+
+                if let Some(varname) = varname.strip_suffix('?') {
+                    if !suffix.is_empty() {
+                        return Err(anyhow!(
+                            "Optional segment \"{varname}?\" should be the last part of the path: {}",
+                            relative_path.display()
+                        ));
+                    }
+
+                    return Ok(CloudFileResolve::Optional(prefix, varname.to_string()));
+                }
+
+                // Typed or constrained param: "$id:int", "$slug:[a-z0-9-]+"
+                let (varname, constraint) = match varname.split_once(':') {
+                    Some((varname, constraint)) => {
+                        let constraint = ParamConstraint::parse(constraint)
+                            .map_err(|err| anyhow!("{err}: {}", relative_path.display()))?;
+                        (varname.to_string(), constraint)
+                    }
+                    None => (varname, None),
+                };
+
                 Ok(CloudFileResolve::Dynamic(
-                    prefix.join("/").replace(".ts", ""),
-                    dynamic_part.1.to_string_lossy().replace(".ts", "").into(),
+                    prefix,
+                    varname,
                     suffix.join("/").replace(".ts", ""),
+                    constraint,
                 ))
             } else {
                 Ok(CloudFileResolve::Pass)
@@ -54,3 +131,30 @@ pub fn cloud_file_resolve(file: CloudFile, _context: CloudContextRaw) -> Result<
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use densky_adapter::{context::CloudContext, CloudFile, CloudFileResolve};
+
+    use super::cloud_file_resolve;
+    use crate::context::HttpRouterContext;
+
+    fn resolve(relative_path: &str) -> densky_adapter::Result<CloudFileResolve> {
+        let file = CloudFile::new(relative_path, relative_path, relative_path);
+        cloud_file_resolve(file, HttpRouterContext::default().to_raw())
+    }
+
+    #[test]
+    fn catch_all_name() {
+        match resolve("docs/$...path.ts") {
+            Ok(CloudFileResolve::CatchAll(prefix, varname)) => {
+                assert_eq!(prefix, "docs");
+                assert_eq!(varname, "$...path");
+            }
+            other => panic!("Expected a catch-all, got {:?}", other),
+        }
+
+        let err = resolve("docs/$....ts").unwrap_err();
+        assert!(err.to_string().contains("doesn't have a name"), "{}", err);
+    }
+}
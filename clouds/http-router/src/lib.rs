@@ -1,10 +1,15 @@
 #[macro_use]
 extern crate densky_adapter;
+#[cfg(test)]
+extern crate regex;
 
 pub mod context;
 mod file_resolve;
 mod manifest;
 mod manifest_process;
+mod strategy;
+#[cfg(test)]
+mod test;
 
 pub use file_resolve::cloud_file_resolve;
 pub use manifest_process::{cloud_before_manifest, cloud_manifest};
@@ -5,16 +5,17 @@ pub mod Manifest {
     use std::{fs, io};
 
     use densky_adapter::{
-        optimized_tree::OptimizedTreeContainer, utils::join_paths, CloudManifestUpdate,
-        CompileContext,
+        optimized_tree::{NodeId, OptimizedTreeContainer},
+        utils::join_paths,
+        CloudManifestUpdate, CompileContext,
     };
 
     /// I need to traverse the OptimizedTreeContainer without get itself
 
     /// Generate TS code for this node and children
-    fn build_node(id: u64, container: &OptimizedTreeContainer) -> CloudManifestUpdate {
+    fn build_node(id: NodeId, container: &OptimizedTreeContainer) -> CloudManifestUpdate {
         let mut updates = CloudManifestUpdate::new();
-        let node = container.nodes.get_reader(id).unwrap();
+        let node = &container.nodes[id];
 
         let mut static_children = String::new();
         let mut children = String::new();
@@ -47,11 +48,7 @@ pub mod Manifest {
             updates += children_update;
         }
 
-        let dynamic_child = if let Some((id, varname)) = node.dynamic.as_ref() {
-            let mut child = container.nodes.get_writer(*id).unwrap();
-            child.varname = Some(varname.clone());
-            drop(child);
-
+        let dynamic_child = if let Some((id, _)) = node.dynamic.as_ref() {
             build_node(*id, container)
         } else {
             CloudManifestUpdate::new()
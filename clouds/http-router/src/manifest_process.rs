@@ -1,120 +1,572 @@
-use densky_adapter::{CloudManifestUpdate, OptimizedTreeLeaf, Result};
+use std::collections::BTreeMap;
+
+use densky_adapter::{
+    anyhow,
+    js::{Expr, ImportSpecifier, Stmt},
+    utils::relative_path,
+    CloudManifestUpdate, CloudOptions, CompileContext, CompileMode, Error, OptimizedTreeLeaf,
+    ParamConstraint, Result,
+};
+
+use crate::strategy::{identifier, is_static_url, url_regex, url_segments, RouterStrategy};
 
 #[no_mangle]
-pub fn cloud_before_manifest() -> Result<CloudManifestUpdate> {
+pub fn cloud_before_manifest(
+    _context: &CompileContext,
+    options: &CloudOptions,
+) -> Result<CloudManifestUpdate> {
+    // The tables are filled by the leaves, before the first request
+    let hoisted = match RouterStrategy::from_options(options)? {
+        RouterStrategy::Map => vec![Stmt::constant(
+            "__DENSKY_static_routes",
+            Expr::ident("Map").construct(vec![]),
+        )],
+        RouterStrategy::Regex => vec![Stmt::constant("__DENSKY_routes", Expr::array(vec![]))],
+        RouterStrategy::Tree | RouterStrategy::Switch => vec![],
+    };
+
     Ok(CloudManifestUpdate::new()
-        .add_import("{ type HTTPRequest }", "densky/http-router.ts")
-        .add_argument("req", "HTTPRequest"))
+        .add_import(
+            ImportSpecifier::named("HTTPRequest").type_only(),
+            "densky/http-router.ts",
+        )
+        .add_argument("req", "HTTPRequest")
+        .add_hoisted(hoisted))
 }
 
 #[no_mangle]
 pub fn cloud_manifest(
     leaf: OptimizedTreeLeaf,
-    static_children: String,
-    children: String,
-    dynamic_child: String,
+    static_children: Vec<(String, Vec<Stmt>)>,
+    children: Vec<Stmt>,
+    dynamic_child: Vec<Stmt>,
+    context: &CompileContext,
+    options: &CloudOptions,
 ) -> Result<CloudManifestUpdate> {
-    let pathname_comment = format!("// {}", leaf.pathname);
+    let strategy = RouterStrategy::from_options(options)?;
+    let mut controllers = Controllers::new(context);
+    let pathname = leaf.pathname.clone();
+    let pathname_comment = Stmt::comment(pathname.as_str());
+
+    let result = match &leaf.index {
+        Some(input_path) => Some(Expr::object(vec![
+            ("middlewares", controllers.thorns(&leaf, "middleware")?),
+            ("fallbacks", controllers.thorns(&leaf, "fallback")?),
+            ("controller", controllers.reference(input_path)?),
+        ])),
+        None => None,
+    };
+    let inner = result.clone().map(|result| Stmt::Return(Some(result)));
+
+    let url = url_segments(&pathname);
+    let mut hoisted = Vec::new();
+    match strategy {
+        RouterStrategy::Map => {
+            if let (Some(result), true) = (result, is_static_url(&url)) {
+                hoisted.push(
+                    Expr::ident("__DENSKY_static_routes")
+                        .method("set", vec![Expr::string(url.join("/")), result])
+                        .into(),
+                );
+            }
+        }
+        RouterStrategy::Regex => {
+            if let Some(result) = result {
+                hoisted.push(route_entry(&url, result)?);
+            }
+            // The root is the last leaf, so all the routes are there
+            if !leaf.is_root {
+                return Ok(controllers.update(vec![], hoisted));
+            }
+            hoisted.push(regex_constant());
+            return Ok(controllers.update(regex_lookup(), hoisted));
+        }
+        RouterStrategy::Tree | RouterStrategy::Switch => {}
+    }
+
     let children = if static_children.is_empty() {
         children
+    } else if strategy == RouterStrategy::Switch {
+        let mut stmts = static_switch(static_children);
+        stmts.extend(children);
+        stmts
     } else {
-        format!(
-            "{{
-                const __DENSKY_static_children = {{ {static_children} }};
-                const out = __DENSKY_static_children[req.__accumulator__.path];
-                if (out) return out();
-            }};
-            {children}"
-        )
+        let static_children = Expr::object(
+            static_children
+                .into_iter()
+                .map(|(pathname, body)| (pathname, Expr::arrow(body))),
+        );
+        let out = Expr::ident("out");
+
+        let mut stmts = vec![Stmt::Block(vec![
+            Stmt::constant("__DENSKY_static_children", static_children),
+            Stmt::constant(
+                "out",
+                Expr::ident("__DENSKY_static_children").index(accumulator("path")),
+            ),
+            Stmt::If(out.clone(), vec![Stmt::Return(Some(out.call(vec![])))]),
+        ])];
+        stmts.extend(children);
+        stmts
     };
 
-    let inner = leaf.index.as_ref().map(|input_path| {
-        let middlewares = leaf
-            .single_thorns
-            .get("middleware")
-            .map(|t| t.iter().map(|t| format!("{t:?},")).collect::<String>())
-            .unwrap_or_default();
-
-        let fallbacks = leaf
-            .single_thorns
-            .get("fallback")
-            .map(|t| t.iter().map(|t| format!("{t:?},")).collect::<String>())
-            .unwrap_or_default();
-
-        format!(
-            "return {{
-                middlewares: [{middlewares}],
-                fallbacks: [{fallbacks}],
-                controller: {input_path:?}
-            }};"
-        )
-    });
+    // Only when the whole path was consumed
+    let end_of_path = |inner: Option<Stmt>| {
+        inner.map(|inner| {
+            Stmt::If(
+                accumulator("segments")
+                    .member("length")
+                    .strict_eq(Expr::number(0)),
+                vec![inner],
+            )
+        })
+    };
 
     if leaf.is_root {
         // Root
-        let inner = inner
-            .map(|i| format!("if (req.__accumulator__.segments.length === 0) {{ {i} }}"))
-            .unwrap_or_default();
+        let mut stmts = Vec::new();
+        if strategy == RouterStrategy::Map {
+            let route = Expr::ident("__DENSKY_route");
+            stmts.push(Stmt::Block(vec![
+                Stmt::constant(
+                    "__DENSKY_route",
+                    Expr::ident("__DENSKY_static_routes").method("get", vec![accumulator("path")]),
+                ),
+                Stmt::If(route.clone(), vec![Stmt::Return(Some(route))]),
+            ]));
+        }
+        stmts.extend(children);
+        stmts.push(pathname_comment);
+        stmts.extend(end_of_path(inner));
+        stmts.extend(dynamic_child);
+        stmts.push(Stmt::Return(Some(Expr::Null)));
 
-        Ok(CloudManifestUpdate::new_content(format!(
-            "{children}\n{pathname_comment}\n{inner}\n{dynamic_child}\nreturn null;",
-        )))
+        Ok(controllers.update(stmts, hoisted))
     } else {
         if leaf.is_static {
             // Static Node
-            Ok(CloudManifestUpdate::new_content(format!(
-                "{pathname_comment}\n{}",
-                inner.expect("Static leafs should have index")
-            )))
+            Ok(controllers.update(
+                vec![
+                    pathname_comment,
+                    inner.ok_or_else(|| missing(&pathname, "index"))?,
+                ],
+                hoisted,
+            ))
+        } else if leaf.is_catch_all {
+            // Catch-all Node
+            let varname = leaf.varname.ok_or_else(|| missing(&pathname, "varname"))?;
+            let varname = varname.strip_prefix("$...").unwrap_or(&varname);
+            let inner = inner.ok_or_else(|| missing(&pathname, "index"))?;
+
+            let mut body = vec![set_param(
+                varname,
+                accumulator("segments").method("join", vec![Expr::string("/")]),
+            )];
+            body.extend(set_accumulator("segments", Expr::array(vec![])));
+            body.extend(set_accumulator("path", Expr::string("")));
+            body.push(inner);
+
+            Ok(controllers.update(
+                vec![
+                    pathname_comment,
+                    Stmt::If(
+                        accumulator("segments").member("length").gt(Expr::number(0)),
+                        body,
+                    ),
+                ],
+                hoisted,
+            ))
+        } else if leaf.is_optional {
+            // Optional Named Node
+            let varname = leaf.varname.ok_or_else(|| missing(&pathname, "varname"))?;
+            let varname = &varname[1..];
+            let var = Expr::ident(format!("__var_{varname}"));
+            let inner = inner.ok_or_else(|| missing(&pathname, "index"))?;
+
+            let mut body = vec![
+                Stmt::constant(
+                    format!("__var_{varname}"),
+                    accumulator("segments").index(Expr::number(0)),
+                ),
+                Stmt::If(
+                    var.clone().strict_ne(Expr::Undefined),
+                    vec![set_param(varname, var)],
+                ),
+            ];
+            body.extend(set_accumulator("segments", Expr::array(vec![])));
+            body.extend(set_accumulator("path", Expr::string("")));
+            body.push(inner);
+
+            Ok(controllers.update(
+                vec![
+                    pathname_comment,
+                    Stmt::If(
+                        accumulator("segments").member("length").le(Expr::number(1)),
+                        body,
+                    ),
+                ],
+                hoisted,
+            ))
         } else {
             // Dynamic Node
-            let inner = inner
-                .map(|i| format!("if (req.__accumulator__.segments.length === 0) {{ {i} }}"))
-                .unwrap_or_default();
+            let inner = end_of_path(inner);
 
             if let Some(varname) = leaf.varname {
                 // Dynamic Named Node
                 let varname = &varname[1..];
+                let segment = accumulator("segments").index(Expr::number(0));
+                let mut check = accumulator("segments").member("length").gt(Expr::number(0));
+                let (value, constraint) = match &leaf.pattern {
+                    // The var is inside the segment: "report-$year.csv"
+                    Some(pattern) => {
+                        let before = Expr::number(pattern.before.len() as i64);
+                        let after = Expr::number(pattern.after.len() as i64);
+                        let min_len = (pattern.before.len() + pattern.after.len()) as i64;
+
+                        check =
+                            check
+                                .and(segment.clone().member("length").gt(Expr::number(min_len)))
+                                .and(segment.clone().method(
+                                    "startsWith",
+                                    vec![Expr::string(pattern.before.as_str())],
+                                ))
+                                .and(segment.clone().method(
+                                    "endsWith",
+                                    vec![Expr::string(pattern.after.as_str())],
+                                ));
+
+                        let value = segment
+                            .clone()
+                            .method("slice", vec![before, segment.member("length") - after]);
+                        (value, pattern.constraint.as_ref())
+                    }
+                    None => (segment, leaf.constraint.as_ref()),
+                };
+                // Validate before bind the param, so on mismatch it falls
+                // through the next candidate
+                if let Some(constraint) = constraint {
+                    check = check.and(constraint_check(constraint, value.clone()));
+                }
+
+                let var = format!("__var_{varname}");
+                let segments = format!("__segments_{varname}");
 
-                Ok(CloudManifestUpdate::new_content(format!(
-                    r#"{pathname_comment}
-                        if (req.__accumulator__.segments.length > 0) {{
-                            const __var_{varname} = req.__accumulator__.segments.shift();
-                            if (__var_{varname} === undefined) {{
-                                throw new Error("Unreachable");
-                            }}
-                            req.params.set("{varname}", __var_{varname});
-                            // @ts-ignore READ-ONLY
-                            req.__accumulator__.path = req.__accumulator__.segments.join("/");
-
-                            {children}
-                            {inner}
-                            {dynamic_child}
-                            return null;
-                        }}"#
-                )))
+                let mut body = vec![
+                    Stmt::constant(var.as_str(), value),
+                    Stmt::constant(segments.as_str(), accumulator("segments")),
+                    set_param(varname, Expr::ident(var)),
+                ];
+                body.extend(set_accumulator(
+                    "segments",
+                    Expr::ident(segments.as_str()).method("slice", vec![Expr::number(1)]),
+                ));
+                body.extend(set_accumulator(
+                    "path",
+                    accumulator("segments").method("join", vec![Expr::string("/")]),
+                ));
+                body.extend(children);
+                body.extend(inner);
+                body.extend(dynamic_child);
+
+                body.push(Stmt::comment(
+                    "Restore the accumulator for the next candidates (catch-all)",
+                ));
+                body.push(
+                    Expr::ident("req")
+                        .member("params")
+                        .method("delete", vec![Expr::string(varname)])
+                        .into(),
+                );
+                body.extend(set_accumulator("segments", Expr::ident(segments.as_str())));
+                body.extend(set_accumulator(
+                    "path",
+                    Expr::ident(segments).method("join", vec![Expr::string("/")]),
+                ));
+
+                Ok(controllers.update(vec![pathname_comment, Stmt::If(check, body)], hoisted))
             } else {
                 // Index Node
-                let slash_count = leaf.relative_pathname.chars().filter(|c| c == &'/').count();
+                let pathname = leaf.relative_pathname;
+                let slash_count = pathname.chars().filter(|c| c == &'/').count();
                 // The first part doesn't have slash
                 let slash_count = slash_count + 1;
 
-                Ok(CloudManifestUpdate::new_content(format!(
-                    r#" {pathname_comment}
-                        if (req.__accumulator__.path === "{pathname}" || req.__accumulator__.path.startsWith("{pathname}/")) {{
-                            // @ts-ignore READ-ONLY
-                            req.__accumulator__.segments = req.__accumulator__.segments.slice({slash_count});
-                            // @ts-ignore READ-ONLY
-                            req.__accumulator__.path = req.__accumulator__.segments.join("/");
-
-                            {children}
-                            {inner}
-                            {dynamic_child}
-                            return null;
-                        }}"#,
-                    pathname = leaf.relative_pathname
-                )))
+                let check = accumulator("path")
+                    .strict_eq(Expr::string(pathname.as_str()))
+                    .or(accumulator("path")
+                        .method("startsWith", vec![Expr::string(format!("{pathname}/"))]));
+                let prev_segments = Expr::ident("__prev_segments");
+
+                let mut body = vec![Stmt::constant("__prev_segments", accumulator("segments"))];
+                body.extend(set_accumulator(
+                    "segments",
+                    prev_segments
+                        .clone()
+                        .method("slice", vec![Expr::number(slash_count as i64)]),
+                ));
+                body.extend(set_accumulator(
+                    "path",
+                    accumulator("segments").method("join", vec![Expr::string("/")]),
+                ));
+                body.extend(children);
+                body.extend(inner);
+                body.extend(dynamic_child);
+
+                body.push(Stmt::comment(
+                    "Restore the accumulator for the next candidates (catch-all)",
+                ));
+                body.extend(set_accumulator("segments", prev_segments.clone()));
+                body.extend(set_accumulator(
+                    "path",
+                    prev_segments.method("join", vec![Expr::string("/")]),
+                ));
+
+                Ok(controllers.update(vec![pathname_comment, Stmt::If(check, body)], hoisted))
             }
         }
     }
 }
+
+/// How the manifest refers to the route files. On development they're paths
+/// that the runtime imports on each reload, on production they're static
+/// imports relative to the manifest, so it can be bundled.
+struct Controllers<'a> {
+    context: &'a CompileContext,
+    /// (Local, Path)
+    imports: Vec<(String, String)>,
+}
+
+impl<'a> Controllers<'a> {
+    fn new(context: &'a CompileContext) -> Controllers<'a> {
+        Controllers {
+            context,
+            imports: Vec::new(),
+        }
+    }
+
+    /// The path or the module of the route file
+    fn reference(&mut self, file: &str) -> Result<Expr> {
+        if self.context.mode == CompileMode::Development {
+            return Ok(Expr::string(file));
+        }
+
+        let path = relative_path(file, &self.context.output_dir)
+            .ok_or_else(|| anyhow!("Can't import {file:?} from the manifest"))?;
+        let path = path.display().to_string();
+        let local = module_local(&path);
+
+        self.imports.push((local.clone(), path));
+        Ok(Expr::ident(local))
+    }
+
+    /// Route files of the single thorn, from the leaf to the root. The
+    /// runtime runs them in this order, so the closest one goes first.
+    fn thorns(&mut self, leaf: &OptimizedTreeLeaf, name: &str) -> Result<Expr> {
+        let files = leaf.single_thorns.get(name).into_iter().flatten();
+        let files = files
+            .map(|file| self.reference(file))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Expr::array(files))
+    }
+
+    fn update(self, statements: Vec<Stmt>, hoisted: Vec<Stmt>) -> CloudManifestUpdate {
+        self.imports.into_iter().fold(
+            CloudManifestUpdate::new_statements(statements).add_hoisted(hoisted),
+            |update, (local, path)| update.add_import(ImportSpecifier::namespace(local), path),
+        )
+    }
+}
+
+/// Name of the imported module, each path has its own name:
+/// `../src/http/$id.ts` => `__module__2e__2e__2f_src_2f_http_2f_$id_2e_ts`
+fn module_local(path: &str) -> String {
+    identifier("__module_", path)
+}
+
+/// A `switch` on each segment of the static children, they don't have to
+/// be on a single segment: `a/b/c`
+fn static_switch(static_children: Vec<(String, Vec<Stmt>)>) -> Vec<Stmt> {
+    #[derive(Default)]
+    struct Trie {
+        body: Option<Vec<Stmt>>,
+        children: BTreeMap<String, Trie>,
+    }
+
+    fn print(trie: Trie, depth: i64) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        if let Some(body) = trie.body {
+            stmts.push(Stmt::If(
+                accumulator("segments")
+                    .member("length")
+                    .strict_eq(Expr::number(depth)),
+                body,
+            ));
+        }
+        if !trie.children.is_empty() {
+            let cases = trie
+                .children
+                .into_iter()
+                .map(|(segment, child)| {
+                    let mut body = print(child, depth + 1);
+                    // Without match it goes to the next candidates
+                    body.push(Stmt::Break);
+                    (Expr::string(segment), body)
+                })
+                .collect();
+            stmts.push(Stmt::Switch(
+                accumulator("segments").index(Expr::number(depth)),
+                cases,
+            ));
+        }
+        stmts
+    }
+
+    let mut trie = Trie::default();
+    for (pathname, body) in static_children {
+        let node = pathname.split('/').fold(&mut trie, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+        node.body = Some(body);
+    }
+
+    print(trie, 0)
+}
+
+/// `__DENSKY_routes.push({ name, source, match })`, `match` binds the params
+/// of the regex groups and returns the route
+fn route_entry(url: &[&str], result: Expr) -> Result<Stmt> {
+    let name = identifier("__route_", &url.join("/"));
+    let (source, params) = url_regex(url, &name)?;
+    let groups = Expr::ident("groups");
+
+    let mut body: Vec<Stmt> = params
+        .into_iter()
+        .map(|param| {
+            let value = groups.clone().member(param.group);
+            let set = set_param(&param.name, value.clone());
+            if param.optional {
+                Stmt::If(value.strict_ne(Expr::Undefined), vec![set])
+            } else {
+                set
+            }
+        })
+        .collect();
+    body.push(Stmt::Return(Some(result)));
+
+    Ok(Expr::ident("__DENSKY_routes")
+        .method(
+            "push",
+            vec![Expr::object(vec![
+                ("name", Expr::string(name)),
+                ("source", Expr::string(source)),
+                ("match", Expr::arrow_with(["groups", "req"], body)),
+            ])],
+        )
+        .into())
+}
+
+/// All the routes on a single regex, in the order of the tree. Each route is
+/// a named group, so the matched one is the group that isn't `undefined`
+fn regex_constant() -> Stmt {
+    let route = Expr::ident("route");
+    let group = Expr::string("(?<")
+        + route.clone().member("name")
+        + Expr::string(">")
+        + route.member("source")
+        + Expr::string(")");
+    let source = Expr::string("^(?:")
+        + Expr::ident("__DENSKY_routes")
+            .method(
+                "map",
+                vec![Expr::arrow_with(["route"], vec![Stmt::Return(Some(group))])],
+            )
+            .method("join", vec![Expr::string("|")])
+        + Expr::string(")$");
+
+    Stmt::constant(
+        "__DENSKY_regex",
+        Expr::ident("RegExp").construct(vec![source]),
+    )
+}
+
+/// Body of the root with the regex strategy
+fn regex_lookup() -> Vec<Stmt> {
+    let matched = Expr::ident("__DENSKY_match");
+    let groups = matched.clone().member("groups");
+    let route = Expr::ident("__DENSKY_route");
+
+    let find = Expr::ident("__DENSKY_routes").method(
+        "find",
+        vec![Expr::arrow_with(
+            ["route"],
+            vec![Stmt::Return(Some(
+                groups
+                    .clone()
+                    .index(Expr::ident("route").member("name"))
+                    .strict_ne(Expr::Undefined),
+            ))],
+        )],
+    );
+
+    vec![
+        Stmt::constant(
+            "__DENSKY_match",
+            Expr::ident("__DENSKY_regex").method("exec", vec![accumulator("path")]),
+        ),
+        // Without routes the regex matches the empty path without groups
+        Stmt::If(
+            matched.and(groups.clone()),
+            vec![
+                Stmt::constant("__DENSKY_route", find),
+                Stmt::If(
+                    route.clone(),
+                    vec![Stmt::Return(Some(
+                        route.method("match", vec![groups, Expr::ident("req")]),
+                    ))],
+                ),
+            ],
+        ),
+        Stmt::Return(Some(Expr::Null)),
+    ]
+}
+
+/// The leaf doesn't have something that its kind needs
+fn missing(pathname: &str, what: &str) -> Error {
+    anyhow!("The leaf {pathname:?} doesn't have {what}")
+}
+
+/// `req.__accumulator__.<field>`
+fn accumulator(field: &str) -> Expr {
+    Expr::ident("req").member("__accumulator__").member(field)
+}
+
+/// Assign a field of the accumulator, it's read-only for the controllers
+fn set_accumulator(field: &str, value: Expr) -> Vec<Stmt> {
+    vec![
+        Stmt::comment("@ts-ignore READ-ONLY"),
+        Stmt::assign(accumulator(field), value),
+    ]
+}
+
+/// `req.params.set("<varname>", value);`
+fn set_param(varname: &str, value: Expr) -> Stmt {
+    Expr::ident("req")
+        .member("params")
+        .method("set", vec![Expr::string(varname), value])
+        .into()
+}
+
+/// Generate the JS expression that validates `value` with the constraint
+fn constraint_check(constraint: &ParamConstraint, value: Expr) -> Expr {
+    let regex = match constraint {
+        ParamConstraint::Int => Expr::regex(r"^-?\d+$", ""),
+        ParamConstraint::Uuid => Expr::regex(
+            "^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$",
+            "i",
+        ),
+        ParamConstraint::Regex(regex) => Expr::regex(format!("^(?:{regex})$"), ""),
+    };
+
+    regex.method("test", vec![value])
+}
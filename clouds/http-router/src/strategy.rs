@@ -0,0 +1,195 @@
+use densky_adapter::{anyhow, CloudOptions, ParamConstraint, Result, SegmentPattern};
+
+/// How the manifest finds the route of a request. It's the `strategy`
+/// option of the cloud on the config file:
+/// ```jsonc
+/// "clouds": {
+///   "http-router": { "version": "^0.2.0", "strategy": "switch" }
+/// }
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RouterStrategy {
+    /// `"tree"`: walk the tree. The static children are found with an
+    /// object lookup and the params with nested `if` chains.
+    #[default]
+    Tree,
+    /// `"switch"`: walk the tree, the static children are found with a
+    /// `switch` for each segment, so nothing is allocated on each request.
+    Switch,
+    /// `"map"`: the routes without params are found on a `Map` that's built
+    /// once, the other ones walk the tree.
+    Map,
+    /// `"regex"`: all the routes are joined in a single regex that's built
+    /// once, the first route that matches the whole path wins.
+    Regex,
+}
+
+impl RouterStrategy {
+    pub fn from_options(options: &CloudOptions) -> Result<RouterStrategy> {
+        let Some(strategy) = options.get("strategy") else {
+            return Ok(RouterStrategy::default());
+        };
+
+        match strategy.as_string().map(String::as_str) {
+            Some("tree") => Ok(RouterStrategy::Tree),
+            Some("switch") => Ok(RouterStrategy::Switch),
+            Some("map") => Ok(RouterStrategy::Map),
+            Some("regex") => Ok(RouterStrategy::Regex),
+            _ => Err(anyhow!(
+                "Invalid router strategy {strategy:?}, it should be \"tree\", \"switch\", \"map\" or \"regex\""
+            )),
+        }
+    }
+}
+
+/// Segments of the URL of a leaf, the route groups aren't part of it:
+/// `/(admin)/users/$id` => `["users", "$id"]`
+pub fn url_segments(pathname: &str) -> Vec<&str> {
+    pathname
+        .split('/')
+        .filter(|segment| {
+            let is_group = segment.starts_with('(') && segment.ends_with(')');
+            !segment.is_empty() && !is_group
+        })
+        .collect()
+}
+
+/// The URL doesn't have params, so it only matches one path
+pub fn is_static_url(segments: &[&str]) -> bool {
+    segments.iter().all(|segment| !segment.contains('$'))
+}
+
+/// A param of the route regex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteParam {
+    pub name: String,
+    /// Name of the regex group
+    pub group: String,
+    /// The group is `undefined` when it isn't on the path
+    pub optional: bool,
+}
+
+/// Regex source that matches the path (without the first slash) of the
+/// URL. Each param is a named group, prefixed with the route name so they're
+/// unique on the joined regex.
+pub fn url_regex(segments: &[&str], route: &str) -> Result<(String, Vec<RouteParam>)> {
+    let mut source = String::new();
+    let mut params = Vec::new();
+    let mut group = |name: &str, optional: bool| {
+        let group = format!("{route}_p_{name}");
+        params.push(RouteParam {
+            name: name.to_string(),
+            group: group.clone(),
+            optional,
+        });
+        group
+    };
+
+    for (i, segment) in segments.iter().enumerate() {
+        let separator = if i == 0 { "" } else { "/" };
+
+        if let Some(name) = segment.strip_prefix("$...") {
+            let group = group(name, false);
+            source += &format!("{separator}(?<{group}>.+)");
+        } else if let Some(name) = segment.strip_prefix('$').and_then(|s| s.strip_suffix('?')) {
+            let group = group(name, true);
+            source += &format!("(?:{separator}(?<{group}>[^/]+))?");
+        } else if let Some(param) = segment.strip_prefix('$') {
+            let (name, constraint) = match param.split_once(':') {
+                Some((name, constraint)) => (name, ParamConstraint::parse(constraint)?),
+                None => (param, None),
+            };
+            let group = group(name, false);
+            let value = value_source(constraint.as_ref());
+            source += &format!("{separator}(?<{group}>{value})");
+        } else if segment.contains('$') {
+            let pattern = SegmentPattern::parse(segment)
+                .map_err(|err| anyhow!("Invalid param in segment {segment:?} ({err})"))?;
+            let group = group(&pattern.varname[1..], false);
+            let value = value_source(pattern.constraint.as_ref());
+            source += &format!(
+                "{separator}{}(?<{group}>{value}){}",
+                escape_regex(&pattern.before),
+                escape_regex(&pattern.after)
+            );
+        } else {
+            source += separator;
+            source += &escape_regex(segment);
+        }
+    }
+
+    Ok((source, params))
+}
+
+/// Regex of a param value, it's the same check of the tree
+fn value_source(constraint: Option<&ParamConstraint>) -> String {
+    match constraint {
+        None => "[^/]+".into(),
+        Some(ParamConstraint::Int) => r"-?\d+".into(),
+        // The joined regex doesn't have the `i` flag
+        Some(ParamConstraint::Uuid) => {
+            "[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}".into()
+        }
+        // It can't match `/` (see `ConstraintRegex`), so it stays on the
+        // segment of the param
+        Some(ParamConstraint::Regex(regex)) => format!("(?:{regex})"),
+    }
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}/".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// A JS identifier for the text, each text has its own identifier:
+/// `../src/http/$id.ts` => `{prefix}_2e__2e__2f_src_2f_http_2f_$id_2e_ts`
+pub fn identifier(prefix: &str, text: &str) -> String {
+    let mut out = String::from(prefix);
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '$' {
+            out.push(c);
+        } else {
+            out.push_str(&format!("_{:x}_", c as u32));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_static_url, url_regex, url_segments};
+
+    #[test]
+    fn route_regex() {
+        let segments = url_segments("/(admin)/users/$id:int/report-$year.csv/$...rest");
+        assert_eq!(
+            segments,
+            vec!["users", "$id:int", "report-$year.csv", "$...rest"]
+        );
+        assert!(!is_static_url(&segments));
+
+        let (source, params) = url_regex(&segments, "r").unwrap();
+        assert_eq!(
+            source,
+            r"users/(?<r_p_id>-?\d+)/report-(?<r_p_year>[^/]+)\.csv/(?<r_p_rest>.+)"
+        );
+        let names: Vec<_> = params.iter().map(|param| param.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "year", "rest"]);
+
+        let (source, params) = url_regex(&url_segments("/$lang?"), "r").unwrap();
+        assert_eq!(source, "(?:(?<r_p_lang>[^/]+))?");
+        assert!(params[0].optional);
+
+        let (source, _) = url_regex(&url_segments("/blog/$slug?"), "r").unwrap();
+        assert_eq!(source, "blog(?:/(?<r_p_slug>[^/]+))?");
+
+        assert_eq!(url_regex(&[], "r").unwrap().0, "");
+        assert!(is_static_url(&url_segments("/(admin)/about")));
+    }
+}
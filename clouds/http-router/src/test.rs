@@ -0,0 +1,657 @@
+//! The generated manifests are run with a small evaluator of the JS syntax
+//! tree, so the router strategies can be compared without a JS runtime.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use densky_adapter::{
+    context::CloudContext,
+    js::{BinaryOp, Expr, Stmt},
+    optimized_tree::{NodeId, OptimizedTreeContainer},
+    CloudDependencyOption, CloudFile, CloudManifestUpdate, CloudOptions, CompileContext,
+    CompileMode, Result,
+};
+use regex::Regex;
+
+use crate::context::HttpRouterContext;
+use crate::{cloud_before_manifest, cloud_file_resolve, cloud_manifest};
+
+#[derive(Debug, Clone)]
+enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(i64),
+    Str(String),
+    Array(Rc<RefCell<Vec<Value>>>),
+    Object(Rc<RefCell<BTreeMap<String, Value>>>),
+    Map(Rc<RefCell<BTreeMap<String, Value>>>),
+    Regex(Rc<Regex>),
+    Function(Rc<Function>),
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    scope: Rc<Scope>,
+}
+
+#[derive(Debug, Default)]
+struct Scope {
+    vars: RefCell<BTreeMap<String, Value>>,
+    parent: Option<Rc<Scope>>,
+}
+
+enum Flow {
+    Next,
+    Break,
+    Return(Value),
+}
+
+impl Value {
+    fn array(items: Vec<Value>) -> Value {
+        Value::Array(Rc::new(RefCell::new(items)))
+    }
+
+    fn object(entries: impl IntoIterator<Item = (String, Value)>) -> Value {
+        Value::Object(Rc::new(RefCell::new(entries.into_iter().collect())))
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Undefined | Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(value) => *value != 0,
+            Value::Str(value) => !value.is_empty(),
+            _ => true,
+        }
+    }
+
+    fn strict_eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Undefined, Value::Undefined) | (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => Rc::ptr_eq(a, b),
+            (Value::Object(a), Value::Object(b)) | (Value::Map(a), Value::Map(b)) => {
+                Rc::ptr_eq(a, b)
+            }
+            _ => false,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Value::Str(value) => value,
+            other => panic!("Expected a string, got {:?}", other),
+        }
+    }
+
+    fn as_number(&self) -> i64 {
+        match self {
+            Value::Number(value) => *value,
+            other => panic!("Expected a number, got {:?}", other),
+        }
+    }
+
+    fn to_js_string(&self) -> String {
+        match self {
+            Value::Str(value) => value.clone(),
+            Value::Number(value) => value.to_string(),
+            other => panic!("Can't convert {:?} to a string", other),
+        }
+    }
+}
+
+impl Scope {
+    fn child(parent: &Rc<Scope>) -> Rc<Scope> {
+        Rc::new(Scope {
+            vars: RefCell::default(),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    fn declare(&self, name: &str, value: Value) {
+        let old = self.vars.borrow_mut().insert(name.to_string(), value);
+        assert!(old.is_none(), "{} is declared twice", name);
+    }
+
+    fn get(&self, name: &str) -> Value {
+        if let Some(value) = self.vars.borrow().get(name) {
+            return value.clone();
+        }
+        match &self.parent {
+            Some(parent) => parent.get(name),
+            None => panic!("{} isn't defined", name),
+        }
+    }
+}
+
+fn exec_block(stmts: &[Stmt], scope: &Rc<Scope>) -> Flow {
+    let scope = Scope::child(scope);
+    exec_all(stmts, &scope)
+}
+
+fn exec_all(stmts: &[Stmt], scope: &Rc<Scope>) -> Flow {
+    for stmt in stmts {
+        match exec(stmt, scope) {
+            Flow::Next => {}
+            flow => return flow,
+        }
+    }
+    Flow::Next
+}
+
+fn exec(stmt: &Stmt, scope: &Rc<Scope>) -> Flow {
+    match stmt {
+        Stmt::Expr(expr) => {
+            eval(expr, scope);
+        }
+        Stmt::Const(name, value) => {
+            let value = eval(value, scope);
+            scope.declare(name, value);
+        }
+        Stmt::Assign(Expr::Member(object, property), value) => {
+            let value = eval(value, scope);
+            match eval(object, scope) {
+                Value::Object(object) => {
+                    object.borrow_mut().insert(property.clone(), value);
+                }
+                other => panic!("Can't assign {} of {:?}", property, other),
+            }
+        }
+        Stmt::Assign(target, _) => panic!("Can't assign {:?}", target),
+        Stmt::If(condition, body) => {
+            if eval(condition, scope).is_truthy() {
+                return exec_block(body, scope);
+            }
+        }
+        Stmt::Block(body) => return exec_block(body, scope),
+        Stmt::Switch(discriminant, cases) => {
+            let discriminant = eval(discriminant, scope);
+            let matched = cases
+                .iter()
+                .position(|(case, _)| eval(case, scope).strict_eq(&discriminant));
+            // The cases fall through until a `break`
+            for (_, body) in cases.iter().skip(matched.unwrap_or(cases.len())) {
+                match exec_block(body, scope) {
+                    Flow::Next => {}
+                    Flow::Break => break,
+                    flow => return flow,
+                }
+            }
+        }
+        Stmt::Break => return Flow::Break,
+        Stmt::Return(value) => {
+            let value = value
+                .as_ref()
+                .map_or(Value::Undefined, |value| eval(value, scope));
+            return Flow::Return(value);
+        }
+        Stmt::Comment(_) => {}
+        Stmt::Origin(_, body) => return exec_all(body, scope),
+        Stmt::Raw(code) => panic!("Raw code can't be run: {}", code),
+    }
+
+    Flow::Next
+}
+
+fn eval(expr: &Expr, scope: &Rc<Scope>) -> Value {
+    match expr {
+        Expr::Ident(name) => scope.get(name),
+        Expr::Str(value) => Value::Str(value.clone()),
+        Expr::Number(value) => Value::Number(*value),
+        Expr::Bool(value) => Value::Bool(*value),
+        Expr::Null => Value::Null,
+        Expr::Undefined => Value::Undefined,
+        Expr::Regex(pattern, flags) => Value::Regex(Rc::new(regex(pattern, flags))),
+        Expr::Array(items) => Value::array(items.iter().map(|item| eval(item, scope)).collect()),
+        Expr::Object(entries) => Value::object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), eval(value, scope))),
+        ),
+        Expr::Arrow(params, body) => Value::Function(Rc::new(Function {
+            params: params.clone(),
+            body: body.clone(),
+            scope: scope.clone(),
+        })),
+        Expr::Member(object, property) => member(&eval(object, scope), property),
+        Expr::Index(object, index) => match (eval(object, scope), eval(index, scope)) {
+            (Value::Array(items), Value::Number(index)) => items
+                .borrow()
+                .get(index as usize)
+                .cloned()
+                .unwrap_or(Value::Undefined),
+            (object, Value::Str(key)) => member(&object, &key),
+            (object, index) => panic!("Can't index {:?} with {:?}", object, index),
+        },
+        Expr::Call(callee, args) => {
+            let args: Vec<Value> = args.iter().map(|arg| eval(arg, scope)).collect();
+            match &**callee {
+                Expr::Member(object, method) => call_method(eval(object, scope), method, args),
+                callee => call(&eval(callee, scope), args),
+            }
+        }
+        Expr::New(callee, args) => {
+            let args: Vec<Value> = args.iter().map(|arg| eval(arg, scope)).collect();
+            match &**callee {
+                Expr::Ident(name) if name == "Map" => Value::Map(Rc::default()),
+                Expr::Ident(name) if name == "RegExp" => {
+                    Value::Regex(Rc::new(regex(args[0].as_str(), "")))
+                }
+                callee => panic!("Can't construct {:?}", callee),
+            }
+        }
+        Expr::Binary(left, BinaryOp::And, right) => {
+            let left = eval(left, scope);
+            if left.is_truthy() {
+                eval(right, scope)
+            } else {
+                left
+            }
+        }
+        Expr::Binary(left, BinaryOp::Or, right) => {
+            let left = eval(left, scope);
+            if left.is_truthy() {
+                left
+            } else {
+                eval(right, scope)
+            }
+        }
+        Expr::Binary(left, op, right) => {
+            let (left, right) = (eval(left, scope), eval(right, scope));
+            match op {
+                BinaryOp::StrictEq => Value::Bool(left.strict_eq(&right)),
+                BinaryOp::StrictNe => Value::Bool(!left.strict_eq(&right)),
+                BinaryOp::Lt => Value::Bool(left.as_number() < right.as_number()),
+                BinaryOp::Le => Value::Bool(left.as_number() <= right.as_number()),
+                BinaryOp::Gt => Value::Bool(left.as_number() > right.as_number()),
+                BinaryOp::Ge => Value::Bool(left.as_number() >= right.as_number()),
+                BinaryOp::Add => match (&left, &right) {
+                    (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                    _ => Value::Str(left.to_js_string() + &right.to_js_string()),
+                },
+                BinaryOp::Sub => Value::Number(left.as_number() - right.as_number()),
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            }
+        }
+        Expr::Not(expr) => Value::Bool(!eval(expr, scope).is_truthy()),
+        Expr::Raw(code) => panic!("Raw code can't be run: {}", code),
+    }
+}
+
+/// `$` can be on the JS group names, but not on the Rust ones
+const GROUP_DOLLAR: &str = "_DOLLAR_";
+
+/// The JS regexes of the manifest, the syntax they use is the same on Rust
+/// but the group names
+fn regex(pattern: &str, flags: &str) -> Regex {
+    let mut parts = pattern.split("(?<");
+    let mut source = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let (name, rest) = part.split_once('>').unwrap();
+        source += &format!("(?<{}>{}", name.replace('$', GROUP_DOLLAR), rest);
+    }
+
+    let source = match flags {
+        "" => source,
+        "i" => format!("(?i){}", source),
+        flags => panic!("Unknown regex flags {:?}", flags),
+    };
+    Regex::new(&source).unwrap()
+}
+
+fn member(object: &Value, property: &str) -> Value {
+    match (object, property) {
+        (Value::Str(value), "length") => Value::Number(value.len() as i64),
+        (Value::Array(items), "length") => Value::Number(items.borrow().len() as i64),
+        (Value::Object(object), property) => object
+            .borrow()
+            .get(property)
+            .cloned()
+            .unwrap_or(Value::Undefined),
+        (object, property) => panic!("Can't read {} of {:?}", property, object),
+    }
+}
+
+fn call(function: &Value, args: Vec<Value>) -> Value {
+    let Value::Function(function) = function else {
+        panic!("{:?} isn't a function", function);
+    };
+
+    let scope = Scope::child(&function.scope);
+    for (i, param) in function.params.iter().enumerate() {
+        scope.declare(param, args.get(i).cloned().unwrap_or(Value::Undefined));
+    }
+
+    match exec_all(&function.body, &scope) {
+        Flow::Return(value) => value,
+        _ => Value::Undefined,
+    }
+}
+
+fn call_method(object: Value, method: &str, args: Vec<Value>) -> Value {
+    let arg = |i: usize| args.get(i).cloned().unwrap_or(Value::Undefined);
+
+    match (&object, method) {
+        (Value::Str(value), "startsWith") => Value::Bool(value.starts_with(arg(0).as_str())),
+        (Value::Str(value), "endsWith") => Value::Bool(value.ends_with(arg(0).as_str())),
+        (Value::Str(value), "slice") => {
+            let start = arg(0).as_number() as usize;
+            let end = match arg(1) {
+                Value::Undefined => value.len(),
+                end => end.as_number() as usize,
+            };
+            Value::Str(value.get(start..end.max(start)).unwrap_or("").to_string())
+        }
+        (Value::Array(items), "slice") => {
+            let start = (arg(0).as_number() as usize).min(items.borrow().len());
+            Value::array(items.borrow()[start..].to_vec())
+        }
+        (Value::Array(items), "join") => Value::Str(
+            items
+                .borrow()
+                .iter()
+                .map(Value::to_js_string)
+                .collect::<Vec<_>>()
+                .join(arg(0).as_str()),
+        ),
+        (Value::Array(items), "push") => {
+            items.borrow_mut().push(arg(0));
+            Value::Number(items.borrow().len() as i64)
+        }
+        (Value::Array(items), "map") => {
+            let items = items.borrow().clone();
+            Value::array(
+                items
+                    .into_iter()
+                    .map(|item| call(&arg(0), vec![item]))
+                    .collect(),
+            )
+        }
+        (Value::Array(items), "find") => {
+            let items = items.borrow().clone();
+            items
+                .into_iter()
+                .find(|item| call(&arg(0), vec![item.clone()]).is_truthy())
+                .unwrap_or(Value::Undefined)
+        }
+        (Value::Map(map), "get") => map
+            .borrow()
+            .get(arg(0).as_str())
+            .cloned()
+            .unwrap_or(Value::Undefined),
+        (Value::Map(map), "set") => {
+            map.borrow_mut().insert(arg(0).to_js_string(), arg(1));
+            object.clone()
+        }
+        (Value::Map(map), "delete") => {
+            Value::Bool(map.borrow_mut().remove(arg(0).as_str()).is_some())
+        }
+        (Value::Regex(regex), "test") => Value::Bool(regex.is_match(arg(0).as_str())),
+        (Value::Regex(regex), "exec") => {
+            let text = arg(0);
+            let Some(captures) = regex.captures(text.as_str()) else {
+                return Value::Null;
+            };
+            let names: Vec<&str> = regex.capture_names().flatten().collect();
+            let groups = if names.is_empty() {
+                Value::Undefined
+            } else {
+                Value::object(names.into_iter().map(|name| {
+                    let value = captures
+                        .name(name)
+                        .map_or(Value::Undefined, |value| Value::Str(value.as_str().into()));
+                    (name.replace(GROUP_DOLLAR, "$"), value)
+                }))
+            };
+            Value::object(vec![("groups".to_string(), groups)])
+        }
+        (Value::Object(_), method) => call(&member(&object, method), args),
+        (object, method) => panic!("Can't call {} of {:?}", method, object),
+    }
+}
+
+/// Like the manifest build of the core, without the origins
+fn build_node(
+    id: NodeId,
+    container: &OptimizedTreeContainer,
+    context: &CompileContext,
+    options: &CloudOptions,
+) -> Result<CloudManifestUpdate> {
+    let node = &container.nodes[id];
+    let mut updates = CloudManifestUpdate::new();
+    let mut build = |id: NodeId| -> Result<Vec<Stmt>> {
+        let mut children_update = build_node(id, container, context, options)?;
+        let statements = children_update.take_statements().unwrap_or_default();
+        updates.merge(&children_update)?;
+        Ok(statements)
+    };
+
+    let mut static_children = Vec::new();
+    for (pathname, id) in node.static_children.iter() {
+        static_children.push((pathname.clone(), build(*id)?));
+    }
+
+    let mut children = Vec::new();
+    for (_, id) in node.dynamic_children.iter() {
+        children.extend(build(*id)?);
+    }
+
+    let mut dynamic_child = Vec::new();
+    let dynamic_ids = node
+        .partial_dynamic
+        .iter()
+        .map(|(id, _)| *id)
+        .chain(node.constrained_dynamic.iter().map(|(id, _, _)| *id))
+        .chain(node.dynamic.iter().map(|(id, _)| *id))
+        .chain(node.catch_all.iter().map(|(id, _)| *id));
+    for id in dynamic_ids {
+        dynamic_child.extend(build(id)?);
+    }
+
+    let leaf = node.into_leaf(container);
+    let mut update = cloud_manifest(
+        leaf,
+        static_children,
+        children,
+        dynamic_child,
+        context,
+        options,
+    )?;
+    let content = update.take_statements();
+    updates.merge(&update)?;
+
+    Ok(match content {
+        Some(content) => updates.set_statements(content),
+        None => updates,
+    })
+}
+
+/// The manifest function of the strategy, after its module statements ran
+struct Manifest {
+    body: Vec<Stmt>,
+    scope: Rc<Scope>,
+}
+
+impl Manifest {
+    fn new(container: &OptimizedTreeContainer, strategy: &str) -> Manifest {
+        let context = CompileContext {
+            output_dir: "OUTPUT".into(),
+            cwd: ".".into(),
+            verbose: false,
+            mode: CompileMode::Development,
+        };
+        let mut options = CloudOptions::default();
+        options.insert(
+            "strategy".into(),
+            CloudDependencyOption::String(strategy.into()),
+        );
+
+        let mut manifest = cloud_before_manifest(&context, &options).unwrap();
+        let root = container.get_root_id().unwrap();
+        let mut root = build_node(root, container, &context, &options).unwrap();
+        let body = root.take_statements().unwrap_or_default();
+        manifest.merge(&root).unwrap();
+
+        let scope = Rc::new(Scope::default());
+        match exec_all(manifest.hoisted(), &scope) {
+            Flow::Next => {}
+            _ => panic!("The module statements of {:?} don't end", strategy),
+        }
+
+        Manifest { body, scope }
+    }
+
+    /// (file, params, middlewares) of the route, like the dev runtime reads it
+    fn resolve(&self, path: &str) -> Option<(String, BTreeMap<String, String>, Vec<String>)> {
+        let mut segments: Vec<&str> = path.split('/').skip(1).collect();
+        if segments.last() == Some(&"") {
+            segments.pop();
+        }
+
+        let params = Value::Map(Rc::default());
+        let accumulator = Value::object(vec![
+            (
+                "segments".to_string(),
+                Value::array(segments.iter().map(|s| Value::Str(s.to_string())).collect()),
+            ),
+            ("path".to_string(), Value::Str(segments.join("/"))),
+        ]);
+        let req = Value::object(vec![
+            ("params".to_string(), params.clone()),
+            ("__accumulator__".to_string(), accumulator),
+        ]);
+
+        let scope = Scope::child(&self.scope);
+        scope.declare("req", req);
+        let result = match exec_all(&self.body, &scope) {
+            Flow::Return(Value::Null) => return None,
+            Flow::Return(result) => result,
+            _ => panic!("The manifest doesn't return"),
+        };
+
+        let Value::Map(params) = params else {
+            unreachable!()
+        };
+        let params = params
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_js_string()))
+            .collect();
+        let middlewares = match member(&result, "middlewares") {
+            Value::Array(items) => items.borrow().iter().map(Value::to_js_string).collect(),
+            other => panic!("Expected the middlewares, got {:?}", other),
+        };
+
+        Some((
+            member(&result, "controller").to_js_string(),
+            params,
+            middlewares,
+        ))
+    }
+}
+
+fn build_tree(files: &[&str]) -> OptimizedTreeContainer {
+    let mut container = OptimizedTreeContainer::new("OUTPUT");
+    container.create_root();
+    let mut resolve =
+        |file: CloudFile| cloud_file_resolve(file, HttpRouterContext::default().to_raw());
+
+    for file in files {
+        let cloud_file = CloudFile::new(format!("FILE/{}", file), file, format!("OUTPUT/{}", file));
+        container.insert_file(cloud_file, &mut resolve).unwrap();
+    }
+    assert_eq!(container.get_conflicts().count(), 0);
+
+    container
+}
+
+#[test]
+fn strategies_match_the_same_routes() {
+    let container = build_tree(&[
+        "_index.ts",
+        "_middleware.ts",
+        "about.ts",
+        "users/_index.ts",
+        "users/_middleware.ts",
+        "users/new.ts",
+        "users/$id:int.ts",
+        "users/$id:int/edit.ts",
+        "users/$name.ts",
+        "items/$id:uuid.ts",
+        "posts/$slug:[a-z-]+.ts",
+        "posts/$slug:[a-z-]+/comments.ts",
+        "reports/report-$year:int.csv.ts",
+        "reports/$name.ts",
+        "docs/$page?.ts",
+        "files/$...path.ts",
+        "files/readme.ts",
+        "a/b/$c.ts",
+        "a/$d/e.ts",
+        "$lang/home.ts",
+        "(admin)/_middleware.ts",
+        "(admin)/dashboard.ts",
+    ]);
+
+    let paths = [
+        "/",
+        "/about",
+        "/about/",
+        "/users",
+        "/users/new",
+        "/users/42",
+        "/users/-7",
+        "/users/42/edit",
+        "/users/bob",
+        "/users/bob/edit",
+        "/items/123e4567-E89B-12d3-a456-426614174000",
+        "/items/123e4567",
+        "/posts/hello-world",
+        "/posts/hello-world/comments",
+        "/posts/Hello",
+        "/posts/hello/world",
+        "/reports/report-2024.csv",
+        "/reports/report-x.csv",
+        "/reports/report-.csv",
+        "/docs",
+        "/docs/intro",
+        "/docs/a/b",
+        "/files",
+        "/files/readme",
+        "/files/a/b/c",
+        "/a/b/1",
+        "/a/2/e",
+        "/a/b/e",
+        "/en/home",
+        "/dashboard",
+        "/missing",
+        "/missing/route",
+    ];
+
+    let manifests: Vec<(&str, Manifest)> = ["tree", "switch", "map", "regex"]
+        .iter()
+        .map(|strategy| (*strategy, Manifest::new(&container, strategy)))
+        .collect();
+
+    for path in paths {
+        let expected = container.match_path(path).map(|route| {
+            let middlewares = route.thorns.get("middleware").cloned().unwrap_or_default();
+            (route.file, route.params, middlewares)
+        });
+
+        for (strategy, manifest) in manifests.iter() {
+            assert_eq!(
+                manifest.resolve(path),
+                expected,
+                "{} with the {:?} strategy",
+                path,
+                strategy
+            );
+        }
+    }
+}
@@ -0,0 +1,482 @@
+//! The insert of the tree before the arena, copied from the baseline
+//! (`optimized_tree/{container,node}.rs` and the core `optimized_tree_strategy`)
+//! to compare both with the same routes. Every node is behind its own
+//! `Arc<RwLock>` and the leaf `index` is carried by a `.dummy` node.
+//!
+//! Only the insert is kept, the logs and the display are removed.
+
+use std::{
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use densky_adapter::{utils::StringStripExtend, AHashMap};
+
+type AsyncNode<T> = Arc<RwLock<T>>;
+
+static GLOBAL_NEXT_NODE_ID: AtomicU64 = AtomicU64::new(0);
+
+fn next_node_id() -> u64 {
+    GLOBAL_NEXT_NODE_ID.fetch_add(1, atomic::Ordering::Relaxed) + 1
+}
+
+/// The resolves that the baseline knows, the catch-alls didn't exist yet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resolve {
+    Pass,
+    Index,
+    SingleThorn(&'static str),
+    /// `(prefix, varname, suffix)`
+    Dynamic(String, String, String),
+}
+
+enum InsertResult {
+    None,
+    Resolve { new_parent: u64, new_suffix: String },
+    RemoveNode,
+    MergeNodes { new_node: u64, new_suffix: String },
+}
+
+// Some fields are only written, they are kept so the nodes have the same size
+#[allow(dead_code)]
+#[derive(Clone, Debug, Default)]
+pub struct LockedNode {
+    id: u64,
+    pathname: String,
+    relative_pathname: String,
+    input_path: Option<PathBuf>,
+    output_path: Option<PathBuf>,
+    static_children: AHashMap<String, u64>,
+    dynamic_children: AHashMap<String, u64>,
+    index: Option<u64>,
+    dynamic: Option<(u64, String)>,
+    is_root: bool,
+    is_static: bool,
+    varname: Option<String>,
+}
+
+impl Hash for LockedNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.id);
+    }
+}
+
+struct SingleContainer<T> {
+    inner: AHashMap<u64, AsyncNode<T>>,
+}
+
+impl<T: Hash> SingleContainer<T> {
+    fn new() -> Self {
+        SingleContainer {
+            inner: AHashMap::new(),
+        }
+    }
+
+    fn hash(&self, x: impl Hash) -> u64 {
+        self.inner.hasher().hash_one(x)
+    }
+
+    fn add(&mut self, x: T) -> u64 {
+        let id = self.hash(&x);
+        let is_overwriting = self.inner.insert(id, Arc::new(RwLock::new(x))).is_some();
+        if is_overwriting {
+            panic!("Nodes should be unique. Overwriting: {}", id);
+        }
+        id
+    }
+
+    fn get(&self, id: u64) -> Option<AsyncNode<T>> {
+        self.inner.get(&id).cloned()
+    }
+
+    fn get_reader(&self, id: u64) -> Option<RwLockReadGuard<'_, T>> {
+        self.inner.get(&id).and_then(|x| x.read().ok())
+    }
+
+    fn get_writer(&self, id: u64) -> Option<RwLockWriteGuard<'_, T>> {
+        self.inner.get(&id).and_then(|x| x.write().ok())
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.inner.remove(&id);
+    }
+}
+
+pub struct LockedTreeContainer {
+    nodes: SingleContainer<LockedNode>,
+    single_thorn: AHashMap<String, AHashMap<String, u64>>,
+    root: AsyncNode<LockedNode>,
+}
+
+impl LockedTreeContainer {
+    pub fn new(output_dir: &str) -> Self {
+        let mut nodes = SingleContainer::new();
+        let root = nodes.add(LockedNode {
+            id: next_node_id(),
+            output_path: Some(PathBuf::from(output_dir).join("_index")),
+            is_root: true,
+            ..Default::default()
+        });
+        let root = nodes.get(root).unwrap();
+
+        LockedTreeContainer {
+            nodes,
+            single_thorn: AHashMap::new(),
+            root,
+        }
+    }
+
+    /// `path` is the relative path without the extension
+    pub fn insert_file(
+        &mut self,
+        path: &str,
+        file_path: &str,
+        output_path: &str,
+        resolve: &impl Fn(&str) -> Resolve,
+    ) {
+        let dummy_leaf = LockedNode::new(
+            path.to_string() + ".dummy",
+            Some(file_path.into()),
+            output_path.into(),
+        );
+        let mut leaf =
+            LockedNode::new(path.to_string(), Some(file_path.into()), output_path.into());
+        let dummy_leaf = self.nodes.add(dummy_leaf);
+        leaf.index = Some(dummy_leaf);
+
+        let leaf = self.nodes.add(leaf);
+
+        let root = self.root.clone();
+        let mut next_iter = root.write().unwrap().insert(leaf, resolve(path), self);
+
+        loop {
+            match next_iter {
+                InsertResult::Resolve {
+                    new_parent: parent,
+                    new_suffix: suffix,
+                }
+                | InsertResult::MergeNodes {
+                    new_node: parent,
+                    new_suffix: suffix,
+                } => {
+                    self.nodes.get_writer(leaf).unwrap().relative_pathname = suffix.clone();
+                    let resolved = resolve(&suffix);
+
+                    let parent = self.nodes.get(parent).expect("Node not found");
+                    next_iter = parent.write().unwrap().insert(leaf, resolved, self);
+                }
+                InsertResult::RemoveNode => {
+                    self.nodes.remove(leaf);
+                    break;
+                }
+                InsertResult::None => break,
+            }
+        }
+    }
+}
+
+impl LockedNode {
+    fn new(relative_pathname: String, input_path: Option<PathBuf>, output_path: PathBuf) -> Self {
+        LockedNode {
+            id: next_node_id(),
+            pathname: relative_pathname.clone(),
+            relative_pathname,
+            input_path,
+            output_path: Some(output_path),
+            ..Default::default()
+        }
+    }
+
+    fn new_child(
+        parent_pathname: &String,
+        relative_pathname: String,
+        output_path: Option<PathBuf>,
+    ) -> Self {
+        let pathname = parent_pathname.to_owned()
+            + "/"
+            + relative_pathname.strip_prefix_if_can(parent_pathname);
+        LockedNode {
+            id: next_node_id(),
+            pathname: pathname.strip_prefix_if_can("/").to_string(),
+            relative_pathname,
+            output_path,
+            ..Default::default()
+        }
+    }
+
+    fn insert(
+        &mut self,
+        leaf_id: u64,
+        file_resolved: Resolve,
+        container: &mut LockedTreeContainer,
+    ) -> InsertResult {
+        let leaf_rw = container
+            .nodes
+            .get(leaf_id)
+            .expect("New leaf should be inserted on container before use it");
+
+        let leaf = leaf_rw.read().unwrap();
+
+        match file_resolved {
+            Resolve::Pass => {
+                let static_pathname = leaf.relative_pathname.strip_prefix_if_can("/").to_string();
+
+                drop(leaf);
+                let mut leaf = leaf_rw.write().unwrap();
+                leaf.relative_pathname = static_pathname.clone();
+                leaf.is_static = true;
+                leaf.varname = None;
+
+                self.static_children.insert(static_pathname, leaf_id);
+
+                InsertResult::None
+            }
+            Resolve::Dynamic(prefix, varname, suffix) => {
+                if prefix.is_empty() {
+                    return if suffix.is_empty() {
+                        self.dynamic = Some((leaf_id, varname));
+                        self.index = leaf.index;
+                        InsertResult::None
+                    } else {
+                        let mut cloned_leaf =
+                            LockedNode::new_child(&self.pathname, varname.clone(), None);
+                        cloned_leaf.is_static = false;
+                        cloned_leaf.varname = Some(varname.clone());
+
+                        let cloned_leaf = container.nodes.add(cloned_leaf);
+                        self.dynamic = Some((cloned_leaf, varname));
+                        InsertResult::Resolve {
+                            new_parent: cloned_leaf,
+                            new_suffix: suffix,
+                        }
+                    };
+                }
+
+                // Search for a common child in the children
+                let common_child: Option<(&u64, Option<String>)> = 'common_child: {
+                    if let Some(new_parent) = self.dynamic_children.get(&prefix) {
+                        Some((new_parent, None))
+                    } else {
+                        if let Some(child) = self.dynamic_children.iter().next() {
+                            break 'common_child get_common_path(&prefix, child.0).map(
+                                |common_path| {
+                                    (
+                                        child.1,
+                                        if &common_path == child.0 {
+                                            None
+                                        } else {
+                                            (common_path != prefix).then_some(common_path)
+                                        },
+                                    )
+                                },
+                            );
+                        }
+                        None
+                    }
+                };
+
+                match common_child {
+                    // Has a common child and needs a merge
+                    Some((common_id, Some(common_path))) => {
+                        let common_child = container.nodes.get_reader(*common_id).unwrap();
+
+                        let old_common_child_pathname = common_child.relative_pathname.clone();
+                        let new_common_child_pathname = common_child
+                            .relative_pathname
+                            .strip_prefix(&common_path)
+                            .unwrap()
+                            .strip_prefix_if_can("/")
+                            .to_string();
+
+                        drop(common_child);
+
+                        container
+                            .nodes
+                            .get_writer(*common_id)
+                            .unwrap()
+                            .relative_pathname = new_common_child_pathname.to_owned();
+
+                        let new_leaf_pathname = leaf
+                            .relative_pathname
+                            .strip_prefix(&common_path)
+                            .unwrap()
+                            .strip_prefix_if_can("/")
+                            .to_string();
+
+                        let mut new_parent =
+                            LockedNode::new_child(&self.pathname, common_path.clone(), None);
+                        new_parent.is_static = false;
+
+                        new_parent
+                            .dynamic_children
+                            .insert(new_common_child_pathname, *common_id);
+
+                        let new_parent = container.nodes.add(new_parent);
+
+                        self.dynamic_children.insert(common_path, new_parent);
+                        self.dynamic_children
+                            .remove(&old_common_child_pathname)
+                            .expect("Child doesn't exist?");
+
+                        InsertResult::MergeNodes {
+                            new_node: new_parent,
+                            new_suffix: new_leaf_pathname,
+                        }
+                    }
+                    // Has a common child, resolve to that child as the new parent
+                    Some((common_id, None)) => {
+                        let new_parent = container.nodes.get_writer(*common_id).unwrap();
+
+                        if let Some(old_leaf_id) = &new_parent.dynamic {
+                            if suffix.is_empty() {
+                                InsertResult::None
+                            } else {
+                                InsertResult::Resolve {
+                                    new_parent: old_leaf_id.0,
+                                    new_suffix: suffix,
+                                }
+                            }
+                        } else {
+                            let prefix = leaf
+                                .relative_pathname
+                                .strip_prefix_if_can(&new_parent.relative_pathname)
+                                .strip_prefix_if_can("/")
+                                .to_string();
+
+                            InsertResult::Resolve {
+                                new_parent: *common_id,
+                                new_suffix: prefix,
+                            }
+                        }
+                    }
+                    // Insert it as normal node
+                    None => {
+                        let mut new_parent =
+                            LockedNode::new_child(&self.pathname, prefix.clone(), None);
+                        new_parent.is_static = false;
+
+                        let cloned_leaf = if suffix.is_empty() {
+                            new_parent.dynamic = Some((leaf_id, varname));
+                            None
+                        } else {
+                            let mut cloned_leaf =
+                                LockedNode::new_child(&new_parent.pathname, varname.clone(), None);
+                            cloned_leaf.is_static = false;
+                            cloned_leaf.varname = Some(varname.clone());
+                            let cloned_leaf = container.nodes.add(cloned_leaf);
+                            new_parent.dynamic = Some((cloned_leaf, varname));
+
+                            Some(cloned_leaf)
+                        };
+
+                        drop(leaf);
+                        let mut leaf = container.nodes.get_writer(leaf_id).unwrap();
+                        leaf.relative_pathname = leaf
+                            .relative_pathname
+                            .strip_prefix_if_can(&prefix)
+                            .strip_prefix_if_can("/")
+                            .to_string();
+                        drop(leaf);
+
+                        let new_parent = container.nodes.add(new_parent);
+                        self.dynamic_children.insert(prefix, new_parent);
+
+                        if let Some(cloned_leaf) = cloned_leaf {
+                            InsertResult::Resolve {
+                                new_parent: cloned_leaf,
+                                new_suffix: suffix,
+                            }
+                        } else {
+                            InsertResult::None
+                        }
+                    }
+                }
+            }
+            Resolve::Index => {
+                let last_slash = leaf
+                    .pathname
+                    .chars()
+                    .rev()
+                    .position(|x| x == '/')
+                    .map(|x| leaf.relative_pathname.len() - x);
+
+                let last_slash = match last_slash {
+                    Some(expr) if expr > 2 => expr - 1,
+                    _ => {
+                        self.index =
+                            Some(leaf.index.expect("Inserted leaf should have 'index' field"));
+                        return InsertResult::RemoveNode;
+                    }
+                };
+
+                let parent = leaf.pathname[0..last_slash].to_string();
+                if parent.is_empty() {
+                    self.index = Some(leaf.index.expect("Inserted leaf should have 'index' field"));
+                    return InsertResult::RemoveNode;
+                }
+
+                drop(leaf);
+                container.nodes.get_writer(leaf_id).unwrap().pathname = parent.clone();
+
+                InsertResult::Resolve {
+                    new_parent: container.nodes.hash(&*self),
+                    new_suffix: parent,
+                }
+            }
+            Resolve::SingleThorn(name) => {
+                let path = &leaf.pathname;
+                let last_slash = path
+                    .chars()
+                    .rev()
+                    .position(|f| f == '/')
+                    .map(|f| path.len() - f - 1)
+                    .filter(|f| f > &0);
+
+                let path = if let Some(last_slash) = last_slash {
+                    &path[0..last_slash]
+                } else {
+                    path
+                };
+
+                container
+                    .single_thorn
+                    .entry(path.to_string())
+                    .or_default()
+                    .insert(name.into(), leaf_id);
+                InsertResult::None
+            }
+        }
+    }
+}
+
+fn get_common_path(path_a: &str, path_b: &str) -> Option<String> {
+    let mut carrier = String::new();
+
+    for segment in path_b.split('/') {
+        if segment.is_empty() {
+            continue;
+        }
+
+        let is_first = carrier.is_empty();
+        let next = if is_first {
+            segment.to_owned()
+        } else {
+            format!("{carrier}/{segment}")
+        };
+
+        if !path_a.starts_with(&next) {
+            return if is_first { None } else { Some(carrier) };
+        }
+
+        if !is_first {
+            carrier += "/";
+        }
+        carrier += segment;
+    }
+
+    Some(path_b.to_string())
+}
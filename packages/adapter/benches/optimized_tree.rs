@@ -0,0 +1,140 @@
+#[macro_use]
+extern crate criterion;
+extern crate densky_adapter;
+
+mod locked_tree;
+
+use criterion::{BenchmarkId, Criterion};
+use densky_adapter::{optimized_tree::OptimizedTreeContainer, CloudFile, CloudFileResolve};
+use locked_tree::LockedTreeContainer;
+
+/// `n` routes split in sections of 100, with indexes, middlewares, params,
+/// nested params and catch-alls.
+fn routes(n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| {
+            let (section, j) = (i / 100, i % 100);
+            match j {
+                0 => format!("s{section}/_index.ts"),
+                1 => format!("s{section}/_middleware.ts"),
+                2 => format!("s{section}/$id.ts"),
+                3 => format!("s{section}/$id/edit.ts"),
+                4 => format!("s{section}/files/$...path.ts"),
+                _ if j % 3 == 0 => format!("s{section}/page{j}.ts"),
+                _ if j % 3 == 1 => format!("s{section}/g{j}/$slug.ts"),
+                _ => format!("s{section}/g{j}/$slug/view.ts"),
+            }
+        })
+        .collect()
+}
+
+/// A small version of the http-router resolver
+fn resolve(file: CloudFile) -> CloudFileResolve {
+    let path = file.relative_path.trim_end_matches(".ts").to_string();
+    let segments: Vec<&str> = path.split('/').collect();
+
+    match *segments.last().unwrap() {
+        "_index" => return CloudFileResolve::Index,
+        "_middleware" => return CloudFileResolve::SingleThorn("middleware"),
+        _ => {}
+    }
+
+    let Some(dynamic) = segments.iter().position(|s| s.starts_with('$')) else {
+        return CloudFileResolve::Pass;
+    };
+    let prefix = segments[..dynamic].join("/");
+    let suffix = segments[dynamic + 1..].join("/");
+    let varname = segments[dynamic].to_string();
+
+    if varname.starts_with("$...") {
+        return CloudFileResolve::CatchAll(prefix, varname);
+    }
+    CloudFileResolve::Dynamic(prefix, varname, suffix, None)
+}
+
+fn build(files: &[String]) -> OptimizedTreeContainer {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    for file in files {
+        let cloud_file = CloudFile::new(format!("FILE/{file}"), file, format!("OUTPUT/{file}"));
+        container
+            .insert_file(cloud_file, &mut |file| Ok(resolve(file)))
+            .unwrap();
+    }
+    container
+}
+
+/// The resolver of `resolve` with the variants of the baseline, the
+/// catch-alls are plain dynamic segments there.
+fn resolve_locked(path: &str) -> locked_tree::Resolve {
+    let cloud_file = CloudFile::new("", path, "");
+    match resolve(cloud_file) {
+        CloudFileResolve::Index => locked_tree::Resolve::Index,
+        CloudFileResolve::SingleThorn(name) => locked_tree::Resolve::SingleThorn(name),
+        CloudFileResolve::Dynamic(prefix, varname, suffix, _) => {
+            locked_tree::Resolve::Dynamic(prefix, varname, suffix)
+        }
+        CloudFileResolve::CatchAll(prefix, varname) => {
+            locked_tree::Resolve::Dynamic(prefix, varname, String::new())
+        }
+        _ => locked_tree::Resolve::Pass,
+    }
+}
+
+fn build_locked(files: &[String]) -> LockedTreeContainer {
+    let mut container = LockedTreeContainer::new("OUTPUT_DIR");
+    for file in files {
+        let path = file.trim_end_matches(".ts");
+        container.insert_file(
+            path,
+            &format!("FILE/{file}"),
+            &format!("OUTPUT/{file}"),
+            &resolve_locked,
+        );
+    }
+    container
+}
+
+/// The arena against the `Arc<RwLock>` per node of the baseline
+/// (`locked_tree`), both with the same routes.
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    group.sample_size(10);
+    for n in [1_000, 10_000] {
+        let files = routes(n);
+        group.bench_with_input(BenchmarkId::new("arena", n), &files, |b, files| {
+            b.iter(|| build(files))
+        });
+        group.bench_with_input(BenchmarkId::new("locked", n), &files, |b, files| {
+            b.iter(|| build_locked(files))
+        });
+    }
+    group.finish();
+}
+
+fn match_path(c: &mut Criterion) {
+    let container = build(&routes(10_000));
+    let urls: Vec<String> = (0..100)
+        .flat_map(|section| {
+            vec![
+                format!("/s{section}"),
+                format!("/s{section}/42/edit"),
+                format!("/s{section}/page30"),
+                format!("/s{section}/g31/hello"),
+                format!("/s{section}/files/a/b/c"),
+                format!("/s{section}/missing/route"),
+            ]
+        })
+        .collect();
+
+    c.bench_function("match_path/10000", |b| {
+        b.iter(|| {
+            urls.iter()
+                .filter(|url| container.match_path(url).is_some())
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, insert, match_path);
+criterion_main!(benches);
@@ -1,7 +1,15 @@
-use std::ops;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Peekable;
 use std::path::PathBuf;
+use std::str::Chars;
 
-use ahash::AHashMap;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::js::{self, Expr, ImportSpecifier, Imports, Origin, Stmt};
 
 /// This is the minimum unit for a Optimized Tree.
 /// This is used for transport basic data like file paths (i/o)
@@ -14,14 +22,18 @@ pub struct OptimizedTreeLeaf {
     pub index: Option<String>,
 
     /// Map<Name, Vec<FilePath>>
-    pub single_thorns: AHashMap<String, Vec<String>>,
+    pub single_thorns: BTreeMap<String, Vec<String>>,
 
     pub is_root: bool,
     pub is_static: bool,
+    pub is_catch_all: bool,
+    pub is_optional: bool,
     pub varname: Option<String>,
+    pub constraint: Option<ParamConstraint>,
+    pub pattern: Option<SegmentPattern>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudFile {
     pub file_path: PathBuf,
     pub relative_path: String,
@@ -42,7 +54,397 @@ impl CloudFile {
     }
 }
 
-#[derive(Default, Debug, PartialEq, Eq)]
+/// Constraint of a dynamic param. A constrained param is tried before the
+/// unconstrained one, and the route falls through when it doesn't match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ParamConstraint {
+    /// `$id:int`
+    Int,
+    /// `$id:uuid`
+    Uuid,
+    /// `$slug:[a-z0-9-]+`
+    Regex(ConstraintRegex),
+}
+
+impl ParamConstraint {
+    /// Parse the constraint part of a param (the text after `:`). A name is
+    /// a type, so it fails when it isn't known, and anything else should be
+    /// a regex of the subset that [`ConstraintRegex`] accepts.
+    /// ```ignore
+    /// assert_eq!(ParamConstraint::parse("int")?, Some(ParamConstraint::Int));
+    /// assert_eq!(ParamConstraint::parse("")?, None);
+    /// assert!(ParamConstraint::parse("itn").is_err());
+    /// ```
+    pub fn parse(constraint: impl AsRef<str>) -> anyhow::Result<Option<ParamConstraint>> {
+        let constraint = constraint.as_ref();
+        let is_name = constraint
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        match constraint {
+            "" => Ok(None),
+            "int" => Ok(Some(ParamConstraint::Int)),
+            "uuid" => Ok(Some(ParamConstraint::Uuid)),
+            name if is_name => Err(anyhow::anyhow!(
+                "Unknown param constraint {name:?}, it should be \"int\", \"uuid\" or a regex"
+            )),
+            regex => Ok(Some(ParamConstraint::Regex(ConstraintRegex::new(regex)?))),
+        }
+    }
+
+    /// Check the value of the param, the same as the generated manifest
+    pub fn is_match(&self, value: &str) -> bool {
+        match self {
+            ParamConstraint::Int => {
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+            }
+            ParamConstraint::Uuid => {
+                let parts: Vec<&str> = value.split('-').collect();
+                parts.len() == 5
+                    && parts.iter().zip([8, 4, 4, 4, 12]).all(|(part, len)| {
+                        part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit())
+                    })
+            }
+            ParamConstraint::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+impl fmt::Display for ParamConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamConstraint::Int => f.write_str("int"),
+            ParamConstraint::Uuid => f.write_str("uuid"),
+            ParamConstraint::Regex(regex) => f.write_str(regex.as_str()),
+        }
+    }
+}
+
+/// Regex of a param constraint, it's compiled once when it's parsed. It's
+/// compared, hashed and saved by its source.
+///
+/// The same source is checked here (the tree matcher) and on the generated
+/// JS, so only a subset that matches the same on both engines is accepted:
+/// + ASCII literals, and `\` before a meta char (`\.`, `\-`, `\(`, ...)
+/// + Classes with ASCII chars and ranges (`[a-z0-9_-]`), `\d` and `\w`
+/// + Groups `(...)` and `(?:...)`, and alternations `a|b`
+/// + Quantifiers `*`, `+`, `?` and `{n}`, `{n,}`, `{n,m}`, also lazy
+///
+/// Anything else is rejected: `.` and negated classes (they match `/`),
+/// anchors, named groups, flags, lookarounds, `\s`, `\p{..}`, POSIX classes
+/// (`[[:alpha:]]`) and class set operations (`[a-z--x]`). A constraint
+/// never matches `/`, so the param stays on its segment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ConstraintRegex {
+    source: String,
+    /// Matches the whole value: `^(?:source)$`
+    regex: Regex,
+}
+
+impl ConstraintRegex {
+    pub fn new(source: impl Into<String>) -> anyhow::Result<ConstraintRegex> {
+        let source = source.into();
+        check_portable_regex(&source)
+            .map_err(|err| anyhow::anyhow!("Invalid param constraint {source:?}: {err}"))?;
+
+        // Without unicode `\d` and `\w` are ASCII, like on JS
+        let regex = RegexBuilder::new(&format!("^(?:{source})$"))
+            .unicode(false)
+            .build()
+            .map_err(|err| anyhow::anyhow!("Invalid param constraint {source:?}: {err}"))?;
+
+        Ok(ConstraintRegex { source, regex })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+}
+
+/// Chars that can be escaped, the other escapes differ between engines
+const ESCAPABLE: &str = "\\.+*?()|[]{}^$-";
+
+/// Check that the regex is on the subset of [`ConstraintRegex`]. The syntax
+/// errors (like an unclosed group) are left to the regex parser.
+fn check_portable_regex(source: &str) -> anyhow::Result<()> {
+    use anyhow::anyhow;
+
+    let mut chars = source.chars().peekable();
+    // The previous token can be repeated
+    let mut repeatable = false;
+
+    while let Some(c) = chars.next() {
+        if !c.is_ascii() || c.is_ascii_control() {
+            return Err(anyhow!("only ASCII chars are allowed, found {c:?}"));
+        }
+
+        match c {
+            '\\' => match chars.next() {
+                Some('d' | 'w') => {}
+                Some(escaped) if ESCAPABLE.contains(escaped) => {}
+                Some(escaped) => return Err(anyhow!("the escape \"\\{escaped}\" isn't supported")),
+                None => return Err(anyhow!("it ends with \"\\\"")),
+            },
+            '[' => check_portable_class(&mut chars)?,
+            '(' => {
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    if chars.next() != Some(':') {
+                        return Err(anyhow!(
+                            "only \"(...)\" and \"(?:...)\" groups are allowed, not named groups, flags or lookarounds"
+                        ));
+                    }
+                }
+                repeatable = false;
+                continue;
+            }
+            '|' => {
+                repeatable = false;
+                continue;
+            }
+            '*' | '+' | '?' | '{' => {
+                if !repeatable {
+                    return Err(anyhow!("\"{c}\" doesn't have something to repeat"));
+                }
+                if c == '{' {
+                    check_portable_repetition(&mut chars)?;
+                }
+                // Lazy
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                }
+                repeatable = false;
+                continue;
+            }
+            '.' => return Err(anyhow!("\".\" matches \"/\", use a class like \"[a-z]\"")),
+            '^' | '$' => {
+                return Err(anyhow!(
+                    "anchors aren't allowed, the constraint always matches the whole param"
+                ))
+            }
+            '/' => return Err(anyhow!("\"/\" can't be part of a param")),
+            ']' | '}' => return Err(anyhow!("\"{c}\" should be escaped")),
+            _ => {}
+        }
+
+        repeatable = true;
+    }
+
+    Ok(())
+}
+
+/// The chars of a class, after `[`
+fn check_portable_class(chars: &mut Peekable<Chars<'_>>) -> anyhow::Result<()> {
+    use anyhow::anyhow;
+
+    match chars.peek() {
+        Some('^') => return Err(anyhow!("negated classes match \"/\"")),
+        Some(']') => return Err(anyhow!("empty classes aren't allowed")),
+        _ => {}
+    }
+
+    // The last char, when it can start a range
+    let mut range_start: Option<char> = None;
+    let mut first = true;
+
+    while let Some(c) = chars.next() {
+        let is_first = std::mem::replace(&mut first, false);
+
+        if !c.is_ascii() || c.is_ascii_control() {
+            return Err(anyhow!("only ASCII chars are allowed, found {c:?}"));
+        }
+
+        let item = match c {
+            ']' => return Ok(()),
+            '[' => {
+                return Err(anyhow!(
+                    "nested classes like \"[[:alpha:]]\" aren't allowed"
+                ))
+            }
+            '&' | '~' if chars.peek() == Some(&c) => {
+                return Err(anyhow!(
+                    "class set operations like \"{c}{c}\" aren't allowed"
+                ))
+            }
+            '/' => return Err(anyhow!("\"/\" can't be part of a param")),
+            '\\' => match chars.next() {
+                Some('d' | 'w') => None,
+                Some(escaped) if ESCAPABLE.contains(escaped) => Some(escaped),
+                Some(escaped) => return Err(anyhow!("the escape \"\\{escaped}\" isn't supported")),
+                None => return Err(anyhow!("it ends with \"\\\"")),
+            },
+            // A range, or a literal at the start or the end of the class
+            '-' if !is_first && chars.peek() != Some(&']') => {
+                if chars.peek() == Some(&'-') {
+                    return Err(anyhow!("class set operations like \"--\" aren't allowed"));
+                }
+                let Some(start) = range_start.take() else {
+                    return Err(anyhow!(
+                        "\"-\" should be escaped or at the end of the class"
+                    ));
+                };
+                let end = match chars.next() {
+                    Some('\\') => chars.next().filter(|c| ESCAPABLE.contains(*c)),
+                    Some(end)
+                        if end.is_ascii() && !end.is_ascii_control() && !"[]".contains(end) =>
+                    {
+                        Some(end)
+                    }
+                    _ => None,
+                };
+                let Some(end) = end else {
+                    return Err(anyhow!("the range after {start:?} should end with a char"));
+                };
+                if start <= '/' && '/' <= end {
+                    return Err(anyhow!("the range \"{start}-{end}\" matches \"/\""));
+                }
+                continue;
+            }
+            c => Some(c),
+        };
+
+        range_start = item;
+    }
+
+    Err(anyhow!("the class isn't closed"))
+}
+
+/// `{n}`, `{n,}` or `{n,m}`, after `{`
+fn check_portable_repetition(chars: &mut Peekable<Chars<'_>>) -> anyhow::Result<()> {
+    let mut digits = 0;
+    let mut commas = 0;
+
+    for c in chars.by_ref() {
+        match c {
+            '0'..='9' => digits += 1,
+            ',' if digits > 0 && commas == 0 => commas += 1,
+            '}' if digits > 0 => return Ok(()),
+            _ => break,
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "\"{{\" should be a repetition like \"{{2}}\", \"{{2,}}\" or \"{{2,4}}\", or be escaped"
+    ))
+}
+
+impl PartialEq for ConstraintRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for ConstraintRegex {}
+
+impl Hash for ConstraintRegex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.hash(state);
+    }
+}
+
+impl fmt::Display for ConstraintRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TryFrom<String> for ConstraintRegex {
+    type Error = anyhow::Error;
+
+    fn try_from(source: String) -> anyhow::Result<ConstraintRegex> {
+        ConstraintRegex::new(source)
+    }
+}
+
+impl From<ConstraintRegex> for String {
+    fn from(regex: ConstraintRegex) -> String {
+        regex.source
+    }
+}
+
+/// A param with static text around it, inside one segment.
+/// ```ignore
+/// // "report-$year.csv"
+/// SegmentPattern {
+///     before: "report-".into(),
+///     varname: "$year".into(),
+///     after: ".csv".into(),
+///     constraint: None,
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SegmentPattern {
+    pub before: String,
+    pub varname: String,
+    pub after: String,
+    pub constraint: Option<ParamConstraint>,
+}
+
+impl SegmentPattern {
+    /// Split a segment like "report-$year:int.csv" into the static text and
+    /// the param. The param name (and its constraint) ends at the first char
+    /// that isn't alphanumeric or `_`.
+    pub fn parse(segment: impl AsRef<str>) -> anyhow::Result<SegmentPattern> {
+        let (before, rest) = segment
+            .as_ref()
+            .split_once('$')
+            .ok_or_else(|| anyhow::anyhow!("The segment doesn't have a param"))?;
+        let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+        let name_len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+        if name_len == 0 {
+            return Err(anyhow::anyhow!("The param doesn't have a name"));
+        }
+        let (name, rest) = rest.split_at(name_len);
+
+        let (constraint, after) = match rest.strip_prefix(':') {
+            Some(rest) => {
+                let constraint_len = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+                let (constraint, after) = rest.split_at(constraint_len);
+                (ParamConstraint::parse(constraint)?, after)
+            }
+            None => (None, rest),
+        };
+
+        if after.contains('$') {
+            return Err(anyhow::anyhow!("Only one param per segment is allowed"));
+        }
+
+        Ok(SegmentPattern {
+            before: before.to_string(),
+            varname: format!("${name}"),
+            after: after.to_string(),
+            constraint,
+        })
+    }
+
+    /// Both patterns match the same segments, the varname doesn't matter
+    pub fn is_equivalent(&self, other: &SegmentPattern) -> bool {
+        self.before == other.before
+            && self.after == other.after
+            && self.constraint == other.constraint
+    }
+}
+
+/// The segment as it's written on the file name: `report-$year:int.csv`
+impl fmt::Display for SegmentPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.before)?;
+        f.write_str(&self.varname)?;
+        if let Some(constraint) = &self.constraint {
+            write!(f, ":{constraint}")?;
+        }
+        f.write_str(&self.after)
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum CloudFileResolve {
     /// Pass through as regular static node
@@ -59,27 +461,119 @@ pub enum CloudFileResolve {
     ///
     /// ## Structure
     /// ```ignore
-    /// CloudFileResolve::Dynamic(prefix, var, suffix)
+    /// CloudFileResolve::Dynamic(prefix, var, suffix, constraint)
     /// ```
     /// # Example
     /// ```ignore
     /// // Ideal fit
     /// let my_pathname = "api/$version/swagger";
-    /// CloudFileResolve::Dynamic("api", "version", "swagger");
+    /// CloudFileResolve::Dynamic("api", "version", "swagger", None);
     ///
     /// // Without prefix
     /// let my_pathname = "$version/swagger";
-    /// CloudFileResolve::Dynamic("", "version", "swagger");
+    /// CloudFileResolve::Dynamic("", "version", "swagger", None);
     ///
     /// // Without suffix
     /// let my_pathname = "api/$version";
-    /// CloudFileResolve::Dynamic("api", "version", "");
+    /// CloudFileResolve::Dynamic("api", "version", "", None);
     ///
     /// // Multi-var
     /// let my_pathname = "api/$version/u/$user";
-    /// CloudFileResolve::Dynamic("api", "version", "u/$user"); // It's recursive
+    /// CloudFileResolve::Dynamic("api", "version", "u/$user", None); // It's recursive
+    ///
+    /// // Constrained, siblings with different constraints can coexist
+    /// let my_pathname = "users/$id:int";
+    /// CloudFileResolve::Dynamic("users", "id", "", Some(ParamConstraint::Int));
+    /// ```
+    Dynamic(String, String, String, Option<ParamConstraint>),
+
+    /// It is used to mark a catch-all (rest) path. It consumes all the
+    /// remaining segments, so it must be the last part of the path.
+    ///
+    /// It's ranked below static and single-segment dynamic children.
+    ///
+    /// ## Structure
+    /// ```ignore
+    /// CloudFileResolve::CatchAll(prefix, var)
+    /// ```
+    /// # Example
+    /// ```ignore
+    /// let my_pathname = "files/$...path";
+    /// CloudFileResolve::CatchAll("files", "$...path");
+    ///
+    /// // Without prefix
+    /// let my_pathname = "$...path";
+    /// CloudFileResolve::CatchAll("", "$...path");
     /// ```
-    Dynamic(String, String, String),
+    CatchAll(String, String),
+
+    /// It is used to mark a param mixed with static text in the same
+    /// segment. It's ranked below static children and above the
+    /// regular dynamic ones.
+    ///
+    /// ## Structure
+    /// ```ignore
+    /// CloudFileResolve::Partial(prefix, pattern, suffix)
+    /// ```
+    /// # Example
+    /// ```ignore
+    /// let my_pathname = "reports/report-$year.csv";
+    /// CloudFileResolve::Partial("reports", SegmentPattern {
+    ///     before: "report-".into(),
+    ///     varname: "$year".into(),
+    ///     after: ".csv".into(),
+    ///     constraint: None,
+    /// }, "");
+    ///
+    /// let my_pathname = "api/v$version/users";
+    /// CloudFileResolve::Partial("api", SegmentPattern {
+    ///     before: "v".into(),
+    ///     varname: "$version".into(),
+    ///     after: "".into(),
+    ///     constraint: None,
+    /// }, "users");
+    /// ```
+    Partial(String, SegmentPattern, String),
+
+    /// It is used to mark an optional dynamic path, it matches with and
+    /// without the var. It must be the last part of the path.
+    ///
+    /// > The parent route can't have an index, so this checks that there's
+    /// > no `_index` (or static file) for the same route, error expected.
+    ///
+    /// ## Structure
+    /// ```ignore
+    /// CloudFileResolve::Optional(prefix, var)
+    /// ```
+    /// # Example
+    /// ```ignore
+    /// // Matches "users" and "users/42"
+    /// let my_pathname = "users/$id?";
+    /// CloudFileResolve::Optional("users", "$id");
+    /// ```
+    Optional(String, String),
+
+    /// It is used to mark a path inside route groups, folders like
+    /// `(admin)` that don't add a URL segment. The route is inserted on the
+    /// same parent with the URL path (the path without groups), but it keeps
+    /// its own pathname, so it's scoped by the thorns of its group.
+    ///
+    /// > Two groups can't define the same URL, error expected.
+    ///
+    /// ## Structure
+    /// ```ignore
+    /// CloudFileResolve::Group(url_path)
+    /// ```
+    /// # Example
+    /// ```ignore
+    /// let my_pathname = "(admin)/users";
+    /// CloudFileResolve::Group("users");
+    ///
+    /// // Index of the group
+    /// let my_pathname = "(admin)";
+    /// CloudFileResolve::Group("");
+    /// ```
+    Group(String),
 
     /// Convert route to a `thorn` (marker) with the provided name.
     ///
@@ -136,131 +630,338 @@ pub enum CloudFileResolve {
     MultiThorn(&'static str),
 }
 
+/// Argument of the manifest function: `name: Type = default`
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestArgument {
+    pub name: String,
+    /// TS type
+    pub def: String,
+    pub default: Option<Expr>,
+    /// Cloud and node that added it, to report conflicts
+    pub origin: Option<Origin>,
+}
+
+impl ManifestArgument {
+    /// Both define the argument in the same way, the origin doesn't matter
+    pub fn is_same(&self, other: &ManifestArgument) -> bool {
+        self.name == other.name && self.def == other.def && self.default == other.default
+    }
+}
+
+impl fmt::Display for ManifestArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.def)?;
+        if let Some(default) = &self.default {
+            write!(f, " = {default}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Ordered collections, so the same updates always emit the same manifest
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
 pub struct CloudManifestUpdate {
-    arguments: AHashMap<String, String>,
-    content: Option<String>,
-    imports: AHashMap<String, String>,
+    /// In declaration order
+    arguments: Vec<ManifestArgument>,
+    content: Option<Vec<Stmt>>,
+    /// Module level statements, before the manifest function. They run
+    /// once, when the manifest is imported.
+    hoisted: Vec<Stmt>,
+    imports: Imports,
 }
 
 impl CloudManifestUpdate {
     pub fn new() -> Self {
-        Self {
-            arguments: AHashMap::new(),
-            content: None,
-            imports: AHashMap::new(),
-        }
+        Self::default()
+    }
+
+    pub fn new_statements(statements: Vec<Stmt>) -> Self {
+        Self::new().set_statements(statements)
     }
 
+    /// Raw TS content, prefer [`CloudManifestUpdate::new_statements`]
     pub fn new_content(content: impl Into<String>) -> Self {
-        Self {
-            arguments: AHashMap::new(),
-            content: Some(content.into()),
-            imports: AHashMap::new(),
-        }
+        Self::new().set_content(content)
     }
 
-    pub fn set_content(mut self, content: impl Into<String>) -> Self {
-        self.content = Some(content.into());
+    pub fn set_statements(mut self, statements: Vec<Stmt>) -> Self {
+        self.content = Some(statements);
         self
     }
 
-    pub fn append_content(mut self, content: impl AsRef<str>) -> Self {
-        match self.content.as_mut() {
-            Some(c) => *c += content.as_ref(),
-            None => self.content = Some(content.as_ref().into()),
-        }
+    /// Raw TS content, prefer [`CloudManifestUpdate::set_statements`]
+    pub fn set_content(self, content: impl Into<String>) -> Self {
+        self.set_statements(vec![Stmt::Raw(content.into())])
+    }
 
+    pub fn append_statements(mut self, statements: impl IntoIterator<Item = Stmt>) -> Self {
+        self.content.get_or_insert_with(Vec::new).extend(statements);
         self
     }
 
-    pub fn add_import(mut self, items: impl Into<String>, path: impl Into<String>) -> Self {
-        self.imports.insert(path.into(), items.into());
+    /// Raw TS content, prefer [`CloudManifestUpdate::append_statements`]
+    pub fn append_content(self, content: impl AsRef<str>) -> Self {
+        self.append_statements(vec![Stmt::Raw(content.as_ref().into())])
+    }
+
+    /// The hoisted statements of the leaves are kept in tree order, the
+    /// children go before their parent
+    pub fn add_hoisted(mut self, statements: impl IntoIterator<Item = Stmt>) -> Self {
+        self.hoisted.extend(statements);
+        self
+    }
+
+    pub fn hoisted(&self) -> &Vec<Stmt> {
+        &self.hoisted
+    }
+
+    /// ```
+    /// use densky_adapter::{js::ImportSpecifier, CloudManifestUpdate};
+    ///
+    /// // import { type HTTPRequest } from "densky/http-router.ts";
+    /// CloudManifestUpdate::new().add_import(
+    ///     ImportSpecifier::named("HTTPRequest").type_only(),
+    ///     "densky/http-router.ts",
+    /// );
+    /// ```
+    pub fn add_import(mut self, specifier: ImportSpecifier, path: impl Into<String>) -> Self {
+        self.imports.add(specifier, path);
         self
     }
 
     pub fn add_argument(mut self, name: impl Into<String>, def: impl Into<String>) -> Self {
-        self.arguments.insert(name.into(), def.into());
+        self.arguments.push(ManifestArgument {
+            name: name.into(),
+            def: def.into(),
+            default: None,
+            origin: None,
+        });
+        self
+    }
+
+    /// `name: def = default`
+    pub fn add_argument_with_default(
+        mut self,
+        name: impl Into<String>,
+        def: impl Into<String>,
+        default: Expr,
+    ) -> Self {
+        self.arguments.push(ManifestArgument {
+            name: name.into(),
+            def: def.into(),
+            default: Some(default),
+            origin: None,
+        });
         self
     }
 
-    pub fn arguments(&self) -> &AHashMap<String, String> {
+    pub fn arguments(&self) -> &Vec<ManifestArgument> {
         &self.arguments
     }
 
-    pub fn content(&self) -> Option<&String> {
+    /// Set the origin of the arguments that don't have one, it's used on
+    /// the conflict errors. The statements are wrapped with it, so they're
+    /// mapped to it on the source map.
+    pub fn set_origin(mut self, origin: Origin) -> Self {
+        for argument in &mut self.arguments {
+            if argument.origin.is_none() {
+                argument.origin = Some(origin.clone());
+            }
+        }
+        if !self.hoisted.is_empty() {
+            let hoisted = std::mem::take(&mut self.hoisted);
+            self.hoisted = vec![Stmt::Origin(origin.clone(), hoisted)];
+        }
+        if let Some(content) = self.content.take() {
+            self.content = Some(vec![Stmt::Origin(origin, content)]);
+        }
+        self
+    }
+
+    pub fn statements(&self) -> Option<&Vec<Stmt>> {
         self.content.as_ref()
     }
 
-    pub fn imports(&self) -> &AHashMap<String, String> {
-        &self.imports
+    /// Take the statements out, to move them to the parent
+    pub fn take_statements(&mut self) -> Option<Vec<Stmt>> {
+        self.content.take()
     }
 
-    pub fn build_update(&self, imports: &mut String, args: &mut String, content: &mut String) {
-        for import in &self.imports {
-            *imports += &format!("import {} from \"{}\";\n", import.1, import.0);
-        }
+    /// The statements printed as TS
+    pub fn content(&self) -> Option<String> {
+        self.content.as_ref().map(|content| js::print(content))
+    }
+
+    pub fn imports(&self) -> &Imports {
+        &self.imports
+    }
 
-        for arg in &self.arguments {
-            *args += &format!("{}: {}", arg.0, arg.1);
+    pub fn build_update(
+        &self,
+        imports: &mut String,
+        hoisted: &mut String,
+        args: &mut String,
+        content: &mut String,
+    ) -> anyhow::Result<()> {
+        *imports += &self.imports.print()?;
+        *hoisted += &js::print(&self.hoisted);
+
+        let mut arguments = Vec::with_capacity(self.arguments.len());
+        for argument in &self.arguments {
+            push_argument(&mut arguments, argument)?;
         }
+        *args += &arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
 
         if let Some(c) = &self.content {
-            *content += c;
+            *content += &js::print(c);
         }
+
+        Ok(())
     }
-}
 
-impl ops::AddAssign<&CloudManifestUpdate> for CloudManifestUpdate {
-    fn add_assign(&mut self, rhs: &CloudManifestUpdate) {
-        for (path, items) in rhs.imports() {
-            let a = self.imports.get_mut(path);
+    /// Merge the imports, hoisted statements and arguments of other update,
+    /// the content isn't merged. It fails when both define the same argument
+    /// in other way.
+    pub fn merge(&mut self, rhs: &CloudManifestUpdate) -> anyhow::Result<()> {
+        self.imports.merge(rhs.imports());
+        self.hoisted.extend(rhs.hoisted().iter().cloned());
 
-            match a {
-                Some(a) => *a += items,
-                None => {
-                    self.imports.insert(path.into(), items.into());
-                }
-            }
+        for argument in rhs.arguments() {
+            push_argument(&mut self.arguments, argument)?;
         }
 
-        for (arg, def) in rhs.arguments() {
-            let old_def = self.arguments.get(arg);
+        Ok(())
+    }
+}
 
-            if let Some(old_def) = old_def {
-                if def != old_def {
-                    panic!("Conflicting argument {}. {:#?} != {:#?}", arg, def, old_def);
-                }
-            } else {
-                self.arguments.insert(arg.into(), def.into());
-            }
-        }
+/// Add the argument if it isn't already, it fails when it's defined in
+/// other way
+fn push_argument(
+    arguments: &mut Vec<ManifestArgument>,
+    argument: &ManifestArgument,
+) -> anyhow::Result<()> {
+    let Some(old) = arguments.iter().find(|old| old.name == argument.name) else {
+        arguments.push(argument.clone());
+        return Ok(());
+    };
+
+    if old.is_same(argument) {
+        return Ok(());
     }
+
+    let origin = |argument: &ManifestArgument| match &argument.origin {
+        Some(origin) => origin.to_string(),
+        None => "an unknown cloud".into(),
+    };
+    Err(anyhow::anyhow!(
+        "Conflicting manifest argument {:?}: `{old}` from {} and `{argument}` from {}",
+        argument.name,
+        origin(old),
+        origin(argument)
+    ))
 }
 
-impl ops::AddAssign<CloudManifestUpdate> for CloudManifestUpdate {
-    fn add_assign(&mut self, rhs: CloudManifestUpdate) {
-        for (path, items) in rhs.imports() {
-            let a = self.imports.get_mut(path);
+#[cfg(test)]
+mod test {
+    use super::{CloudManifestUpdate, ParamConstraint};
+    use crate::js::{Expr, Origin};
+
+    #[test]
+    fn param_constraints() {
+        let int = ParamConstraint::parse("int").unwrap().unwrap();
+        assert!(int.is_match("-42"));
+        assert!(!int.is_match("4.2"));
+        assert!(!int.is_match("-"));
+
+        let uuid = ParamConstraint::parse("uuid").unwrap().unwrap();
+        assert!(uuid.is_match("123e4567-E89B-12d3-a456-426614174000"));
+        assert!(!uuid.is_match("123e4567-e89b-12d3-a456"));
+
+        let slug = ParamConstraint::parse("[a-z-]+").unwrap().unwrap();
+        assert!(slug.is_match("hello-world"));
+        assert!(!slug.is_match("Hello"));
+        assert_eq!(slug.to_string(), "[a-z-]+");
+
+        assert_eq!(ParamConstraint::parse("").unwrap(), None);
+        // A typo isn't a regex, and the regex should be valid
+        assert!(ParamConstraint::parse("itn").is_err());
+        assert!(ParamConstraint::parse("[a-z").is_err());
+    }
 
-            match a {
-                Some(a) => *a += items,
-                None => {
-                    self.imports.insert(path.into(), items.into());
-                }
-            }
+    #[test]
+    fn portable_param_constraints() {
+        // Same matches on Rust and JS
+        for source in [
+            r"[a-z0-9_-]+",
+            r"\d{4}(?:-\d{2}){0,2}",
+            r"(draft|final)\.v[0-9]+?",
+            r"[-a-f\]]{2,}",
+            r"\w*\+\w*",
+        ] {
+            assert!(ParamConstraint::parse(source).is_ok(), "{}", source);
         }
 
-        for (arg, def) in rhs.arguments() {
-            let old_def = self.arguments.get(arg);
-
-            if let Some(old_def) = old_def {
-                if def != old_def {
-                    panic!("Conflicting argument {}. {:#?} != {:#?}", arg, def, old_def);
-                }
-            } else {
-                self.arguments.insert(arg.into(), def.into());
-            }
+        // `\d` is ASCII, like on JS
+        let digits = ParamConstraint::parse(r"\d+").unwrap().unwrap();
+        assert!(digits.is_match("2024"));
+        assert!(!digits.is_match("٢٠٢٤"));
+
+        for source in [
+            // Rust only syntax
+            "[[:alpha:]]+",
+            "(?P<year>[0-9]+)",
+            "(?<year>[0-9]+)",
+            "(?i)[a-z]+",
+            r"\p{L}+",
+            "[a-z--x]+",
+            "[a-z&&b]+",
+            // Matches `/` or outside of the segment
+            ".+",
+            "[^-]+",
+            "[+-9]+",
+            "^[a-z]+$",
+            // Differs with non ASCII text
+            r"\s+",
+            "é+",
+            // JS only syntax
+            "(?=[a-z])[a-z0-9]+",
+            "[a-z]{,3}",
+            r"\/",
+        ] {
+            assert!(ParamConstraint::parse(source).is_err(), "{}", source);
         }
     }
+
+    #[test]
+    fn manifest_arguments() {
+        let mut update = CloudManifestUpdate::new()
+            .add_argument("req", "HTTPRequest")
+            .set_origin(Origin::cloud("http/router"));
+        let other = CloudManifestUpdate::new()
+            .add_argument("req", "HTTPRequest")
+            .add_argument_with_default("ctx", "Ctx", Expr::object(Vec::<(String, Expr)>::new()))
+            .set_origin(Origin::leaf("http/router", "/users", None));
+        update.merge(&other).unwrap();
+
+        let (mut imports, mut hoisted, mut args, mut content) =
+            (String::new(), String::new(), String::new(), String::new());
+        update
+            .build_update(&mut imports, &mut hoisted, &mut args, &mut content)
+            .unwrap();
+        assert_eq!(args, "req: HTTPRequest, ctx: Ctx = {}");
+
+        let conflict = CloudManifestUpdate::new()
+            .add_argument("req", "Request")
+            .set_origin(Origin::leaf("http/router", "/admin", None));
+        let err = update.merge(&conflict).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "Conflicting manifest argument \"req\": `req: HTTPRequest` from http/router and \
+             `req: Request` from http/router on \"/admin\""
+        );
+    }
 }
@@ -5,6 +5,8 @@ pub use self::file_process::*;
 pub use self::setup::*;
 
 use crate::context;
+use crate::js::Stmt;
+use crate::CompileContext;
 
 macro_rules! create_call {
     ($call_name:ident, $symbol:expr, $($fn:tt)+) => {
@@ -52,15 +54,18 @@ create_call!(
 create_call!(
     CloudBeforeManifestCall,
     b"cloud_before_manifest",
-    fn() -> anyhow::Result<file_process::CloudManifestUpdate>
+    fn(&CompileContext, &setup::CloudOptions) -> anyhow::Result<file_process::CloudManifestUpdate>
 );
+// (leaf, Vec<(Pathname, StaticChild)>, dynamic children, dynamic child, context, options)
 create_call!(
     CloudOptimizedManifestCall,
     b"cloud_manifest",
     fn(
         OptimizedTreeLeaf,
-        String,
-        String,
-        String,
+        Vec<(String, Vec<Stmt>)>,
+        Vec<Stmt>,
+        Vec<Stmt>,
+        &CompileContext,
+        &setup::CloudOptions,
     ) -> anyhow::Result<file_process::CloudManifestUpdate>
 );
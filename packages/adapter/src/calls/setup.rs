@@ -52,9 +52,12 @@ pub struct CloudDependency {
     pub version: CloudVersion,
     pub optional: bool,
 
-    pub options: AHashMap<String, CloudDependencyOption>,
+    pub options: CloudOptions,
 }
 
+/// Options of a cloud on the config file, without `version`
+pub type CloudOptions = AHashMap<String, CloudDependencyOption>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CloudDependencyOption {
     /// A string value.
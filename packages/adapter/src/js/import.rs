@@ -0,0 +1,338 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+/// What an import binds from the module
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImportKind {
+    /// `import local from "path"`
+    Default,
+    /// `import * as local from "path"`
+    Namespace,
+    /// `import { name as local } from "path"`
+    Named(String),
+}
+
+/// One binding of an import statement
+/// ```
+/// use densky_adapter::js::ImportSpecifier;
+///
+/// // import { type HTTPRequest } from "..."
+/// ImportSpecifier::named("HTTPRequest").type_only();
+/// // import { join as joinPath } from "..."
+/// ImportSpecifier::named_as("join", "joinPath");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ImportSpecifier {
+    pub kind: ImportKind,
+    /// Name of the binding on the manifest
+    pub local: String,
+    /// Only used as type, it's removed on the JS output
+    pub type_only: bool,
+}
+
+impl ImportSpecifier {
+    pub fn default(local: impl Into<String>) -> ImportSpecifier {
+        ImportSpecifier {
+            kind: ImportKind::Default,
+            local: local.into(),
+            type_only: false,
+        }
+    }
+
+    pub fn namespace(local: impl Into<String>) -> ImportSpecifier {
+        ImportSpecifier {
+            kind: ImportKind::Namespace,
+            local: local.into(),
+            type_only: false,
+        }
+    }
+
+    pub fn named(name: impl Into<String>) -> ImportSpecifier {
+        let name = name.into();
+        ImportSpecifier {
+            kind: ImportKind::Named(name.clone()),
+            local: name,
+            type_only: false,
+        }
+    }
+
+    pub fn named_as(name: impl Into<String>, local: impl Into<String>) -> ImportSpecifier {
+        ImportSpecifier {
+            kind: ImportKind::Named(name.into()),
+            local: local.into(),
+            type_only: false,
+        }
+    }
+
+    pub fn type_only(mut self) -> ImportSpecifier {
+        self.type_only = true;
+        self
+    }
+
+    /// The same binding, without looking at `type_only`
+    fn is_same_binding(&self, other: &ImportSpecifier) -> bool {
+        self.kind == other.kind && self.local == other.local
+    }
+
+    /// `name as local` without the `type` modifier
+    fn write_named(&self, out: &mut String) {
+        let ImportKind::Named(name) = &self.kind else {
+            return;
+        };
+
+        out.push_str(name);
+        if name != &self.local {
+            out.push_str(" as ");
+            out.push_str(&self.local);
+        }
+    }
+}
+
+/// The binding as it's written on the import: `{ A as B }`, `* as B`...
+impl fmt::Display for ImportSpecifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let type_only = if self.type_only { "type " } else { "" };
+        match &self.kind {
+            ImportKind::Default => write!(f, "{type_only}{}", self.local),
+            ImportKind::Namespace => write!(f, "{type_only}* as {}", self.local),
+            ImportKind::Named(_) => {
+                let mut named = String::new();
+                self.write_named(&mut named);
+                write!(f, "{{ {type_only}{named} }}")
+            }
+        }
+    }
+}
+
+/// Imports of the manifest, grouped by module. The same binding can be
+/// added many times, they're merged and printed once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imports {
+    /// Map<Path, Specifiers>. A module without specifiers is imported only
+    /// for its side effects.
+    modules: BTreeMap<String, BTreeSet<ImportSpecifier>>,
+}
+
+impl Imports {
+    pub fn new() -> Imports {
+        Imports::default()
+    }
+
+    pub fn add(&mut self, specifier: ImportSpecifier, path: impl Into<String>) {
+        self.modules
+            .entry(path.into())
+            .or_default()
+            .insert(specifier);
+    }
+
+    /// `import "path";`
+    pub fn add_side_effect(&mut self, path: impl Into<String>) {
+        self.modules.entry(path.into()).or_default();
+    }
+
+    pub fn merge(&mut self, other: &Imports) {
+        for (path, specifiers) in &other.modules {
+            self.modules
+                .entry(path.clone())
+                .or_default()
+                .extend(specifiers.iter().cloned());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    /// Map<Path, Specifiers>
+    pub fn modules(&self) -> &BTreeMap<String, BTreeSet<ImportSpecifier>> {
+        &self.modules
+    }
+
+    /// Check that each local name is bound once, and print one import
+    /// statement for each module.
+    ///
+    /// TS can't mix a namespace with named bindings, or a type-only default
+    /// with value bindings, so those go on their own statement.
+    pub fn print(&self) -> Result<String> {
+        self.check_collisions()?;
+
+        let mut out = String::new();
+        for (path, specifiers) in &self.modules {
+            let path = super::Expr::string(path.as_str()).to_string();
+            if specifiers.is_empty() {
+                out.push_str(&format!("import {path};\n"));
+                continue;
+            }
+
+            // A value import also works as type, so it replaces the
+            // type-only one
+            let specifiers: Vec<&ImportSpecifier> = specifiers
+                .iter()
+                .filter(|specifier| {
+                    !specifier.type_only
+                        || !specifiers
+                            .iter()
+                            .any(|other| !other.type_only && other.is_same_binding(specifier))
+                })
+                .collect();
+
+            let all_types = specifiers.iter().all(|specifier| specifier.type_only);
+            let (mut defaults, mut namespaces, mut named) = (Vec::new(), Vec::new(), Vec::new());
+            for specifier in specifiers {
+                match specifier.kind {
+                    ImportKind::Default => defaults.push(specifier),
+                    ImportKind::Namespace => namespaces.push(specifier),
+                    ImportKind::Named(_) => named.push(specifier),
+                }
+            }
+
+            // Bindings that can't go on the main statement
+            let mut alone = Vec::new();
+
+            let mut clause = Vec::new();
+            if !all_types {
+                if let Some(i) = defaults.iter().position(|default| !default.type_only) {
+                    clause.push(defaults.remove(i).local.clone());
+                }
+                if named.is_empty() {
+                    if let Some(i) = namespaces.iter().position(|namespace| !namespace.type_only) {
+                        clause.push(format!("* as {}", namespaces.remove(i).local));
+                    }
+                }
+            }
+            alone.extend(defaults);
+            alone.extend(namespaces);
+
+            if !named.is_empty() {
+                let named: Vec<String> = named
+                    .iter()
+                    .map(|specifier| {
+                        let mut out = String::new();
+                        if specifier.type_only && !all_types {
+                            out.push_str("type ");
+                        }
+                        specifier.write_named(&mut out);
+                        out
+                    })
+                    .collect();
+                clause.push(format!("{{ {} }}", named.join(", ")));
+            }
+
+            if !clause.is_empty() {
+                let type_only = if all_types { "type " } else { "" };
+                out.push_str(&format!(
+                    "import {type_only}{} from {path};\n",
+                    clause.join(", ")
+                ));
+            }
+            for specifier in alone {
+                out.push_str(&format!("import {specifier} from {path};\n"));
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn check_collisions(&self) -> Result<()> {
+        // Map<Local, (Path, Specifier)>
+        let mut locals: BTreeMap<&String, (&String, &ImportSpecifier)> = BTreeMap::new();
+        for (path, specifiers) in &self.modules {
+            for specifier in specifiers {
+                let Some((old_path, old_specifier)) = locals.get(&specifier.local) else {
+                    locals.insert(&specifier.local, (path, specifier));
+                    continue;
+                };
+
+                if *old_path == path && old_specifier.is_same_binding(specifier) {
+                    continue;
+                }
+
+                return Err(anyhow!(
+                    "Conflicting import {:?}: `{old_specifier}` from {old_path:?} and `{specifier}` from {path:?}",
+                    specifier.local
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ImportSpecifier, Imports};
+
+    #[test]
+    fn merge_named() {
+        let mut imports = Imports::new();
+        imports.add(ImportSpecifier::named("A"), "./a.ts");
+
+        let mut other = Imports::new();
+        other.add(ImportSpecifier::named("B"), "./a.ts");
+        other.add(ImportSpecifier::named("A"), "./a.ts");
+        other.add(ImportSpecifier::named("A").type_only(), "./a.ts");
+        imports.merge(&other);
+
+        assert_eq!(
+            imports.print().unwrap(),
+            "import { A, B } from \"./a.ts\";\n"
+        );
+    }
+
+    #[test]
+    fn specifier_kinds() {
+        let mut imports = Imports::new();
+        imports.add(
+            ImportSpecifier::named("HTTPRequest").type_only(),
+            "densky/http.ts",
+        );
+        imports.add(ImportSpecifier::named("Ctx").type_only(), "densky/http.ts");
+
+        imports.add(ImportSpecifier::default("React"), "react");
+        imports.add(ImportSpecifier::named_as("useState", "state"), "react");
+        imports.add(ImportSpecifier::named("FC").type_only(), "react");
+        imports.add(ImportSpecifier::namespace("ReactAll"), "react");
+
+        imports.add(ImportSpecifier::namespace("path"), "node:path");
+        imports.add(
+            ImportSpecifier::default("Config").type_only(),
+            "./config.ts",
+        );
+        imports.add_side_effect("./polyfill.ts");
+
+        assert_eq!(
+            imports.print().unwrap(),
+            "\
+import type Config from \"./config.ts\";
+import \"./polyfill.ts\";
+import type { Ctx, HTTPRequest } from \"densky/http.ts\";
+import * as path from \"node:path\";
+import React, { type FC, useState as state } from \"react\";
+import * as ReactAll from \"react\";
+"
+        );
+    }
+
+    #[test]
+    fn collisions() {
+        let mut imports = Imports::new();
+        imports.add(ImportSpecifier::named("A"), "./a.ts");
+        imports.add(ImportSpecifier::default("A"), "./b.ts");
+
+        let err = imports.print().unwrap_err().to_string();
+        assert!(err.contains("Conflicting import \"A\""), "{}", err);
+
+        let mut imports = Imports::new();
+        imports.add(ImportSpecifier::named("A"), "./a.ts");
+        imports.add(ImportSpecifier::named_as("B", "A"), "./a.ts");
+        assert!(imports.print().is_err());
+
+        // Same binding, only the type modifier changes
+        let mut imports = Imports::new();
+        imports.add(ImportSpecifier::named("A"), "./a.ts");
+        imports.add(ImportSpecifier::named("A").type_only(), "./a.ts");
+        assert!(imports.print().is_ok());
+    }
+}
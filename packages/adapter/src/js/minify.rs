@@ -0,0 +1,280 @@
+//! Minifier for the generated manifest.
+//!
+//! It works on tokens, so strings, template literals and regex literals are
+//! kept as they are. The comments and the indentation are removed, and the
+//! line breaks are only kept where they could end a statement. The internal
+//! identifiers of the manifest are shortened.
+//!
+//! The source map markers and the `@ts-` directives are kept on their own
+//! lines, so [`SourceMap::extract`](super::SourceMap::extract) works on the
+//! minified code.
+
+use std::collections::HashMap;
+
+/// Prefixes of the identifiers declared by the manifest itself, they're
+/// never read from outside
+const INTERNAL_PREFIXES: [&str; 5] = [
+    "__DENSKY_",
+    "__var_",
+    "__segments_",
+    "__prev_segments",
+    "__module_",
+];
+
+/// Comments that have to be kept, on their own line
+const KEPT_COMMENTS: [&str; 2] = ["// @densky-origin", "// @ts-"];
+
+/// Minify TS code.
+/// ```
+/// use densky_adapter::js::minify;
+///
+/// let code = "// Static children\nconst __DENSKY_out = {\n  a: \"b  c\",\n};\nreturn __DENSKY_out;\n";
+/// assert_eq!(minify(code), "const _0={a:\"b  c\",};return _0;");
+/// ```
+pub fn minify(code: &str) -> String {
+    Minifier::new(code).run()
+}
+
+struct Minifier<'a> {
+    code: &'a str,
+    pos: usize,
+    out: String,
+    /// Whitespace was skipped since the last token
+    pending_space: bool,
+    /// A line break was skipped since the last token
+    pending_newline: bool,
+    names: HashMap<String, String>,
+}
+
+impl<'a> Minifier<'a> {
+    fn new(code: &'a str) -> Minifier<'a> {
+        Minifier {
+            code,
+            pos: 0,
+            out: String::with_capacity(code.len()),
+            pending_space: false,
+            pending_newline: false,
+            names: HashMap::new(),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.code[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn run(mut self) -> String {
+        while let Some(c) = self.peek() {
+            let rest = self.rest();
+
+            if c.is_whitespace() {
+                self.pending_space = true;
+                self.pending_newline |= c == '\n';
+                self.pos += c.len_utf8();
+            } else if rest.starts_with("//") {
+                let len = rest.find('\n').unwrap_or(rest.len());
+                let comment = rest[..len].trim_end();
+                self.pos += len;
+
+                if KEPT_COMMENTS
+                    .iter()
+                    .any(|prefix| comment.starts_with(prefix))
+                {
+                    self.own_line(comment);
+                } else {
+                    self.pending_space = true;
+                }
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let len = comment.find("*/").map_or(rest.len(), |end| end + 4);
+                self.pending_space = true;
+                self.pending_newline |= rest[..len].contains('\n');
+                self.pos += len;
+            } else if c == '"' || c == '\'' || c == '`' {
+                let len = quoted_len(rest, c, false);
+                self.token(&rest[..len], len);
+            } else if c == '/' && self.regex_allowed() {
+                let len = quoted_len(rest, '/', true);
+                let flags = rest[len..]
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len() - len);
+                self.token(&rest[..len + flags], len + flags);
+            } else if is_word_char(c) {
+                let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+                let word = &rest[..len];
+                let is_property = self.out.ends_with('.');
+                let word = if !is_property && is_internal(word) {
+                    self.short_name(word)
+                } else {
+                    word.to_string()
+                };
+                self.token(&word, len);
+            } else {
+                self.token(&rest[..c.len_utf8()], c.len_utf8());
+            }
+        }
+
+        self.out
+    }
+
+    /// Write the skipped whitespace that's still needed and the token, `len`
+    /// is the length of the source token
+    fn token(&mut self, token: &str, len: usize) {
+        let first = token.chars().next().unwrap_or(' ');
+        let prev = self.out.chars().next_back();
+
+        if let Some(prev) = prev {
+            let newline_ends_nothing = ";{,([".contains(prev) || "}]),;".contains(first);
+            if self.pending_newline && !newline_ends_nothing && prev != '\n' {
+                self.out.push('\n');
+            } else if self.pending_space && prev != '\n' && needs_space(prev, first) {
+                self.out.push(' ');
+            }
+        }
+
+        self.out.push_str(token);
+        self.pos += len;
+        self.pending_space = false;
+        self.pending_newline = false;
+    }
+
+    fn own_line(&mut self, line: &str) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+        self.pending_space = false;
+        self.pending_newline = false;
+    }
+
+    /// A `/` starts a regex after an operator or a keyword, otherwise it's a
+    /// division
+    fn regex_allowed(&self) -> bool {
+        let out = self.out.trim_end();
+        let Some(prev) = out.chars().next_back() else {
+            return true;
+        };
+        if !is_word_char(prev) {
+            return !")]".contains(prev);
+        }
+
+        let word_start = out.rfind(|c| !is_word_char(c)).map_or(0, |i| i + 1);
+        matches!(
+            &out[word_start..],
+            "return" | "typeof" | "case" | "in" | "of" | "new" | "delete" | "void"
+        )
+    }
+
+    fn short_name(&mut self, name: &str) -> String {
+        let len = self.names.len();
+        self.names
+            .entry(name.to_string())
+            .or_insert_with(|| format!("_{}", base36(len)))
+            .clone()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_internal(word: &str) -> bool {
+    INTERNAL_PREFIXES
+        .iter()
+        .any(|prefix| word.starts_with(prefix))
+}
+
+/// Two words are joined by the space, and `a - -b` isn't `a --b`
+fn needs_space(prev: char, next: char) -> bool {
+    (is_word_char(prev) && is_word_char(next)) || (prev == next && (prev == '+' || prev == '-'))
+}
+
+/// Length of the quoted token, the quotes included. The `/` of a regex
+/// inside a class doesn't close it: `/[/]/`
+fn quoted_len(rest: &str, quote: char, is_regex: bool) -> usize {
+    let mut in_class = false;
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' if is_regex => in_class = true,
+            ']' if is_regex => in_class = false,
+            c if c == quote && !in_class => return i + c.len_utf8(),
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn base36(mut n: usize) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let mut out = Vec::new();
+    loop {
+        out.push(DIGITS[n % 36]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{print, Expr, Origin, SourceMap, Stmt};
+    use super::minify;
+
+    #[test]
+    fn tokens() {
+        let code = "\
+import * as __module_a from \"../a.ts\";
+const re = /^\\/a\\/[/]+$/i; // not a comment: \"x\"
+const s = 'it\\'s' + `a  ${b}` + \"// no\";
+/* block
+   comment */
+if (x - -1 > 0) {
+  return __module_a.__module_a;
+}
+foo()
+(bar);
+";
+
+        assert_eq!(
+            minify(code),
+            "import*as _0 from\"../a.ts\";const re=/^\\/a\\/[/]+$/i;\
+             const s='it\\'s'+`a  ${b}`+\"// no\";\
+             if(x- -1>0){return _0.__module_a;}\nfoo()\n(bar);"
+        );
+    }
+
+    #[test]
+    fn kept_comments() {
+        let origin = Origin::leaf("http/router", "/users", Some("users.ts".into()));
+        let code = print(&[
+            Stmt::comment("manifest"),
+            Stmt::Origin(
+                origin,
+                vec![
+                    Stmt::comment("@ts-ignore READ-ONLY"),
+                    Stmt::assign(Expr::ident("a").member("b"), Expr::number(1)),
+                    Stmt::Return(Some(Expr::Null)),
+                ],
+            ),
+            Stmt::Return(Some(Expr::Null)),
+        ]);
+
+        let (code, source_map) = SourceMap::extract(&minify(&code), "manifest.ts");
+        assert_eq!(
+            code,
+            "// @ts-ignore READ-ONLY\na.b=1;return null;\nreturn null;"
+        );
+        assert_eq!(source_map.origin_of(1), Some(("users.ts", Some("/users"))));
+        assert_eq!(source_map.origin_of(2), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Small JS/TS syntax tree to generate the manifest.
+//!
+//! Clouds build statements and expressions instead of concatenating
+//! strings, so braces, commas and quotes are always balanced. The core
+//! prints them to TS with [`print`].
+//! ```
+//! use densky_adapter::js::{Expr, Stmt};
+//!
+//! let segments = Expr::ident("req").member("segments");
+//! let stmt = Stmt::If(
+//!     segments.member("length").strict_eq(Expr::number(0)),
+//!     vec![Stmt::Return(Some(Expr::string("index")))],
+//! );
+//!
+//! assert_eq!(
+//!     densky_adapter::js::print(&[stmt]),
+//!     "if (req.segments.length === 0) {\n  return \"index\";\n}\n"
+//! );
+//! ```
+
+use std::ops;
+
+mod import;
+mod minify;
+mod print;
+mod source_map;
+
+pub use self::import::{ImportKind, ImportSpecifier, Imports};
+pub use self::minify::minify;
+pub use self::print::print;
+pub use self::source_map::{Origin, SourceMap};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// `req`, `__var_id`
+    Ident(String),
+    /// Quoted and escaped on print
+    Str(String),
+    Number(i64),
+    Bool(bool),
+    Null,
+    Undefined,
+    /// `/pattern/flags`. Slashes inside the pattern must be escaped.
+    Regex(String, String),
+    Array(Vec<Expr>),
+    /// `{ key: value }`, the keys are quoted only when needed
+    Object(Vec<(String, Expr)>),
+    /// `(params) => { body }`
+    Arrow(Vec<String>, Vec<Stmt>),
+    /// `object.property`
+    Member(Box<Expr>, String),
+    /// `object[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `callee(args)`
+    Call(Box<Expr>, Vec<Expr>),
+    /// `new callee(args)`
+    New(Box<Expr>, Vec<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// `!expr`
+    Not(Box<Expr>),
+    /// Code printed as is
+    Raw(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    StrictEq,
+    StrictNe,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+impl BinaryOp {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::And => "&&",
+            BinaryOp::StrictEq => "===",
+            BinaryOp::StrictNe => "!==",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+        }
+    }
+
+    /// Same order as JS, higher binds tighter
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::StrictEq | BinaryOp::StrictNe => 3,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+        }
+    }
+}
+
+impl Expr {
+    pub fn ident(name: impl Into<String>) -> Expr {
+        Expr::Ident(name.into())
+    }
+
+    pub fn string(value: impl Into<String>) -> Expr {
+        Expr::Str(value.into())
+    }
+
+    pub fn number(value: i64) -> Expr {
+        Expr::Number(value)
+    }
+
+    pub fn regex(pattern: impl Into<String>, flags: impl Into<String>) -> Expr {
+        Expr::Regex(pattern.into(), flags.into())
+    }
+
+    pub fn raw(code: impl Into<String>) -> Expr {
+        Expr::Raw(code.into())
+    }
+
+    pub fn array(items: impl IntoIterator<Item = Expr>) -> Expr {
+        Expr::Array(items.into_iter().collect())
+    }
+
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Expr)>) -> Expr {
+        Expr::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+
+    /// Arrow function without params
+    pub fn arrow(body: Vec<Stmt>) -> Expr {
+        Expr::Arrow(Vec::new(), body)
+    }
+
+    pub fn arrow_with<P: Into<String>>(
+        params: impl IntoIterator<Item = P>,
+        body: Vec<Stmt>,
+    ) -> Expr {
+        Expr::Arrow(params.into_iter().map(Into::into).collect(), body)
+    }
+
+    pub fn member(self, property: impl Into<String>) -> Expr {
+        Expr::Member(Box::new(self), property.into())
+    }
+
+    pub fn index(self, index: Expr) -> Expr {
+        Expr::Index(Box::new(self), Box::new(index))
+    }
+
+    pub fn call(self, args: Vec<Expr>) -> Expr {
+        Expr::Call(Box::new(self), args)
+    }
+
+    /// `new self(args)`
+    pub fn construct(self, args: Vec<Expr>) -> Expr {
+        Expr::New(Box::new(self), args)
+    }
+
+    /// `self.method(args)`
+    pub fn method(self, method: impl Into<String>, args: Vec<Expr>) -> Expr {
+        self.member(method).call(args)
+    }
+
+    pub fn binary(self, op: BinaryOp, rhs: Expr) -> Expr {
+        Expr::Binary(Box::new(self), op, Box::new(rhs))
+    }
+
+    pub fn or(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Or, rhs)
+    }
+
+    pub fn and(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::And, rhs)
+    }
+
+    pub fn strict_eq(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::StrictEq, rhs)
+    }
+
+    pub fn strict_ne(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::StrictNe, rhs)
+    }
+
+    pub fn lt(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Lt, rhs)
+    }
+
+    pub fn le(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Le, rhs)
+    }
+
+    pub fn gt(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Gt, rhs)
+    }
+
+    pub fn ge(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Ge, rhs)
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Expr;
+
+    fn add(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Add, rhs)
+    }
+}
+
+impl ops::Sub for Expr {
+    type Output = Expr;
+
+    fn sub(self, rhs: Expr) -> Expr {
+        self.binary(BinaryOp::Sub, rhs)
+    }
+}
+
+impl ops::Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        Expr::Not(Box::new(self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    /// `expr;`
+    Expr(Expr),
+    /// `const name = value;`
+    Const(String, Expr),
+    /// `target = value;`
+    Assign(Expr, Expr),
+    /// `if (condition) { body }`
+    If(Expr, Vec<Stmt>),
+    /// `{ body }`
+    Block(Vec<Stmt>),
+    /// `switch (discriminant) { case value: { body } }`. The cases don't
+    /// break by themselves
+    Switch(Expr, Vec<(Expr, Vec<Stmt>)>),
+    /// `break;`
+    Break,
+    /// `return value;`
+    Return(Option<Expr>),
+    /// `// comment`
+    Comment(String),
+    /// Code printed as is, on its own line
+    Raw(String),
+    /// Statements generated by a leaf or cloud, see [`SourceMap`]
+    Origin(Origin, Vec<Stmt>),
+}
+
+impl Stmt {
+    pub fn constant(name: impl Into<String>, value: Expr) -> Stmt {
+        Stmt::Const(name.into(), value)
+    }
+
+    pub fn assign(target: Expr, value: Expr) -> Stmt {
+        Stmt::Assign(target, value)
+    }
+
+    pub fn comment(text: impl Into<String>) -> Stmt {
+        Stmt::Comment(text.into())
+    }
+
+    pub fn raw(code: impl Into<String>) -> Stmt {
+        Stmt::Raw(code.into())
+    }
+}
+
+impl From<Expr> for Stmt {
+    fn from(expr: Expr) -> Stmt {
+        Stmt::Expr(expr)
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+
+use super::{Expr, Origin, Stmt};
+
+const INDENT: &str = "  ";
+
+/// Print the statements as TS code, one statement per line
+pub fn print(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    write_block(&mut out, stmts, 0);
+    out
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        write_stmt(&mut out, self, 0);
+        f.write_str(&out)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        write_expr(&mut out, self, 0);
+        f.write_str(&out)
+    }
+}
+
+fn pad(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str(INDENT);
+    }
+}
+
+fn write_block(out: &mut String, stmts: &[Stmt], indent: usize) {
+    for stmt in stmts {
+        write_stmt(out, stmt, indent);
+    }
+}
+
+/// `{`, the body and `}` without the line break
+fn write_braced(out: &mut String, stmts: &[Stmt], indent: usize) {
+    if stmts.is_empty() {
+        out.push_str("{}");
+        return;
+    }
+
+    out.push_str("{\n");
+    write_block(out, stmts, indent + 1);
+    pad(out, indent);
+    out.push('}');
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, indent: usize) {
+    pad(out, indent);
+    match stmt {
+        Stmt::Expr(expr) => {
+            // At the start of a statement they would be parsed as a block
+            let needs_parens = matches!(expr, Expr::Object(_) | Expr::Arrow(..));
+            if needs_parens {
+                out.push('(');
+            }
+            write_expr(out, expr, indent);
+            if needs_parens {
+                out.push(')');
+            }
+            out.push(';');
+        }
+        Stmt::Const(name, value) => {
+            out.push_str("const ");
+            out.push_str(name);
+            out.push_str(" = ");
+            write_expr(out, value, indent);
+            out.push(';');
+        }
+        Stmt::Assign(target, value) => {
+            write_expr(out, target, indent);
+            out.push_str(" = ");
+            write_expr(out, value, indent);
+            out.push(';');
+        }
+        Stmt::If(condition, body) => {
+            out.push_str("if (");
+            write_expr(out, condition, indent);
+            out.push_str(") ");
+            write_braced(out, body, indent);
+        }
+        Stmt::Block(body) => write_braced(out, body, indent),
+        Stmt::Switch(discriminant, cases) => {
+            out.push_str("switch (");
+            write_expr(out, discriminant, indent);
+            out.push_str(") {\n");
+            for (value, body) in cases {
+                pad(out, indent + 1);
+                out.push_str("case ");
+                write_expr(out, value, indent + 1);
+                out.push_str(": ");
+                write_braced(out, body, indent + 1);
+                out.push('\n');
+            }
+            pad(out, indent);
+            out.push('}');
+        }
+        Stmt::Break => out.push_str("break;"),
+        Stmt::Return(None) => out.push_str("return;"),
+        Stmt::Return(Some(value)) => {
+            out.push_str("return ");
+            write_expr(out, value, indent);
+            out.push(';');
+        }
+        Stmt::Comment(text) => {
+            for (i, line) in text.lines().enumerate() {
+                if i > 0 {
+                    out.push('\n');
+                    pad(out, indent);
+                }
+                out.push_str("// ");
+                out.push_str(line);
+            }
+        }
+        Stmt::Raw(code) => out.push_str(code),
+        Stmt::Origin(origin, body) => {
+            out.push_str(&origin.marker());
+            out.push('\n');
+            write_block(out, body, indent);
+            pad(out, indent);
+            out.push_str(Origin::marker_end());
+        }
+    }
+    out.push('\n');
+}
+
+/// Precedence of the expression as an operand, higher binds tighter
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary(_, op, _) => op.precedence(),
+        Expr::Not(_) => 6,
+        // Unknown code and arrows are always wrapped
+        Expr::Arrow(..) | Expr::Raw(_) => 0,
+        _ => 7,
+    }
+}
+
+fn write_operand(out: &mut String, expr: &Expr, min_precedence: u8, indent: usize) {
+    if precedence(expr) < min_precedence {
+        out.push('(');
+        write_expr(out, expr, indent);
+        out.push(')');
+    } else {
+        write_expr(out, expr, indent);
+    }
+}
+
+fn write_list(out: &mut String, items: &[Expr], indent: usize) {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expr(out, item, indent);
+    }
+}
+
+fn write_expr(out: &mut String, expr: &Expr, indent: usize) {
+    match expr {
+        Expr::Ident(name) => out.push_str(name),
+        Expr::Str(value) => write_string(out, value),
+        Expr::Number(value) => out.push_str(&value.to_string()),
+        Expr::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+        Expr::Null => out.push_str("null"),
+        Expr::Undefined => out.push_str("undefined"),
+        Expr::Regex(pattern, flags) => {
+            out.push('/');
+            out.push_str(pattern);
+            out.push('/');
+            out.push_str(flags);
+        }
+        Expr::Array(items) => {
+            out.push('[');
+            write_list(out, items, indent);
+            out.push(']');
+        }
+        Expr::Object(entries) => {
+            if entries.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+
+            out.push_str("{\n");
+            for (key, value) in entries {
+                pad(out, indent + 1);
+                if is_identifier(key) {
+                    out.push_str(key);
+                } else {
+                    write_string(out, key);
+                }
+                out.push_str(": ");
+                write_expr(out, value, indent + 1);
+                out.push_str(",\n");
+            }
+            pad(out, indent);
+            out.push('}');
+        }
+        Expr::Arrow(params, body) => {
+            out.push('(');
+            out.push_str(&params.join(", "));
+            out.push_str(") => ");
+            write_braced(out, body, indent);
+        }
+        Expr::Member(object, property) => {
+            write_operand(out, object, 7, indent);
+            out.push('.');
+            out.push_str(property);
+        }
+        Expr::Index(object, index) => {
+            write_operand(out, object, 7, indent);
+            out.push('[');
+            write_expr(out, index, indent);
+            out.push(']');
+        }
+        Expr::Call(callee, args) => {
+            write_operand(out, callee, 7, indent);
+            out.push('(');
+            write_list(out, args, indent);
+            out.push(')');
+        }
+        Expr::New(callee, args) => {
+            out.push_str("new ");
+            // `new a.b()` is fine, but a call on the callee would take the
+            // args: `new (f())()`
+            let needs_parens = !is_constructor_path(callee);
+            if needs_parens {
+                out.push('(');
+            }
+            write_expr(out, callee, indent);
+            if needs_parens {
+                out.push(')');
+            }
+            out.push('(');
+            write_list(out, args, indent);
+            out.push(')');
+        }
+        Expr::Binary(lhs, op, rhs) => {
+            // Left associative, so the right side is wrapped on ties
+            write_operand(out, lhs, op.precedence(), indent);
+            out.push(' ');
+            out.push_str(op.as_str());
+            out.push(' ');
+            write_operand(out, rhs, op.precedence() + 1, indent);
+        }
+        Expr::Not(expr) => {
+            out.push('!');
+            write_operand(out, expr, 6, indent);
+        }
+        Expr::Raw(code) => out.push_str(code),
+    }
+}
+
+/// Double quoted JS string, it's valid JSON too
+pub(super) fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // Line terminators on JS strings
+            '\u{2028}' | '\u{2029}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `a` or `a.b.c`
+fn is_constructor_path(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(_) => true,
+        Expr::Member(object, _) => is_constructor_path(object),
+        _ => false,
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+
+    (first.is_ascii_alphabetic() || first == '_' || first == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Expr, Stmt};
+    use super::print;
+
+    #[test]
+    fn precedence() {
+        let a = Expr::ident("a");
+        let b = Expr::ident("b");
+        let c = Expr::ident("c");
+
+        let expr = a.clone().or(b.clone()).and(c.clone());
+        assert_eq!(expr.to_string(), "(a || b) && c");
+
+        let expr = a.clone().and(b.clone().or(c.clone()));
+        assert_eq!(expr.to_string(), "a && (b || c)");
+
+        let expr = a.clone() - (b.clone() - c.clone());
+        assert_eq!(expr.to_string(), "a - (b - c)");
+
+        let expr = !a.clone().strict_eq(b.clone());
+        assert_eq!(expr.to_string(), "!(a === b)");
+
+        let expr = Expr::raw("a ?? b").member("length");
+        assert_eq!(expr.to_string(), "(a ?? b).length");
+
+        let expr = Expr::ident("Map")
+            .construct(vec![])
+            .method("get", vec![a.clone()]);
+        assert_eq!(expr.to_string(), "new Map().get(a)");
+
+        let expr = Expr::ident("f").call(vec![]).construct(vec![b]);
+        assert_eq!(expr.to_string(), "new (f())(b)");
+    }
+
+    #[test]
+    fn strings_and_keys() {
+        let expr = Expr::string("say \"hi\"\n\\\u{2028}");
+        assert_eq!(expr.to_string(), r#""say \"hi\"\n\\\u2028""#);
+
+        let expr = Expr::object(vec![
+            ("users", Expr::Null),
+            ("users/list", Expr::Null),
+            ("2fa", Expr::Null),
+            ("$id", Expr::Null),
+        ]);
+        assert_eq!(
+            expr.to_string(),
+            "{\n  users: null,\n  \"users/list\": null,\n  \"2fa\": null,\n  $id: null,\n}"
+        );
+    }
+
+    #[test]
+    fn statements() {
+        let out = Expr::ident("out");
+        let stmts = vec![
+            Stmt::comment("static\nchildren"),
+            Stmt::Block(vec![
+                Stmt::constant(
+                    "children",
+                    Expr::object(vec![(
+                        "a",
+                        Expr::arrow(vec![Stmt::Return(Some(Expr::number(1)))]),
+                    )]),
+                ),
+                Stmt::constant("out", Expr::ident("children").index(Expr::ident("path"))),
+                Stmt::If(out.clone(), vec![Stmt::Return(Some(out.call(vec![])))]),
+            ]),
+            Stmt::Expr(Expr::object(Vec::<(String, Expr)>::new())),
+            Stmt::Switch(
+                Expr::ident("segment"),
+                vec![
+                    (Expr::string("a"), vec![Stmt::Break]),
+                    (Expr::string("b"), vec![]),
+                ],
+            ),
+            Stmt::Return(Some(Expr::Null)),
+        ];
+
+        assert_eq!(
+            print(&stmts),
+            "\
+// static
+// children
+{
+  const children = {
+    a: () => {
+      return 1;
+    },
+  };
+  const out = children[path];
+  if (out) {
+    return out();
+  }
+}
+({});
+switch (segment) {
+  case \"a\": {
+    break;
+  }
+  case \"b\": {}
+}
+return null;
+"
+        );
+    }
+}
@@ -0,0 +1,386 @@
+//! Source maps of the generated manifest.
+//!
+//! The statements of a leaf are wrapped on [`Stmt::Origin`](super::Stmt),
+//! which is printed between marker comments. The formatter keeps comments
+//! on their lines, so after formatting [`SourceMap::extract`] removes the
+//! markers and maps each line to the innermost origin around it.
+
+use std::fmt;
+
+use super::print::write_string;
+
+const MARKER: &str = "// @densky-origin ";
+const MARKER_END: &str = "// @densky-origin-end";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Who generated a fragment of the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Origin {
+    /// Name of the cloud
+    pub cloud: String,
+    /// Pathname of the leaf, `None` for the code that isn't from a leaf
+    pub pathname: Option<String>,
+    /// Route file of the leaf, relative to the manifest
+    pub file: Option<String>,
+}
+
+impl Origin {
+    /// Code of the cloud that isn't from a leaf, like the one before the
+    /// manifest
+    pub fn cloud(cloud: impl Into<String>) -> Origin {
+        Origin {
+            cloud: cloud.into(),
+            pathname: None,
+            file: None,
+        }
+    }
+
+    pub fn leaf(
+        cloud: impl Into<String>,
+        pathname: impl Into<String>,
+        file: Option<String>,
+    ) -> Origin {
+        Origin {
+            cloud: cloud.into(),
+            pathname: Some(pathname.into()),
+            file,
+        }
+    }
+
+    /// Source of the mapped lines, the route file or the cloud
+    fn source(&self) -> String {
+        match &self.file {
+            Some(file) => file.clone(),
+            None => format!("cloud:{}", self.cloud),
+        }
+    }
+
+    /// `// @densky-origin cloud=...;pathname=...;file=...`
+    pub(super) fn marker(&self) -> String {
+        let mut out = String::from(MARKER);
+        out.push_str("cloud=");
+        escape(&mut out, &self.cloud);
+        if let Some(pathname) = &self.pathname {
+            out.push_str(";pathname=");
+            escape(&mut out, pathname);
+        }
+        if let Some(file) = &self.file {
+            out.push_str(";file=");
+            escape(&mut out, file);
+        }
+        out
+    }
+
+    pub(super) fn marker_end() -> &'static str {
+        MARKER_END
+    }
+
+    fn from_marker(marker: &str) -> Option<Origin> {
+        let mut origin = Origin::cloud("");
+        let mut has_cloud = false;
+        for field in marker.split(';') {
+            let (key, value) = field.split_once('=')?;
+            let value = unescape(value)?;
+            match key {
+                "cloud" => {
+                    origin.cloud = value;
+                    has_cloud = true;
+                }
+                "pathname" => origin.pathname = Some(value),
+                "file" => origin.file = Some(value),
+                _ => return None,
+            }
+        }
+
+        has_cloud.then_some(origin)
+    }
+}
+
+/// `http/router on "/users"`
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.cloud)?;
+        if let Some(pathname) = &self.pathname {
+            write!(f, " on {pathname:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The separators of the marker and the line breaks are written as `%XX`
+fn escape(out: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '%' | ';' | '=' | '\n' | '\r' => out.push_str(&format!("%{:02X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+
+        let hex: String = chars.by_ref().take(2).collect();
+        let code = u32::from_str_radix(&hex, 16).ok()?;
+        out.push(char::from_u32(code)?);
+    }
+
+    Some(out)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Mapping {
+    source: usize,
+    name: Option<usize>,
+}
+
+/// Source map (v3) with one mapping for each generated line. The lines are
+/// mapped to the start of their source, it's enough to find the route file
+/// or the cloud from a stack trace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Generated file, relative to the map
+    pub file: String,
+    /// Route files and clouds
+    sources: Vec<String>,
+    /// Pathnames of the leaves
+    names: Vec<String>,
+    /// Origin of each generated line
+    lines: Vec<Option<Mapping>>,
+}
+
+impl SourceMap {
+    /// Remove the origin markers of the code and map its lines.
+    /// ```
+    /// use densky_adapter::js::{print, Origin, SourceMap, Stmt};
+    ///
+    /// let origin = Origin::leaf("http/router", "/users", Some("../src/http/users.ts".into()));
+    /// let code = print(&[
+    ///     Stmt::comment("manifest"),
+    ///     Stmt::Origin(origin, vec![Stmt::comment("/users")]),
+    /// ]);
+    ///
+    /// let (code, source_map) = SourceMap::extract(&code, "manifest.ts");
+    /// assert_eq!(code, "// manifest\n// /users\n");
+    /// assert_eq!(source_map.origin_of(0), None);
+    /// assert_eq!(
+    ///     source_map.origin_of(1),
+    ///     Some(("../src/http/users.ts", Some("/users")))
+    /// );
+    /// ```
+    pub fn extract(code: &str, file: impl Into<String>) -> (String, SourceMap) {
+        let mut source_map = SourceMap {
+            file: file.into(),
+            ..SourceMap::default()
+        };
+        let mut out = String::with_capacity(code.len());
+        // The innermost origin is the last
+        let mut stack: Vec<Mapping> = Vec::new();
+
+        for line in code.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if trimmed == MARKER_END {
+                stack.pop();
+                continue;
+            }
+            if let Some(origin) = trimmed.strip_prefix(MARKER).and_then(Origin::from_marker) {
+                let mapping = source_map.mapping(&origin);
+                stack.push(mapping);
+                continue;
+            }
+
+            out.push_str(line);
+            source_map.lines.push(stack.last().copied());
+        }
+
+        (out, source_map)
+    }
+
+    fn mapping(&mut self, origin: &Origin) -> Mapping {
+        fn position(items: &mut Vec<String>, item: String) -> usize {
+            items.iter().position(|i| i == &item).unwrap_or_else(|| {
+                items.push(item);
+                items.len() - 1
+            })
+        }
+
+        Mapping {
+            source: position(&mut self.sources, origin.source()),
+            name: origin
+                .pathname
+                .clone()
+                .map(|pathname| position(&mut self.names, pathname)),
+        }
+    }
+
+    /// Source and leaf pathname of a generated line (zero-based)
+    pub fn origin_of(&self, line: usize) -> Option<(&str, Option<&str>)> {
+        let mapping = (*self.lines.get(line)?)?;
+        Some((
+            self.sources[mapping.source].as_str(),
+            mapping.name.map(|name| self.names[name].as_str()),
+        ))
+    }
+
+    /// `mappings` field, one segment at the start of each mapped line
+    pub fn mappings(&self) -> String {
+        let mut out = String::new();
+        let (mut prev_source, mut prev_name) = (0, 0);
+        for (i, mapping) in self.lines.iter().enumerate() {
+            if i > 0 {
+                out.push(';');
+            }
+            let Some(mapping) = mapping else {
+                continue;
+            };
+
+            // Generated column, source, source line and source column
+            write_vlq(&mut out, 0);
+            write_vlq(&mut out, mapping.source as i64 - prev_source as i64);
+            write_vlq(&mut out, 0);
+            write_vlq(&mut out, 0);
+            prev_source = mapping.source;
+
+            if let Some(name) = mapping.name {
+                write_vlq(&mut out, name as i64 - prev_name as i64);
+                prev_name = name;
+            }
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |items: &[String]| {
+            let mut out = String::new();
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(&mut out, item);
+            }
+            out
+        };
+
+        let mut file = String::new();
+        write_string(&mut file, &self.file);
+
+        format!(
+            "{{\"version\":3,\"file\":{file},\"sources\":[{}],\"names\":[{}],\"mappings\":\"{}\"}}",
+            list(&self.sources),
+            list(&self.names),
+            self.mappings()
+        )
+    }
+}
+
+/// Base64 VLQ, the sign goes on the lowest bit
+fn write_vlq(out: &mut String, value: i64) {
+    let mut vlq = if value < 0 {
+        (value.unsigned_abs() << 1) | 1
+    } else {
+        (value as u64) << 1
+    };
+
+    loop {
+        let mut digit = vlq & 0b11111;
+        vlq >>= 5;
+        if vlq > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+
+        if vlq == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{print, Expr, Stmt};
+    use super::{write_vlq, Origin, SourceMap};
+
+    #[test]
+    fn vlq() {
+        let encode = |value| {
+            let mut out = String::new();
+            write_vlq(&mut out, value);
+            out
+        };
+
+        assert_eq!(encode(0), "A");
+        assert_eq!(encode(1), "C");
+        assert_eq!(encode(-1), "D");
+        assert_eq!(encode(15), "e");
+        assert_eq!(encode(16), "gB");
+        assert_eq!(encode(-17), "jB");
+    }
+
+    #[test]
+    fn marker() {
+        let origin = Origin::leaf("http/router", "/a;b=%", Some("x\ny.ts".into()));
+        let marker = origin.marker();
+        assert!(!marker.contains('\n'));
+
+        let (code, source_map) = SourceMap::extract(&format!("{marker}\nx;\n"), "manifest.ts");
+        assert_eq!(code, "x;\n");
+        assert_eq!(source_map.origin_of(0), Some(("x\ny.ts", Some("/a;b=%"))));
+
+        // Other comments are kept
+        let (code, source_map) =
+            SourceMap::extract("// @densky-origin of the route\n", "manifest.ts");
+        assert_eq!(code, "// @densky-origin of the route\n");
+        assert_eq!(source_map.origin_of(0), None);
+    }
+
+    #[test]
+    fn nested_origins() {
+        let root = Origin::cloud("http/router");
+        let users = Origin::leaf("http/router", "/users", Some("../src/http/users.ts".into()));
+        let user = Origin::leaf("http/router", "/users/$id", None);
+
+        let code = print(&[Stmt::Origin(
+            root,
+            vec![
+                Stmt::If(
+                    Expr::ident("a"),
+                    vec![Stmt::Origin(
+                        users,
+                        vec![
+                            Stmt::Origin(user, vec![Stmt::Return(Some(Expr::number(1)))]),
+                            Stmt::Return(Some(Expr::number(2))),
+                        ],
+                    )],
+                ),
+                Stmt::Return(Some(Expr::Null)),
+            ],
+        )]);
+
+        let (code, source_map) = SourceMap::extract(&code, "manifest.ts");
+        assert_eq!(
+            code,
+            "if (a) {\n  return 1;\n  return 2;\n}\nreturn null;\n"
+        );
+
+        let cloud = Some(("cloud:http/router", None));
+        let users = Some(("../src/http/users.ts", Some("/users")));
+        let user = Some(("cloud:http/router", Some("/users/$id")));
+        let lines: Vec<_> = (0..6).map(|line| source_map.origin_of(line)).collect();
+        assert_eq!(lines, vec![cloud, user, users, cloud, cloud, None]);
+
+        assert_eq!(
+            source_map.to_json(),
+            "{\"version\":3,\"file\":\"manifest.ts\",\
+             \"sources\":[\"cloud:http/router\",\"../src/http/users.ts\"],\
+             \"names\":[\"/users\",\"/users/$id\"],\
+             \"mappings\":\"AAAA;AAAAC;ACAAD;ADAA;AAAA\"}"
+        );
+    }
+}
@@ -1,14 +1,20 @@
 extern crate ahash;
 extern crate anyhow;
 extern crate pathdiff;
+extern crate regex;
 pub extern crate semver;
+pub extern crate serde;
 pub extern crate thiserror;
 
+#[cfg(test)]
+extern crate proptest;
+
 pub use ahash::{AHashMap, AHashSet};
 pub use anyhow::{anyhow, Context as ErrorContext, Error, Result};
 
 mod calls;
 pub mod context;
+pub mod js;
 pub mod macros;
 pub mod optimized_tree;
 pub mod utils;
@@ -20,4 +26,15 @@ pub struct CompileContext {
     pub output_dir: String,
     pub cwd: String,
     pub verbose: bool,
+    pub mode: CompileMode,
+}
+
+/// What the output is for, the clouds can generate other code for each one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompileMode {
+    /// `densky dev`, the files are reloaded on each change
+    #[default]
+    Development,
+    /// `densky build`, the output must be deployable as is
+    Production,
 }
@@ -1,24 +1,24 @@
-use std::{
-    fmt,
-    hash::Hash,
-    path::PathBuf,
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-};
+use std::{collections::BTreeMap, path::PathBuf};
 
-use crate::{log_debug, log_error, utils::join_paths, AHashMap};
+use serde::{Deserialize, Serialize};
 
-use super::OptimizedTreeNode;
+use crate::{log_error, utils::join_paths};
 
-type AsyncNode<T> = Arc<RwLock<T>>;
+use super::{Arena, NodeId, OptimizedTreeNode, RouteConflict};
+use crate::CloudFile;
 
 /// `Optimized tree container`
-/// It contains all tree context to avoid errors with mutable borrowings.
+/// It owns all the nodes, they're linked by their [`NodeId`].
+#[derive(Serialize, Deserialize)]
 pub struct OptimizedTreeContainer {
     output_dir: String,
-    root: Option<u64>,
-    pub nodes: SingleContainer<OptimizedTreeNode>,
-    pub leafs: SingleContainer<()>,
+    root: Option<NodeId>,
+    pub nodes: Arena<OptimizedTreeNode>,
     pub single_thorn: SingleThornContainer,
+    /// Relative path of each inserted file -> The node that represents it
+    pub(super) files: BTreeMap<String, NodeId>,
+    /// Files that couldn't be inserted, retried on each removal
+    pub(super) conflicts: Vec<(CloudFile, RouteConflict)>,
 }
 
 impl OptimizedTreeContainer {
@@ -30,9 +30,10 @@ impl OptimizedTreeContainer {
         OptimizedTreeContainer {
             output_dir: output_dir.as_ref().to_string(),
             root: None,
-            nodes: SingleContainer::new(),
-            leafs: SingleContainer::new(),
+            nodes: Arena::new(),
             single_thorn: SingleThornContainer::new(),
+            files: BTreeMap::new(),
+            conflicts: Vec::new(),
         }
     }
 
@@ -43,72 +44,82 @@ impl OptimizedTreeContainer {
 
     /// Create root node.
     /// This will overwrite the root
-    pub fn create_root(&mut self) -> AsyncNode<OptimizedTreeNode> {
+    pub fn create_root(&mut self) -> NodeId {
         let mut root = OptimizedTreeNode::default();
         root.pathname = String::new();
         root.output_path = Some(join_paths("_index", &self.output_dir).into());
         root.is_root = true;
 
-        let root_id = self.nodes.add(root);
+        let root_id = self.add_node(root);
         self.root = Some(root_id);
 
-        self.nodes.get(root_id).unwrap()
+        root_id
     }
 
     /// Try to get root node.
     /// If the root isn't setted `None` is returned.
     /// ```
-    /// # use densky_core::optimized_tree::WalkerContainer;
+    /// # use densky_adapter::optimized_tree::OptimizedTreeContainer;
     /// #
-    /// let mut container = WalkerContainer::new("output_dir");
+    /// let mut container = OptimizedTreeContainer::new("output_dir");
     ///
     /// assert!(container.get_root().is_none()); // No root
     ///
     /// container.create_root();
     /// assert!(container.get_root().is_some()); // Expected node
     /// ```
-    pub fn get_root(&self) -> Option<AsyncNode<OptimizedTreeNode>> {
-        if let Some(root) = self.root {
-            self.nodes.get(root)
-        } else {
-            None
-        }
+    pub fn get_root(&self) -> Option<&OptimizedTreeNode> {
+        self.root.and_then(|root| self.nodes.get(root))
     }
 
     /// If the root isn't setted `None` is returned.
-    pub fn get_root_id(&self) -> Option<u64> {
-        self.root.clone()
+    pub fn get_root_id(&self) -> Option<NodeId> {
+        self.root
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = AsyncNode<OptimizedTreeNode>> + '_ {
-        self.nodes.inner.iter().map(|f| f.1).cloned()
+    /// Add the node with the next id
+    pub fn add_node(&mut self, mut node: OptimizedTreeNode) -> NodeId {
+        self.nodes.add_with(|id| {
+            node.id = id;
+            node
+        })
     }
 
-    pub fn transverse(&self) -> Vec<AsyncNode<OptimizedTreeNode>> {
-        self.transverse_node(self.root.unwrap())
+    /// Run `f` with the node out of the container, so both can be mutated.
+    /// The node isn't on the container while `f` runs.
+    pub(super) fn with_node<R>(
+        &mut self,
+        id: NodeId,
+        f: impl FnOnce(&mut OptimizedTreeNode, &mut Self) -> R,
+    ) -> R {
+        let mut node = std::mem::take(&mut self.nodes[id]);
+        let result = f(&mut node, self);
+        self.nodes[id] = node;
+        result
+    }
+
+    /// All the nodes, sorted by id
+    pub fn iter(&self) -> impl Iterator<Item = &OptimizedTreeNode> {
+        self.nodes.iter().map(|(_, node)| node)
     }
 
-    pub fn transverse_node(&self, id: u64) -> Vec<AsyncNode<OptimizedTreeNode>> {
-        let root = self.nodes.get(id).unwrap();
+    pub fn transverse(&self) -> Vec<&OptimizedTreeNode> {
+        self.transverse_node(self.root.unwrap())
+    }
 
-        let dynamic_children = &root.read().unwrap().dynamic_children;
-        let static_children = &root.read().unwrap().static_children;
-        let mut out = Vec::with_capacity(1 + dynamic_children.len() + static_children.len());
+    pub fn transverse_node(&self, id: NodeId) -> Vec<&OptimizedTreeNode> {
+        let root = &self.nodes[id];
+        let mut out =
+            Vec::with_capacity(1 + root.dynamic_children.len() + root.static_children.len());
 
-        out.push(root.clone());
+        out.push(root);
 
-        for (_, id) in static_children.iter() {
-            let a = self.transverse_node(*id);
-            for b in a.into_iter() {
-                out.push(b);
-            }
+        for (_, id) in root.static_children.iter() {
+            out.extend(self.transverse_node(*id));
         }
 
-        for (_, id) in dynamic_children.iter() {
-            let a = self.transverse_node(*id);
-            for b in a.into_iter() {
-                out.push(b);
-            }
+        for (_, id) in root.dynamic_children.iter() {
+            out.extend(self.transverse_node(*id));
         }
 
         out
@@ -116,91 +127,23 @@ impl OptimizedTreeContainer {
 }
 
 impl IntoIterator for OptimizedTreeContainer {
-    type Item = AsyncNode<OptimizedTreeNode>;
-    type IntoIter = std::collections::hash_map::IntoValues<u64, Self::Item>;
+    type Item = OptimizedTreeNode;
+    type IntoIter = <Arena<OptimizedTreeNode> as IntoIterator>::IntoIter;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.nodes.inner.into_values()
+        self.nodes.into_iter()
     }
 }
 
-pub struct SingleContainer<T> {
-    pub(super) inner: AHashMap<u64, AsyncNode<T>>,
-}
-
-impl<T> SingleContainer<T> {
-    pub fn new() -> SingleContainer<T> {
-        SingleContainer {
-            inner: AHashMap::new(),
-        }
-    }
-
-    pub fn hash(&self, x: impl Hash) -> u64 {
-        self.inner.hasher().hash_one(x)
-    }
-
-    pub fn insert(&mut self, id: u64, x: T) {
-        let is_overwriting = self.inner.insert(id, Arc::new(RwLock::new(x))).is_some();
-        if is_overwriting {
-            panic!("{}", "Nodes should be unique. Overwriting: {id}");
-        }
-    }
-
-    pub fn get(&self, id: u64) -> Option<AsyncNode<T>> {
-        self.inner.get(&id).cloned()
-    }
-
-    pub fn get_by(&self, x: impl Hash) -> Option<AsyncNode<T>> {
-        self.get(self.hash(x))
-    }
-
-    pub fn get_reader(&self, id: u64) -> Option<RwLockReadGuard<T>> {
-        self.inner.get(&id).and_then(|x| x.read().ok())
-    }
-
-    pub fn get_writer(&self, id: u64) -> Option<RwLockWriteGuard<T>> {
-        self.inner.get(&id).and_then(|x| x.write().ok())
-    }
-
-    pub fn remove(&mut self, id: u64) {
-        log_debug!(["SingleContainer"] "Node deleted: {id}");
-        self.inner.remove(&id);
-    }
-}
-
-impl<T: Hash> SingleContainer<T> {
-    pub fn add(&mut self, x: T) -> u64 {
-        let id = self.hash(&x);
-        self.insert(id, x);
-        id
-    }
-}
-
-impl<T: fmt::Debug> SingleContainer<T> {
-    #[cfg(not(debug_assertions))]
-    pub fn debug(&self) {}
-
-    #[cfg(debug_assertions)]
-    pub fn debug(&self) {
-        log_debug!(["optimized_tree::SingleContainer"] "{self:#?}");
-    }
-}
-
-impl<T: fmt::Debug> fmt::Debug for SingleContainer<T> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.inner.fmt(f)
-    }
-}
-
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SingleThornContainer {
-    inner: AHashMap<String, AHashMap<String, u64>>,
+    inner: BTreeMap<String, BTreeMap<String, NodeId>>,
 }
 
 impl SingleThornContainer {
     pub fn new() -> Self {
         Self {
-            inner: AHashMap::new(),
+            inner: BTreeMap::new(),
         }
     }
 
@@ -208,7 +151,7 @@ impl SingleThornContainer {
         self.get(name, path).is_some()
     }
 
-    pub fn get(&self, name: &String, path: &String) -> Option<u64> {
+    pub fn get(&self, name: &String, path: &String) -> Option<NodeId> {
         if let Some(thorns) = self.inner.get(path) {
             thorns.get(name).copied()
         } else {
@@ -216,7 +159,7 @@ impl SingleThornContainer {
         }
     }
 
-    pub fn get_all(&self, name: &String, path: &String) -> Vec<u64> {
+    pub fn get_all(&self, name: &String, path: &String) -> Vec<NodeId> {
         let mut out = Vec::new();
 
         if let Some(id) = self.get(name, path) {
@@ -233,8 +176,8 @@ impl SingleThornContainer {
         out
     }
 
-    pub fn get_all_on(&self, path: &String) -> AHashMap<String, u64> {
-        let mut out = AHashMap::new();
+    pub fn get_all_on(&self, path: &String) -> BTreeMap<String, NodeId> {
+        let mut out = BTreeMap::new();
 
         if let Some(thorns) = self.inner.get(path) {
             for (name, id) in thorns {
@@ -245,8 +188,8 @@ impl SingleThornContainer {
         out
     }
 
-    pub fn get_all_of(&self, path: &String) -> AHashMap<String, Vec<u64>> {
-        let mut out = AHashMap::new();
+    pub fn get_all_of(&self, path: &String) -> BTreeMap<String, Vec<NodeId>> {
+        let mut out = BTreeMap::new();
 
         if let Some(thorns) = self.inner.get(path) {
             for (name, id) in thorns {
@@ -272,11 +215,11 @@ impl SingleThornContainer {
         out
     }
 
-    pub fn insert(&mut self, name: String, path: String, node: u64) -> bool {
+    pub fn insert(&mut self, name: String, path: String, node: NodeId) -> bool {
         let thorns = if let Some(thorns) = self.inner.get_mut(&path) {
             thorns
         } else {
-            self.inner.insert(path.clone(), AHashMap::new());
+            self.inner.insert(path.clone(), BTreeMap::new());
             self.inner.get_mut(&path).unwrap()
         };
 
@@ -288,23 +231,45 @@ impl SingleThornContainer {
             true
         }
     }
+
+    /// Remove the thorn with this node.
+    /// Returns `false` if the node isn't a thorn
+    pub fn remove(&mut self, node: NodeId) -> bool {
+        let thorn = self.inner.iter().find_map(|(path, thorns)| {
+            thorns
+                .iter()
+                .find(|(_, id)| **id == node)
+                .map(|(name, _)| (path.clone(), name.clone()))
+        });
+
+        if let Some((path, name)) = thorn {
+            let thorns = self.inner.get_mut(&path).unwrap();
+            thorns.remove(&name);
+            if thorns.is_empty() {
+                self.inner.remove(&path);
+            }
+            return true;
+        }
+
+        false
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::SingleThornContainer;
+    use super::{NodeId, SingleThornContainer};
 
     #[test]
     fn single_thorn_container() {
         let mut container = SingleThornContainer::new();
-        container.insert("middleware".into(), "a/b/c".into(), 1);
-        container.insert("middleware".into(), "a".into(), 3);
-        container.insert("middleware".into(), "a/b".into(), 2);
-        container.insert("fallback".into(), "a/b".into(), 4);
+        container.insert("middleware".into(), "a/b/c".into(), NodeId(1));
+        container.insert("middleware".into(), "a".into(), NodeId(3));
+        container.insert("middleware".into(), "a/b".into(), NodeId(2));
+        container.insert("fallback".into(), "a/b".into(), NodeId(4));
 
         assert!(container.inner.len() == 3);
 
         let middlewares = container.get_all(&"middleware".into(), &"a/b/c".into());
-        assert_eq!(middlewares, [1, 2, 3]);
+        assert_eq!(middlewares, [NodeId(1), NodeId(2), NodeId(3)]);
     }
 }
@@ -0,0 +1,215 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use super::{NodeId, OptimizedTreeContainer};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GraphNodeKind {
+    Root,
+    Static,
+    /// Shared prefix of dynamic routes
+    Prefix,
+    /// Prefix shared by several routes, created by merging them
+    MergedPrefix,
+    Param,
+    Index,
+    Thorn,
+}
+
+struct GraphNode {
+    id: String,
+    label: String,
+    kind: GraphNodeKind,
+}
+
+struct GraphEdge {
+    from: String,
+    to: String,
+    /// Edges to thorns are dashed
+    dashed: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+impl OptimizedTreeContainer {
+    /// Export the tree as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let graph = self.graph();
+        let mut out = String::from("flowchart TD\n");
+
+        for node in &graph.nodes {
+            let label = node.label.replace('"', "#quot;");
+            let (open, close) = match node.kind {
+                GraphNodeKind::Root => ("((", "))"),
+                GraphNodeKind::Static => ("[", "]"),
+                GraphNodeKind::Prefix => ("[/", "/]"),
+                GraphNodeKind::MergedPrefix => ("[\\", "\\]"),
+                GraphNodeKind::Param => ("{{", "}}"),
+                GraphNodeKind::Index => ("([", "])"),
+                GraphNodeKind::Thorn => (">", "]"),
+            };
+            let _ = writeln!(out, "    {}{open}\"{label}\"{close}", node.id);
+        }
+
+        for edge in &graph.edges {
+            let arrow = if edge.dashed { "-.->" } else { "-->" };
+            let _ = writeln!(out, "    {} {arrow} {}", edge.from, edge.to);
+        }
+
+        out
+    }
+
+    /// Export the tree as a Graphviz (DOT) graph
+    pub fn to_dot(&self) -> String {
+        let graph = self.graph();
+        let mut out = String::from("digraph OptimizedTree {\n");
+
+        for node in &graph.nodes {
+            let attrs = match node.kind {
+                GraphNodeKind::Root => "shape=doublecircle",
+                GraphNodeKind::Static => "shape=box",
+                GraphNodeKind::Prefix => "shape=parallelogram",
+                GraphNodeKind::MergedPrefix => "shape=parallelogram, style=bold",
+                GraphNodeKind::Param => "shape=hexagon",
+                GraphNodeKind::Index => "shape=note",
+                GraphNodeKind::Thorn => "shape=cds",
+            };
+            let _ = writeln!(out, "    {} [label={:?}, {attrs}];", node.id, node.label);
+        }
+
+        for edge in &graph.edges {
+            let attrs = if edge.dashed { " [style=dashed]" } else { "" };
+            let _ = writeln!(out, "    {} -> {}{attrs};", edge.from, edge.to);
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn graph(&self) -> Graph {
+        let mut graph = Graph::default();
+        if let Some(root) = self.get_root_id() {
+            // Files relative to the cloud folder
+            let files = self
+                .files
+                .iter()
+                .map(|(file, id)| (*id, file.as_str()))
+                .collect();
+            let mut thorn_paths = BTreeSet::new();
+            self.graph_node(
+                root,
+                "/".into(),
+                GraphNodeKind::Root,
+                &files,
+                &mut thorn_paths,
+                &mut graph,
+            );
+        }
+        graph
+    }
+
+    fn graph_node(
+        &self,
+        id: NodeId,
+        label: String,
+        kind: GraphNodeKind,
+        files: &BTreeMap<NodeId, &str>,
+        thorn_paths: &mut BTreeSet<String>,
+        graph: &mut Graph,
+    ) {
+        let Some(node) = self.nodes.get(id) else {
+            return;
+        };
+
+        let node_id = format!("n{id}");
+        graph.nodes.push(GraphNode {
+            id: node_id.clone(),
+            label,
+            kind,
+        });
+
+        let edge = |graph: &mut Graph, to: String, dashed: bool| {
+            graph.edges.push(GraphEdge {
+                from: node_id.clone(),
+                to,
+                dashed,
+            })
+        };
+
+        // The file that handles the node: its index or the leaf itself
+        let file = match node.index {
+            Some(index) => Some((format!("n{index}"), index)),
+            None if node.input_path.is_some() => Some((format!("f{id}"), id)),
+            None => None,
+        };
+        if let Some((file_id, file)) = file {
+            graph.nodes.push(GraphNode {
+                id: file_id.clone(),
+                label: files.get(&file).copied().unwrap_or_default().to_string(),
+                kind: GraphNodeKind::Index,
+            });
+            edge(graph, file_id, false);
+        }
+
+        // Static and prefix nodes can share the path, the thorns go on the
+        // first one
+        if thorn_paths.insert(node.pathname.clone()) {
+            for (name, thorn) in self.single_thorn.get_all_on(&node.pathname) {
+                let Some(file) = files.get(&thorn) else {
+                    continue;
+                };
+                graph.nodes.push(GraphNode {
+                    id: format!("n{thorn}"),
+                    label: format!("{name}: {file}"),
+                    kind: GraphNodeKind::Thorn,
+                });
+                edge(graph, format!("n{thorn}"), true);
+            }
+        }
+
+        let mut children: Vec<(NodeId, String, GraphNodeKind)> = Vec::new();
+        for (path, id) in node.static_children.iter() {
+            children.push((*id, path.clone(), GraphNodeKind::Static));
+        }
+        for (path, id) in node.dynamic_children.iter() {
+            let is_merged = self.nodes.get(*id).is_some_and(|child| {
+                child.input_path.is_none() && child.index.is_none() && child.children().count() > 1
+            });
+            let kind = if is_merged {
+                GraphNodeKind::MergedPrefix
+            } else {
+                GraphNodeKind::Prefix
+            };
+            children.push((*id, path.clone(), kind));
+        }
+        for (id, pattern) in node.partial_dynamic.iter() {
+            children.push((*id, pattern.to_string(), GraphNodeKind::Param));
+        }
+        for (id, varname, constraint) in node.constrained_dynamic.iter() {
+            children.push((*id, format!("{varname}:{constraint}"), GraphNodeKind::Param));
+        }
+        if let Some((id, varname)) = node.dynamic.as_ref() {
+            let is_optional = self.nodes.get(*id).is_some_and(|child| child.is_optional);
+            let label = if is_optional {
+                format!("{varname}?")
+            } else {
+                varname.clone()
+            };
+            children.push((*id, label, GraphNodeKind::Param));
+        }
+        if let Some((id, varname)) = node.catch_all.as_ref() {
+            children.push((*id, varname.clone(), GraphNodeKind::Param));
+        }
+
+        for (id, label, kind) in children {
+            edge(graph, format!("n{id}"), false);
+            self.graph_node(id, label, kind, files, thorn_paths, graph);
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{NodeId, OptimizedTreeContainer};
+
+/// The route that handles a path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OptimizedTreeMatch {
+    /// File that handles the route
+    pub file: String,
+    /// Map<Name, Value>
+    pub params: BTreeMap<String, String>,
+    /// Map<Name, Vec<FilePath>> of the inherited thorns, in execution order
+    pub thorns: BTreeMap<String, Vec<String>>,
+}
+
+type Params = BTreeMap<String, String>;
+
+impl OptimizedTreeContainer {
+    /// Resolve a path like the generated manifest does, without running it.
+    /// ```ignore
+    /// let route = container.match_path("/users/42").unwrap();
+    /// assert_eq!(route.params.get("id"), Some(&"42".to_string()));
+    /// ```
+    pub fn match_path(&self, path: &str) -> Option<OptimizedTreeMatch> {
+        // Same segments as the request: "/my/path/" -> ["my", "path"]
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let mut segments: Vec<&str> = path.split('/').collect();
+        if segments.last() == Some(&"") {
+            segments.pop();
+        }
+
+        let mut params = Params::new();
+        self.match_node(self.get_root_id()?, &segments, &mut params)
+    }
+
+    /// Root, prefix and param nodes: children, index and then the params
+    fn match_node(
+        &self,
+        id: NodeId,
+        segments: &[&str],
+        params: &mut Params,
+    ) -> Option<OptimizedTreeMatch> {
+        let node = self.nodes.get(id)?;
+        let path = segments.join("/");
+
+        // Static children are the whole remaining path
+        if let Some(child) = node.static_children.get(&path) {
+            return self.match_index(*child, params);
+        }
+
+        for (prefix, child) in node.dynamic_children.iter() {
+            if path != *prefix && !path.starts_with(&format!("{prefix}/")) {
+                continue;
+            }
+
+            let relative_pathname = self.nodes.get(*child)?.relative_pathname.clone();
+            let slash_count = relative_pathname.matches('/').count() + 1;
+            let rest = segments.get(slash_count..).unwrap_or_default();
+            if let Some(found) = self.match_node(*child, rest, params) {
+                return Some(found);
+            }
+        }
+
+        if segments.is_empty() {
+            if let Some(found) = self.match_index(id, params) {
+                return Some(found);
+            }
+        }
+
+        let (segment, rest) = match segments.split_first() {
+            Some((segment, rest)) => (Some(*segment), rest),
+            None => (None, segments),
+        };
+
+        if let Some(segment) = segment {
+            for (child, pattern) in node.partial_dynamic.iter() {
+                let is_match = segment.len() > pattern.before.len() + pattern.after.len()
+                    && segment.starts_with(&pattern.before)
+                    && segment.ends_with(&pattern.after);
+                if !is_match {
+                    continue;
+                }
+
+                let value = &segment[pattern.before.len()..segment.len() - pattern.after.len()];
+                if pattern
+                    .constraint
+                    .as_ref()
+                    .is_some_and(|constraint| !constraint.is_match(value))
+                {
+                    continue;
+                }
+
+                let found = self.match_param(*child, &pattern.varname, value, rest, params);
+                if found.is_some() {
+                    return found;
+                }
+            }
+
+            for (child, varname, constraint) in node.constrained_dynamic.iter() {
+                if !constraint.is_match(segment) {
+                    continue;
+                }
+
+                let found = self.match_param(*child, varname, segment, rest, params);
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+
+        if let Some((child, varname)) = node.dynamic.as_ref() {
+            let is_optional = self.nodes.get(*child)?.is_optional;
+            if is_optional {
+                // Optional params end the path
+                if segments.len() <= 1 {
+                    if let Some(segment) = segment {
+                        params.insert(varname[1..].to_string(), segment.to_string());
+                    }
+                    return self.match_index(*child, params);
+                }
+            } else if let Some(segment) = segment {
+                let found = self.match_param(*child, varname, segment, rest, params);
+                if found.is_some() {
+                    return found;
+                }
+            }
+        }
+
+        if let Some((child, varname)) = node.catch_all.as_ref() {
+            if !segments.is_empty() {
+                let varname = varname.strip_prefix("$...").unwrap_or(varname);
+                params.insert(varname.to_string(), path);
+                return self.match_index(*child, params);
+            }
+        }
+
+        None
+    }
+
+    /// Bind the param and continue on the param node, the param is unbound
+    /// when it doesn't match
+    fn match_param(
+        &self,
+        id: NodeId,
+        varname: &str,
+        value: &str,
+        rest: &[&str],
+        params: &mut Params,
+    ) -> Option<OptimizedTreeMatch> {
+        let varname = &varname[1..];
+        params.insert(varname.to_string(), value.to_string());
+
+        let found = self.match_node(id, rest, params);
+        if found.is_none() {
+            params.remove(varname);
+        }
+        found
+    }
+
+    fn match_index(&self, id: NodeId, params: &Params) -> Option<OptimizedTreeMatch> {
+        let leaf = self.nodes.get(id)?.into_leaf(self);
+
+        Some(OptimizedTreeMatch {
+            file: leaf.index?,
+            params: params.clone(),
+            thorns: leaf.single_thorns,
+        })
+    }
+}
@@ -1,8 +1,16 @@
+mod arena;
 mod container;
+mod export;
+mod matcher;
 mod node;
+mod routes;
+mod update;
 
 #[cfg(test)]
 mod test;
 
+pub use self::arena::{Arena, NodeId};
 pub use self::container::OptimizedTreeContainer;
-pub use self::node::OptimizedTreeNode;
+pub use self::matcher::OptimizedTreeMatch;
+pub use self::node::{OptimizedTreeNode, OptimizedTreeNodeInsertResult, RouteConflict};
+pub use self::routes::OptimizedTreeRoute;
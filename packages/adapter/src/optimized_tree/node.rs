@@ -1,5 +1,6 @@
-use std::sync::atomic::{self, AtomicU64};
+use std::collections::BTreeMap;
 use std::{
+    fmt,
     hash::{Hash, Hasher},
     path::PathBuf,
 };
@@ -8,19 +9,12 @@ use crate::{
     log::PathDebugDisplay,
     log_debug, log_trace,
     utils::{Color, Fmt, StringStripExtend},
-    AHashMap, CloudFileResolve, OptimizedTreeLeaf,
+    CloudFileResolve, OptimizedTreeLeaf, ParamConstraint, SegmentPattern,
 };
 
-use super::OptimizedTreeContainer;
+use serde::{Deserialize, Serialize};
 
-static mut GLOBAL_NEXT_NODE_ID: atomic::AtomicU64 = atomic::AtomicU64::new(0);
-
-pub fn next_node_id() -> u64 {
-    unsafe {
-        GLOBAL_NEXT_NODE_ID.fetch_add(1, atomic::Ordering::Relaxed);
-        GLOBAL_NEXT_NODE_ID.load(atomic::Ordering::Relaxed)
-    }
-}
+use super::{NodeId, OptimizedTreeContainer};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum OptimizedTreeNodeInsertResult {
@@ -28,23 +22,48 @@ pub enum OptimizedTreeNodeInsertResult {
     /// Move current node to `new_parent` with `new_suffix`
     Resolve {
         /// New parent of actual node
-        new_parent: u64,
+        new_parent: NodeId,
         /// New "path" of actual node
         new_suffix: String,
     },
-    RemoveNode,
 
     /// Move current node to `new_node` with `new_suffix`
     /// And create `new_node`
     MergeNodes {
-        new_node: u64,
+        new_node: NodeId,
         new_suffix: String,
     }, // ResolveAndDeleteNode()
+
+    /// The leaf defines a route that's already defined by other file.
+    /// The leaf isn't inserted.
+    Conflict(RouteConflict),
 }
 
-#[derive(Clone, Debug, Default)]
+/// Two files that define the same (or an ambiguous) URL
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteConflict {
+    /// Conflicting URL pattern
+    pub url: String,
+    /// File that was being inserted
+    pub file: String,
+    /// File that already defines the URL
+    pub other_file: String,
+}
+
+impl fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Conflicting route {:?}. {:?} and {:?} define the same URL.",
+            self.url, self.file, self.other_file
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct OptimizedTreeNode {
-    pub id: u64,
+    /// Assigned by the container when the node is added
+    pub id: NodeId,
     /// Absolute path
     pub pathname: String,
 
@@ -55,19 +74,29 @@ pub struct OptimizedTreeNode {
     /// Output file path
     pub output_path: Option<PathBuf>,
 
-    pub static_children: AHashMap<String, u64>,
-    pub dynamic_children: AHashMap<String, u64>,
-    pub index: Option<u64>,
-    pub dynamic: Option<(u64, String)>,
+    pub static_children: BTreeMap<String, NodeId>,
+    pub dynamic_children: BTreeMap<String, NodeId>,
+    pub index: Option<NodeId>,
+    pub dynamic: Option<(NodeId, String)>,
+    /// Dynamic children with a constraint, they're tried in order before `dynamic`
+    pub constrained_dynamic: Vec<(NodeId, String, ParamConstraint)>,
+    /// Params mixed with static text (`report-$year.csv`), they're tried in
+    /// order before `constrained_dynamic`
+    pub partial_dynamic: Vec<(NodeId, SegmentPattern)>,
+    pub catch_all: Option<(NodeId, String)>,
 
     pub is_root: bool,
     pub is_static: bool,
+    pub is_catch_all: bool,
+    pub is_optional: bool,
     pub varname: Option<String>,
+    pub constraint: Option<ParamConstraint>,
+    pub pattern: Option<SegmentPattern>,
 }
 
 impl Hash for OptimizedTreeNode {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_u64(self.id);
+        self.id.hash(state);
     }
 }
 
@@ -78,18 +107,11 @@ impl OptimizedTreeNode {
         output_path: PathBuf,
     ) -> Self {
         Self {
-            id: next_node_id(),
             pathname: relative_pathname.clone(),
             relative_pathname,
             input_path,
             output_path: Some(output_path),
-            static_children: AHashMap::new(),
-            dynamic_children: AHashMap::new(),
-            index: None,
-            dynamic: None,
-            is_root: false,
-            is_static: false,
-            varname: None,
+            ..Default::default()
         }
     }
 
@@ -98,20 +120,7 @@ impl OptimizedTreeNode {
         input_path: Option<PathBuf>,
         output_path: PathBuf,
     ) -> Self {
-        Self {
-            id: next_node_id(),
-            pathname: relative_pathname.clone(),
-            relative_pathname,
-            input_path,
-            output_path: Some(output_path),
-            static_children: AHashMap::new(),
-            dynamic_children: AHashMap::new(),
-            index: None,
-            dynamic: None,
-            is_root: false,
-            is_static: false,
-            varname: None,
-        }
+        Self::new(relative_pathname, input_path, output_path)
     }
 
     pub fn new_child(
@@ -119,22 +128,12 @@ impl OptimizedTreeNode {
         relative_pathname: String,
         output_path: Option<PathBuf>,
     ) -> Self {
-        let pathname = parent_pathname.to_owned()
-            + "/"
-            + relative_pathname.strip_prefix_if_can(&parent_pathname);
+        let pathname = parent_pathname.to_owned() + "/" + &relative_pathname;
         Self {
-            id: next_node_id(),
             pathname: pathname.strip_prefix_if_can(&"/").to_string(),
             relative_pathname,
-            input_path: None,
             output_path,
-            static_children: AHashMap::new(),
-            dynamic_children: AHashMap::new(),
-            index: None,
-            dynamic: None,
-            is_root: false,
-            is_static: false,
-            varname: None,
+            ..Default::default()
         }
     }
 
@@ -143,7 +142,7 @@ impl OptimizedTreeNode {
     /// + None -> Leaf was inserted on self
     pub fn insert(
         &mut self,
-        leaf_id: u64,
+        leaf_id: NodeId,
         file_resolved: CloudFileResolve,
         container: &mut OptimizedTreeContainer,
     ) -> OptimizedTreeNodeInsertResult {
@@ -151,20 +150,32 @@ impl OptimizedTreeNode {
             return OptimizedTreeNodeInsertResult::None;
         }
 
-        let leaf_rw = container
+        let leaf = container
             .nodes
             .get(leaf_id)
             .expect("New leaf should be inserted on container before use it");
-
-        let leaf = leaf_rw.read().unwrap();
-        log_trace!(["OTreeNode"] "Inserting <{1:?}> {0:#?}", leaf.pathname, file_resolved);
+        let leaf_pathname = leaf.pathname.clone();
+        let leaf_relative_pathname = leaf.relative_pathname.clone();
+        log_trace!(["OTreeNode"] "Inserting <{1:?}> {0:#?}", leaf_pathname, file_resolved);
 
         match file_resolved {
             CloudFileResolve::Pass => {
-                let static_pathname = leaf.relative_pathname.strip_prefix_if_can("/").to_string();
+                let static_pathname = leaf_relative_pathname.strip_prefix_if_can("/").to_string();
+
+                let optional_leaf = self
+                    .dynamic_children
+                    .get(&static_pathname)
+                    .and_then(|id| container.nodes.get(*id))
+                    .and_then(|dynamic_child| dynamic_child.optional_dynamic(container));
+                if let Some(optional_leaf) = optional_leaf {
+                    return self.conflict(&static_pathname, leaf_id, optional_leaf, container);
+                }
+
+                if let Some(old_leaf) = self.static_children.get(&static_pathname) {
+                    return self.conflict(&static_pathname, leaf_id, *old_leaf, container);
+                }
 
-                drop(leaf);
-                let mut leaf = leaf_rw.write().unwrap();
+                let leaf = &mut container.nodes[leaf_id];
                 leaf.relative_pathname = static_pathname.clone();
                 leaf.is_static = true;
                 leaf.varname = None;
@@ -173,24 +184,163 @@ impl OptimizedTreeNode {
 
                 OptimizedTreeNodeInsertResult::None
             }
-            CloudFileResolve::Dynamic(prefix, varname, suffix) => {
-                macro_rules! return_fix_suffix {
-                    ($id:expr) => {
-                        if suffix.len() == 0 {
-                            OptimizedTreeNodeInsertResult::None
-                        } else {
-                            OptimizedTreeNodeInsertResult::Resolve {
-                                new_parent: $id,
+            CloudFileResolve::Dynamic(prefix, varname, suffix, Some(constraint)) => {
+                if !prefix.is_empty() {
+                    return self.resolve_dynamic_prefix(prefix, &leaf_relative_pathname, container);
+                }
+
+                let is_leaf = |id: &NodeId| {
+                    container
+                        .nodes
+                        .get(*id)
+                        .is_some_and(|node| node.input_path.is_some())
+                };
+
+                if suffix.is_empty() {
+                    let conflict = self
+                        .constrained_dynamic
+                        .iter()
+                        .find(|(id, _, c)| c == &constraint && is_leaf(id));
+                    if let Some((old_leaf_id, _, _)) = conflict {
+                        return self.conflict(
+                            &leaf_relative_pathname,
+                            leaf_id,
+                            *old_leaf_id,
+                            container,
+                        );
+                    }
+
+                    let leaf = &mut container.nodes[leaf_id];
+                    leaf.is_static = false;
+                    leaf.varname = Some(varname.clone());
+                    leaf.constraint = Some(constraint.clone());
+
+                    self.constrained_dynamic
+                        .push((leaf_id, varname, constraint));
+                    return OptimizedTreeNodeInsertResult::None;
+                }
+
+                let middle_node = self
+                    .constrained_dynamic
+                    .iter()
+                    .find(|(id, v, c)| v == &varname && c == &constraint && !is_leaf(id))
+                    .map(|(id, _, _)| *id);
+
+                let new_parent = if let Some(middle_node) = middle_node {
+                    middle_node
+                } else {
+                    let mut cloned_leaf =
+                        OptimizedTreeNode::new_child(&self.pathname, varname.clone(), None);
+                    cloned_leaf.is_static = false;
+                    cloned_leaf.varname = Some(varname.clone());
+                    cloned_leaf.constraint = Some(constraint.clone());
+
+                    let cloned_leaf = container.add_node(cloned_leaf);
+                    self.constrained_dynamic
+                        .push((cloned_leaf, varname, constraint));
+                    cloned_leaf
+                };
+
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent,
+                    new_suffix: suffix,
+                }
+            }
+            CloudFileResolve::Partial(prefix, pattern, suffix) => {
+                if !prefix.is_empty() {
+                    return self.resolve_dynamic_prefix(prefix, &leaf_relative_pathname, container);
+                }
+
+                let is_leaf = |id: &NodeId| {
+                    container
+                        .nodes
+                        .get(*id)
+                        .is_some_and(|node| node.input_path.is_some())
+                };
+
+                if suffix.is_empty() {
+                    let conflict = self
+                        .partial_dynamic
+                        .iter()
+                        .find(|(id, p)| p.is_equivalent(&pattern) && is_leaf(id));
+                    if let Some((old_leaf_id, _)) = conflict {
+                        return self.conflict(
+                            &leaf_relative_pathname,
+                            leaf_id,
+                            *old_leaf_id,
+                            container,
+                        );
+                    }
+
+                    let leaf = &mut container.nodes[leaf_id];
+                    leaf.is_static = false;
+                    leaf.varname = Some(pattern.varname.clone());
+                    leaf.pattern = Some(pattern.clone());
+
+                    self.partial_dynamic.push((leaf_id, pattern));
+                    return OptimizedTreeNodeInsertResult::None;
+                }
+
+                let middle_node = self
+                    .partial_dynamic
+                    .iter()
+                    .find(|(id, p)| p == &pattern && !is_leaf(id))
+                    .map(|(id, _)| *id);
+
+                let new_parent = if let Some(middle_node) = middle_node {
+                    middle_node
+                } else {
+                    let segment = format!("{}{}{}", pattern.before, pattern.varname, pattern.after);
+                    let mut cloned_leaf =
+                        OptimizedTreeNode::new_child(&self.pathname, segment, None);
+                    cloned_leaf.is_static = false;
+                    cloned_leaf.varname = Some(pattern.varname.clone());
+                    cloned_leaf.pattern = Some(pattern.clone());
+
+                    let cloned_leaf = container.add_node(cloned_leaf);
+                    self.partial_dynamic.push((cloned_leaf, pattern));
+                    cloned_leaf
+                };
+
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent,
+                    new_suffix: suffix,
+                }
+            }
+            CloudFileResolve::Dynamic(prefix, varname, suffix, None) => {
+                if prefix.is_empty() {
+                    if let Some((old_id, old_varname)) = self.dynamic.clone() {
+                        // An optional param is the last part of its path, so the
+                        // children would be ambiguous: "users/$id?" and "users/$id/posts"
+                        if old_varname == varname && container.nodes[old_id].is_optional {
+                            return self.conflict(&varname, leaf_id, old_id, container);
+                        }
+
+                        // Same param, the children are resolved on the existing node
+                        if old_varname == varname && !suffix.is_empty() {
+                            return OptimizedTreeNodeInsertResult::Resolve {
+                                new_parent: old_id,
                                 new_suffix: suffix,
-                            }
+                            };
                         }
-                    };
-                }
 
-                if prefix.len() == 0 {
-                    return if suffix.len() == 0 {
+                        // The existing node only has children, so the leaf is its index
+                        if old_varname == varname && Self::input_file(old_id, container).is_none() {
+                            return container.with_node(old_id, |old_node, container| {
+                                old_node.insert_index(leaf_id, container)
+                            });
+                        }
+
+                        // Duplicated ("$id.ts" twice) or ambiguous ("$a.ts" and "$b.ts")
+                        return self.conflict(&varname, leaf_id, old_id, container);
+                    }
+
+                    return if suffix.is_empty() {
+                        let leaf = &mut container.nodes[leaf_id];
+                        leaf.is_static = false;
+                        leaf.varname = Some(varname.clone());
+
                         self.dynamic = Some((leaf_id, varname));
-                        self.index = leaf.index.clone();
                         OptimizedTreeNodeInsertResult::None
                     } else {
                         let mut cloned_leaf = OptimizedTreeNode::new_child(
@@ -201,7 +351,7 @@ impl OptimizedTreeNode {
                         cloned_leaf.is_static = false;
                         cloned_leaf.varname = Some(varname.clone());
 
-                        let cloned_leaf = container.nodes.add(cloned_leaf);
+                        let cloned_leaf = container.add_node(cloned_leaf);
                         self.dynamic = Some((cloned_leaf, varname));
                         OptimizedTreeNodeInsertResult::Resolve {
                             new_parent: cloned_leaf,
@@ -210,103 +360,27 @@ impl OptimizedTreeNode {
                     };
                 }
 
-                // Search for a common child in the children
-                let common_child: Option<(&u64, Option<String>)> = 'common_child: {
-                    if let Some(new_parent) = self.dynamic_children.get(&prefix) {
-                        Some((new_parent, None))
-                    } else {
-                        for child in self.dynamic_children.iter() {
-                            break 'common_child OptimizedTreeNode::get_common_path(
-                                &prefix, &child.0,
-                            )
-                            .and_then(|common_path| {
-                                Some((
-                                    child.1,
-                                    // Some(_) when common_path is not equal
-                                    // to complete prefix or child relative path
-                                    if &common_path == child.0 {
-                                        None
-                                    } else {
-                                        (common_path != prefix).then_some(common_path)
-                                    },
-                                ))
-                            });
-                        }
-                        None
-                    }
-                };
-
-                match common_child {
+                match self.common_dynamic_child(&prefix) {
                     // Has a common child and needs a merge
-                    Some((common_id, Some(common_path))) => {
-                        let common_child = container.nodes.get_reader(*common_id).unwrap();
-                        log_debug!(["OTreeNode"] "Merging children ({}, {}) from {common_path}", common_child.relative_pathname, leaf.relative_pathname);
-
-                        let old_common_child_pathname = common_child.relative_pathname.clone();
-                        let new_common_child_pathname = common_child
-                            .relative_pathname
-                            .strip_prefix(&common_path)
-                            .unwrap()
-                            .strip_prefix_if_can("/")
-                            .to_string();
-
-                        // Unlock container.nodes from inmutable reference
-                        drop(common_child);
-
-                        container
-                            .nodes
-                            .get_writer(*common_id)
-                            .unwrap()
-                            .relative_pathname = new_common_child_pathname.to_owned();
-
-                        let new_leaf_pathname = leaf
-                            .relative_pathname
-                            .strip_prefix(&common_path)
-                            .unwrap()
-                            .strip_prefix_if_can("/")
-                            .to_string();
-
-                        let mut new_parent =
-                            OptimizedTreeNode::new_child(&self.pathname, common_path.clone(), None);
-                        new_parent.is_static = false;
-
-                        new_parent
-                            .dynamic_children
-                            .insert(new_common_child_pathname, *common_id);
-
-                        let new_parent = container.nodes.add(new_parent);
-
-                        // Borrow self as mutable makes this imposible, so we do
-                        // it before the borrow and just see the magik
-                        self.dynamic_children.insert(common_path, new_parent);
-                        self.dynamic_children
-                            .remove(&old_common_child_pathname)
-                            .expect("Child doesn't exist?");
-
-                        OptimizedTreeNodeInsertResult::MergeNodes {
-                            new_node: new_parent,
-                            new_suffix: new_leaf_pathname,
-                        }
-                    }
+                    Some((common_id, Some(common_path))) => self.merge_dynamic_child(
+                        common_id,
+                        common_path,
+                        &leaf_relative_pathname,
+                        container,
+                    ),
                     // Has a common child, resolve to that child as the new parent
                     Some((common_id, None)) => {
                         log_trace!(["OTreeNode"] "Common child encountered {common_id}");
-                        let new_parent = container.nodes.get_writer(*common_id).unwrap();
+                        let new_parent = &container.nodes[common_id];
 
-                        if let Some(old_leaf_id) = &new_parent.dynamic {
-                            return_fix_suffix!(old_leaf_id.0)
-                        } else {
-                            let prefix = leaf
-                                .relative_pathname
-                                .strip_prefix_if_can(&new_parent.relative_pathname)
-                                .strip_prefix_if_can("/")
-                                .to_string();
-                            // println!("{prefix}");
+                        let prefix = leaf_relative_pathname
+                            .strip_prefix_if_can(&new_parent.relative_pathname)
+                            .strip_prefix_if_can("/")
+                            .to_string();
 
-                            OptimizedTreeNodeInsertResult::Resolve {
-                                new_parent: *common_id,
-                                new_suffix: prefix,
-                            }
+                        OptimizedTreeNodeInsertResult::Resolve {
+                            new_parent: common_id,
+                            new_suffix: prefix,
                         }
                     }
                     // Insert it as normal node
@@ -316,6 +390,10 @@ impl OptimizedTreeNode {
                         new_parent.is_static = false;
 
                         let cloned_leaf = if suffix.len() == 0 {
+                            let leaf = &mut container.nodes[leaf_id];
+                            leaf.is_static = false;
+                            leaf.varname = Some(varname.clone());
+
                             new_parent.dynamic = Some((leaf_id, varname));
                             None
                         } else {
@@ -326,22 +404,18 @@ impl OptimizedTreeNode {
                             );
                             cloned_leaf.is_static = false;
                             cloned_leaf.varname = Some(varname.clone());
-                            let cloned_leaf = container.nodes.add(cloned_leaf);
+                            let cloned_leaf = container.add_node(cloned_leaf);
                             new_parent.dynamic = Some((cloned_leaf, varname));
 
                             Some(cloned_leaf)
                         };
 
-                        drop(leaf);
-                        let mut leaf = container.nodes.get_writer(leaf_id).unwrap();
-                        leaf.relative_pathname = leaf
-                            .relative_pathname
+                        container.nodes[leaf_id].relative_pathname = leaf_relative_pathname
                             .strip_prefix_if_can(&prefix)
                             .strip_prefix_if_can("/")
                             .to_string();
-                        drop(leaf);
 
-                        let new_parent = container.nodes.add(new_parent);
+                        let new_parent = container.add_node(new_parent);
                         self.dynamic_children.insert(prefix, new_parent);
 
                         if let Some(cloned_leaf) = cloned_leaf {
@@ -355,39 +429,85 @@ impl OptimizedTreeNode {
                     }
                 }
             }
+            CloudFileResolve::CatchAll(prefix, varname) => {
+                if prefix.is_empty() {
+                    if let Some((old_leaf_id, _)) = &self.catch_all {
+                        return self.conflict(&varname, leaf_id, *old_leaf_id, container);
+                    }
+
+                    let leaf = &mut container.nodes[leaf_id];
+                    leaf.is_static = false;
+                    leaf.is_catch_all = true;
+                    leaf.varname = Some(varname.clone());
+
+                    self.catch_all = Some((leaf_id, varname));
+                    return OptimizedTreeNodeInsertResult::None;
+                }
+
+                self.resolve_dynamic_prefix(prefix, &leaf_relative_pathname, container)
+            }
+            CloudFileResolve::Optional(prefix, varname) => {
+                if prefix.is_empty() {
+                    if let Some(old_index) = self.index {
+                        return self.conflict(&varname, leaf_id, old_index, container);
+                    }
+                    if let Some((old_leaf_id, _)) = &self.dynamic {
+                        return self.conflict(&varname, leaf_id, *old_leaf_id, container);
+                    }
+
+                    let leaf = &mut container.nodes[leaf_id];
+                    leaf.is_static = false;
+                    leaf.is_optional = true;
+                    leaf.varname = Some(varname.clone());
+
+                    self.dynamic = Some((leaf_id, varname));
+                    return OptimizedTreeNodeInsertResult::None;
+                }
+
+                if let Some(old_leaf_id) = self.static_children.get(&prefix) {
+                    return self.conflict(&prefix, leaf_id, *old_leaf_id, container);
+                }
+
+                self.resolve_dynamic_prefix(prefix, &leaf_relative_pathname, container)
+            }
             CloudFileResolve::Index => {
-                let last_slash = leaf
-                    .pathname
+                let last_slash = leaf_pathname
                     .chars()
                     .rev()
                     .position(|x| x == '/')
-                    .map(|x| leaf.relative_pathname.len() - x);
+                    .map(|x| leaf_relative_pathname.len() - x);
 
                 let last_slash = match last_slash {
-                    Some(expr) if expr > 2 => expr - 1,
-                    _ => {
-                        self.index =
-                            Some(leaf.index.expect("Inserted leaf should have 'index' field"));
-                        return OptimizedTreeNodeInsertResult::RemoveNode;
-                    }
+                    Some(expr) if expr > 1 => expr - 1,
+                    _ => return self.insert_index(leaf_id, container),
                 };
 
-                let parent = leaf.pathname[0..last_slash].to_string();
+                let parent = leaf_pathname[0..last_slash].to_string();
                 if parent.len() == 0 {
-                    self.index = Some(leaf.index.expect("Inserted leaf should have 'index' field"));
-                    return OptimizedTreeNodeInsertResult::RemoveNode;
+                    return self.insert_index(leaf_id, container);
                 }
 
-                drop(leaf);
-                container.nodes.get_writer(leaf_id).unwrap().pathname = parent.clone();
+                container.nodes[leaf_id].pathname = parent.clone();
 
                 OptimizedTreeNodeInsertResult::Resolve {
-                    new_parent: container.nodes.hash(self),
+                    new_parent: self.id,
                     new_suffix: parent,
                 }
             }
+            CloudFileResolve::Group(url_path) => {
+                if url_path.is_empty() {
+                    return self.insert_index(leaf_id, container);
+                }
+
+                container.nodes[leaf_id].relative_pathname = url_path.clone();
+
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent: self.id,
+                    new_suffix: url_path,
+                }
+            }
             CloudFileResolve::SingleThorn(name) => {
-                let path = &leaf.pathname;
+                let path = &leaf_pathname;
                 let last_slash = path
                     .chars()
                     .rev()
@@ -395,10 +515,11 @@ impl OptimizedTreeNode {
                     .map(|f| path.len() - f - 1)
                     .filter(|f| f > &0);
 
+                // Thorns on the cloud folder are on the root
                 let path = if let Some(last_slash) = last_slash {
                     &path[0..last_slash]
                 } else {
-                    &path
+                    ""
                 };
 
                 container
@@ -410,6 +531,270 @@ impl OptimizedTreeNode {
         }
     }
 
+    /// Search for a dynamic child that shares path with `prefix`.
+    ///
+    /// # Returns
+    /// + Some((child, None)) -> The child can be used as the new parent.
+    /// + Some((child, Some(common_path))) -> The child needs a merge with
+    ///   `common_path` as the new middle node.
+    /// + None -> There isn't a common child.
+    fn common_dynamic_child(&self, prefix: &String) -> Option<(NodeId, Option<String>)> {
+        if let Some(new_parent) = self.dynamic_children.get(prefix) {
+            return Some((*new_parent, None));
+        }
+
+        self.dynamic_children
+            .iter()
+            .find_map(|(child_path, child_id)| {
+                OptimizedTreeNode::get_common_path(prefix, child_path).map(|common_path| {
+                    (
+                        *child_id,
+                        // Some(_) when common_path is not equal
+                        // to child relative path, so it needs a merge
+                        (&common_path != child_path).then_some(common_path),
+                    )
+                })
+            })
+    }
+
+    /// Create a middle node for `common_path` and move the common child
+    /// inside it. The leaf should be re-inserted on the new node.
+    fn merge_dynamic_child(
+        &mut self,
+        common_id: NodeId,
+        common_path: String,
+        leaf_pathname: &String,
+        container: &mut OptimizedTreeContainer,
+    ) -> OptimizedTreeNodeInsertResult {
+        let common_child = &container.nodes[common_id];
+        log_debug!(["OTreeNode"] "Merging children ({}, {}) from {common_path}", common_child.relative_pathname, leaf_pathname);
+
+        let old_common_child_pathname = common_child.relative_pathname.clone();
+        let new_common_child_pathname = common_child
+            .relative_pathname
+            .strip_prefix(&common_path)
+            .unwrap()
+            .strip_prefix_if_can("/")
+            .to_string();
+
+        container.nodes[common_id].relative_pathname = new_common_child_pathname.to_owned();
+
+        let new_leaf_pathname = leaf_pathname
+            .strip_prefix(&common_path)
+            .unwrap()
+            .strip_prefix_if_can("/")
+            .to_string();
+
+        let mut new_parent =
+            OptimizedTreeNode::new_child(&self.pathname, common_path.clone(), None);
+        new_parent.is_static = false;
+
+        new_parent
+            .dynamic_children
+            .insert(new_common_child_pathname, common_id);
+
+        let new_parent = container.add_node(new_parent);
+
+        // Borrow self as mutable makes this imposible, so we do
+        // it before the borrow and just see the magik
+        self.dynamic_children.insert(common_path, new_parent);
+        self.dynamic_children
+            .remove(&old_common_child_pathname)
+            .expect("Child doesn't exist?");
+
+        OptimizedTreeNodeInsertResult::MergeNodes {
+            new_node: new_parent,
+            new_suffix: new_leaf_pathname,
+        }
+    }
+
+    /// Resolve a leaf on the dynamic child that owns `prefix`, creating
+    /// it if there isn't one. The leaf is re-resolved without the prefix.
+    fn resolve_dynamic_prefix(
+        &mut self,
+        prefix: String,
+        leaf_pathname: &String,
+        container: &mut OptimizedTreeContainer,
+    ) -> OptimizedTreeNodeInsertResult {
+        match self.common_dynamic_child(&prefix) {
+            Some((common_id, Some(common_path))) => {
+                self.merge_dynamic_child(common_id, common_path, leaf_pathname, container)
+            }
+            Some((common_id, None)) => {
+                let new_parent = &container.nodes[common_id];
+                let new_suffix = leaf_pathname
+                    .strip_prefix_if_can(&new_parent.relative_pathname)
+                    .strip_prefix_if_can("/")
+                    .to_string();
+
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent: common_id,
+                    new_suffix,
+                }
+            }
+            None => {
+                let mut new_parent =
+                    OptimizedTreeNode::new_child(&self.pathname, prefix.clone(), None);
+                new_parent.is_static = false;
+
+                let new_suffix = leaf_pathname
+                    .strip_prefix_if_can(&prefix)
+                    .strip_prefix_if_can("/")
+                    .to_string();
+
+                let new_parent = container.add_node(new_parent);
+                self.dynamic_children.insert(prefix, new_parent);
+
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent,
+                    new_suffix,
+                }
+            }
+        }
+    }
+
+    /// Set the leaf as the index of the node, unless the node is a leaf or
+    /// an optional param already claims it.
+    fn insert_index(
+        &mut self,
+        leaf_id: NodeId,
+        container: &OptimizedTreeContainer,
+    ) -> OptimizedTreeNodeInsertResult {
+        if let Some(optional_leaf) = self.optional_dynamic(container) {
+            return self.conflict("", leaf_id, optional_leaf, container);
+        }
+        if self.input_path.is_some() {
+            return self.conflict("", leaf_id, self.id, container);
+        }
+        if let Some(old_index) = self.index {
+            return self.conflict("", leaf_id, old_index, container);
+        }
+
+        self.index = Some(leaf_id);
+        OptimizedTreeNodeInsertResult::None
+    }
+
+    /// Create the conflict between `leaf_id` and the node that already
+    /// defines the route `url` (relative to self).
+    fn conflict(
+        &self,
+        url: impl AsRef<str>,
+        leaf_id: NodeId,
+        old_id: NodeId,
+        container: &OptimizedTreeContainer,
+    ) -> OptimizedTreeNodeInsertResult {
+        let url = [self.pathname.as_str(), url.as_ref()]
+            .iter()
+            .filter(|part| !part.is_empty())
+            .fold(String::new(), |url, part| url + "/" + part);
+
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: if url.is_empty() { "/".to_string() } else { url },
+            file: Self::route_file(leaf_id, container).unwrap_or_default(),
+            // Self is out of the container while a leaf is inserted on it
+            other_file: if old_id == self.id {
+                self.input_path.as_ref().map(|p| p.display().to_string())
+            } else {
+                Self::route_file(old_id, container)
+            }
+            .unwrap_or_default(),
+        })
+    }
+
+    /// Get a file that defines a route on the node or below it.
+    fn route_file(id: NodeId, container: &OptimizedTreeContainer) -> Option<String> {
+        if let Some(file) = Self::input_file(id, container) {
+            return Some(file);
+        }
+
+        let node = container.nodes.get(id)?;
+        let file = node
+            .index
+            .iter()
+            .chain(node.children())
+            .find_map(|id| Self::route_file(*id, container));
+        file
+    }
+
+    /// Iterate over all the children ids, without the index
+    pub fn children(&self) -> impl Iterator<Item = &NodeId> {
+        self.static_children
+            .values()
+            .chain(self.dynamic_children.values())
+            .chain(self.partial_dynamic.iter().map(|(id, _)| id))
+            .chain(self.constrained_dynamic.iter().map(|(id, _, _)| id))
+            .chain(self.dynamic.iter().map(|(id, _)| id))
+            .chain(self.catch_all.iter().map(|(id, _)| id))
+    }
+
+    /// Unlink a child (or the index) of the node.
+    /// # Returns
+    /// `true` if the node was a child
+    pub fn remove_child(&mut self, id: NodeId) -> bool {
+        let len = self.static_children.len()
+            + self.dynamic_children.len()
+            + self.partial_dynamic.len()
+            + self.constrained_dynamic.len();
+
+        self.static_children.retain(|_, child| *child != id);
+        self.dynamic_children.retain(|_, child| *child != id);
+        self.partial_dynamic.retain(|(child, _)| *child != id);
+        self.constrained_dynamic
+            .retain(|(child, _, _)| *child != id);
+
+        let mut removed = len
+            != self.static_children.len()
+                + self.dynamic_children.len()
+                + self.partial_dynamic.len()
+                + self.constrained_dynamic.len();
+
+        if self.dynamic.as_ref().is_some_and(|(child, _)| *child == id) {
+            self.dynamic = None;
+            removed = true;
+        }
+        if self
+            .catch_all
+            .as_ref()
+            .is_some_and(|(child, _)| *child == id)
+        {
+            self.catch_all = None;
+            removed = true;
+        }
+        if self.index == Some(id) {
+            self.index = None;
+            removed = true;
+        }
+
+        removed
+    }
+
+    /// The node doesn't define a route and doesn't have children
+    pub fn is_empty(&self) -> bool {
+        self.input_path.is_none() && self.index.is_none() && self.children().next().is_none()
+    }
+
+    /// Get the file that defines the node, only leafs have it.
+    fn input_file(id: NodeId, container: &OptimizedTreeContainer) -> Option<String> {
+        container
+            .nodes
+            .get(id)
+            .and_then(|node| node.input_path.as_ref().map(|p| p.display().to_string()))
+    }
+
+    /// Get the optional param of the node (as dynamic child), that already
+    /// claims the node index.
+    fn optional_dynamic(&self, container: &OptimizedTreeContainer) -> Option<NodeId> {
+        self.dynamic
+            .as_ref()
+            .filter(|(id, _)| {
+                container
+                    .nodes
+                    .get(*id)
+                    .is_some_and(|dynamic| dynamic.is_optional)
+            })
+            .map(|(id, _)| *id)
+    }
+
     /// Get the shared path between two branchs.
     /// Eg.
     /// ```
@@ -500,8 +885,14 @@ impl OptimizedTreeNode {
             )?;
         }
 
-        if let Some((dynamic_id, _)) = &self.dynamic {
-            let dynamic = container.nodes.get_reader(*dynamic_id).unwrap();
+        let dynamic_ids = self
+            .partial_dynamic
+            .iter()
+            .map(|(id, _)| id)
+            .chain(self.constrained_dynamic.iter().map(|(id, _, _)| id))
+            .chain(self.dynamic.as_ref().map(|(id, _)| id));
+        for dynamic_id in dynamic_ids {
+            let dynamic = &container.nodes[*dynamic_id];
             let dynamic = format!("{}", Fmt(move |f| dynamic.display(f, &container)));
 
             // write!(
@@ -521,11 +912,25 @@ impl OptimizedTreeNode {
             }
         }
 
+        if let Some((catch_all_id, _)) = &self.catch_all {
+            let catch_all = &container.nodes[*catch_all_id];
+            let catch_all = format!("{}", Fmt(move |f| catch_all.display(f, container)));
+
+            for line in catch_all.split("\n") {
+                write!(
+                    f,
+                    "\n{0} {1}",
+                    Color::custom([Color::Dim, Color::Bold]).color("|*"),
+                    line
+                )?;
+            }
+        }
+
         let mut static_children = self.static_children.keys().collect::<Vec<_>>();
         static_children.sort();
         for child in static_children {
             let child = self.static_children.get(child).unwrap();
-            let child = container.nodes.get_reader(*child).unwrap();
+            let child = &container.nodes[*child];
             let fmtd = format!("{}", Fmt(move |f| child.display(f, &container)));
 
             for line in fmtd.split("\n") {
@@ -550,7 +955,7 @@ impl OptimizedTreeNode {
         dynamic_children.sort();
         for child in dynamic_children {
             let child = self.dynamic_children.get(child).unwrap();
-            let child = container.nodes.get_reader(*child).unwrap();
+            let child = &container.nodes[*child];
             let fmtd = format!("{}", Fmt(move |f| child.display(f, &container)));
 
             for line in fmtd.split("\n") {
@@ -585,12 +990,11 @@ impl OptimizedTreeNode {
         );
         let index = 'index: {
             if let Some(index) = &self.index {
-                let self_hash = container.nodes.hash(self);
-                if index == &self_hash {
+                if index == &self.id {
                     break 'index "<RECURSIVE NODE>".to_owned();
                 }
 
-                let index = container.nodes.get_reader(*index);
+                let index = container.nodes.get(*index);
                 if let Some(index) = index {
                     let index = format!("{:#?}", Fmt(|f| index.debug(f, &container)));
                     index
@@ -604,7 +1008,7 @@ impl OptimizedTreeNode {
 
         let mut static_children: String = "{\n".into();
         for (child_pathname, child_id) in &self.static_children {
-            let child = container.nodes.get_reader(*child_id).unwrap();
+            let child = &container.nodes[*child_id];
             let child = format!("{:#?}", Fmt(|f| child.debug(f, &container)));
             let mut child = child
                 .split('\n')
@@ -623,7 +1027,7 @@ impl OptimizedTreeNode {
 
         let mut dynamic_children: String = "{\n".into();
         for (child_pathname, child_id) in &self.dynamic_children {
-            let child = container.nodes.get_reader(*child_id).unwrap();
+            let child = &container.nodes[*child_id];
             let child = format!("{:#?}", Fmt(|f| child.debug(f, &container)));
             let mut child = child
                 .split('\n')
@@ -641,7 +1045,7 @@ impl OptimizedTreeNode {
         dynamic_children.push('}');
 
         let dynamic_child = if let Some((dynamic, _)) = self.dynamic {
-            let dynamic = container.nodes.get_reader(dynamic).unwrap();
+            let dynamic = &container.nodes[dynamic];
             let dynamic = format!("{:#?}", Fmt(|f| dynamic.debug(f, &container)));
             let mut dynamic = dynamic
                 .split('\n')
@@ -667,7 +1071,7 @@ impl OptimizedTreeNode {
 
 impl OptimizedTreeNode {
     pub fn into_leaf(&self, container: &OptimizedTreeContainer) -> OptimizedTreeLeaf {
-        let mut single_thorns: AHashMap<String, Vec<String>> = AHashMap::new();
+        let mut single_thorns: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
         for (name, thorns) in container
             .single_thorn
@@ -679,10 +1083,7 @@ impl OptimizedTreeNode {
                 thorns
                     .into_iter()
                     .map(|f| {
-                        container
-                            .nodes
-                            .get_reader(f)
-                            .unwrap()
+                        container.nodes[f]
                             .input_path
                             .clone()
                             .unwrap()
@@ -705,10 +1106,7 @@ impl OptimizedTreeNode {
                 .map(|f| f.display().to_string())
                 .or_else(|| {
                     self.index.map(|f| {
-                        container
-                            .nodes
-                            .get_reader(f)
-                            .unwrap()
+                        container.nodes[f]
                             .input_path
                             .as_ref()
                             .expect("Index node should have an input_path")
@@ -720,7 +1118,11 @@ impl OptimizedTreeNode {
             single_thorns,
             is_root: self.is_root,
             is_static: self.is_static,
+            is_catch_all: self.is_catch_all,
+            is_optional: self.is_optional,
             varname: self.varname.clone(),
+            constraint: self.constraint.clone(),
+            pattern: self.pattern.clone(),
         }
     }
 }
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{NodeId, OptimizedTreeContainer};
+
+/// A reachable route of the tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OptimizedTreeRoute {
+    /// URL pattern with the params as they're written on the files:
+    /// `/users/$id:int/posts/$...slug`
+    pub url: String,
+    /// File that handles the route
+    pub file: String,
+    /// Map<Name, Vec<FilePath>> of the inherited thorns, in execution order
+    pub thorns: BTreeMap<String, Vec<String>>,
+}
+
+impl OptimizedTreeContainer {
+    /// All the reachable routes, in the same order the manifest tries them
+    pub fn routes(&self) -> Vec<OptimizedTreeRoute> {
+        let mut routes = Vec::new();
+        if let Some(root) = self.get_root_id() {
+            self.collect_routes(root, String::new(), &mut routes);
+        }
+        routes
+    }
+
+    fn collect_routes(&self, id: NodeId, url: String, routes: &mut Vec<OptimizedTreeRoute>) {
+        let Some(node) = self.nodes.get(id) else {
+            return;
+        };
+
+        let leaf = node.into_leaf(self);
+        if let Some(file) = leaf.index {
+            routes.push(OptimizedTreeRoute {
+                url: if url.is_empty() {
+                    "/".into()
+                } else {
+                    url.clone()
+                },
+                file,
+                thorns: leaf.single_thorns,
+            });
+        }
+
+        let child_url = |segment: &dyn std::fmt::Display| format!("{url}/{segment}");
+
+        for (path, id) in node.static_children.iter() {
+            self.collect_routes(*id, child_url(path), routes);
+        }
+        for (path, id) in node.dynamic_children.iter() {
+            self.collect_routes(*id, child_url(path), routes);
+        }
+        for (id, pattern) in node.partial_dynamic.iter() {
+            self.collect_routes(*id, child_url(pattern), routes);
+        }
+        for (id, varname, constraint) in node.constrained_dynamic.iter() {
+            let segment = format!("{varname}:{constraint}");
+            self.collect_routes(*id, child_url(&segment), routes);
+        }
+        if let Some((id, varname)) = node.dynamic.as_ref() {
+            let is_optional = self.nodes.get(*id).is_some_and(|child| child.is_optional);
+            let segment = if is_optional {
+                format!("{varname}?")
+            } else {
+                varname.clone()
+            };
+            self.collect_routes(*id, child_url(&segment), routes);
+        }
+        if let Some((id, varname)) = node.catch_all.as_ref() {
+            self.collect_routes(*id, child_url(varname), routes);
+        }
+    }
+}
@@ -1,40 +1,48 @@
-use std::sync::{Arc, RwLock};
+use proptest::{prelude::*, test_runner::TestCaseError};
 
-use crate::{utils::Fmt, CloudFileResolve};
+use crate::{
+    anyhow, utils::Fmt, CloudFile, CloudFileResolve, ParamConstraint, Result, SegmentPattern,
+};
 
-use super::{node::OptimizedTreeNodeInsertResult, OptimizedTreeContainer, OptimizedTreeNode};
+use super::{
+    NodeId, OptimizedTreeContainer, OptimizedTreeNode, OptimizedTreeNodeInsertResult, RouteConflict,
+};
 
 fn create_dummy_leaf(
     container: &mut OptimizedTreeContainer,
     path: impl Into<String>,
     file_name: impl Into<String>,
-) -> u64 {
+) -> NodeId {
     let path = path.into();
     let file_name = file_name.into();
     let file_path = format!("FILE/{file_name}");
     let output_path = format!("OUTPUT/{file_name}");
 
-    let dummy_leaf = OptimizedTreeNode::new_leaf(
-        path.clone() + ".dummy",
-        Some(file_path.clone().into()),
-        output_path.clone().into(),
-    );
-    let mut leaf = OptimizedTreeNode::new_leaf(path, Some(file_path.into()), output_path.into());
-    let dummy_leaf = container.nodes.add(dummy_leaf);
-    leaf.index = Some(dummy_leaf);
+    let leaf = OptimizedTreeNode::new_leaf(path, Some(file_path.into()), output_path.into());
+    container.add_node(leaf)
+}
 
-    container.nodes.add(leaf)
+/// Insert the leaf on `parent`, like a step of `insert_file`
+fn insert_on(
+    container: &mut OptimizedTreeContainer,
+    parent: NodeId,
+    leaf: NodeId,
+    resolved: CloudFileResolve,
+) -> OptimizedTreeNodeInsertResult {
+    container.with_node(parent, |parent, container| {
+        parent.insert(leaf, resolved, container)
+    })
 }
 
 fn insert_dummy_leaf(
     container: &mut OptimizedTreeContainer,
-    root: Arc<RwLock<OptimizedTreeNode>>,
+    root: NodeId,
     path: impl Into<String>,
     file_name: impl Into<String>,
     resolved: CloudFileResolve,
-) -> (u64, OptimizedTreeNodeInsertResult) {
+) -> (NodeId, OptimizedTreeNodeInsertResult) {
     let leaf = create_dummy_leaf(container, path, file_name);
-    let insert_result = root.write().unwrap().insert(leaf, resolved, container);
+    let insert_result = insert_on(container, root, leaf, resolved);
 
     (leaf, insert_result)
 }
@@ -46,7 +54,7 @@ fn simple_static_insert() {
 
     let (static_leaf, insert_result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "/a/b",
         "a/b.ts",
         CloudFileResolve::Pass,
@@ -54,7 +62,7 @@ fn simple_static_insert() {
 
     assert_eq!(insert_result, OptimizedTreeNodeInsertResult::None);
 
-    let root_static_children = &root.read().unwrap().static_children;
+    let root_static_children = &container.nodes[root].static_children;
     assert!(
         root_static_children.len() == 1,
         "root should insert 1 node, but it inserts {} nodes",
@@ -76,7 +84,7 @@ fn multi_static_insert() {
 
     let (leaf1, insert_result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "/a/b",
         "a/b.ts",
         CloudFileResolve::Pass,
@@ -85,7 +93,7 @@ fn multi_static_insert() {
 
     let (leaf2, insert_result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "/a/shared-prefix",
         "a/shared-prefix.ts",
         CloudFileResolve::Pass,
@@ -94,14 +102,14 @@ fn multi_static_insert() {
 
     let (leaf3, insert_result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "/other/path",
         "other/path.ts",
         CloudFileResolve::Pass,
     );
     assert_eq!(insert_result, OptimizedTreeNodeInsertResult::None);
 
-    let root_static_children = &root.read().unwrap().static_children;
+    let root_static_children = &container.nodes[root].static_children;
     assert!(
         root_static_children.len() == 3,
         "root should insert 1 node, but it inserts {} nodes",
@@ -137,15 +145,15 @@ fn simple_dynamic_insert() {
 
     let (_, insert_result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "/a/$b",
         "a/b.ts",
-        CloudFileResolve::Dynamic("a".into(), "b".into(), "".into()),
+        CloudFileResolve::Dynamic("a".into(), "b".into(), "".into(), None),
     );
 
     assert_eq!(insert_result, OptimizedTreeNodeInsertResult::None);
 
-    let root_dynamic_children = &root.read().unwrap().dynamic_children;
+    let root_dynamic_children = &container.nodes[root].dynamic_children;
     assert!(
         root_dynamic_children.len() == 1,
         "root should insert 1 node, but it inserts {} nodes",
@@ -166,64 +174,1136 @@ fn multi_dynamic_insert() {
 
     let (leaf, result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "a/b/$c/d",
         "a/b/$c/d.ts",
-        CloudFileResolve::Dynamic("a/b".into(), "$c".into(), "d".into()),
+        CloudFileResolve::Dynamic("a/b".into(), "$c".into(), "d".into(), None),
     );
     println!("{result:?}");
 
-    let OptimizedTreeNodeInsertResult::Resolve(new_parent, new_relative) = result else {
+    let OptimizedTreeNodeInsertResult::Resolve {
+        new_parent,
+        new_suffix: new_relative,
+    } = result
+    else {
         panic!("Insert result should be OptimizedTreeNodeInsertResult::Resolve");
     };
 
-    container.nodes.get_writer(leaf).unwrap().relative_pathname = new_relative;
+    container.nodes[leaf].relative_pathname = new_relative;
 
-    let b = container.nodes.get(new_parent).unwrap();
-    let a = b
-        .write()
-        .unwrap()
-        .insert(leaf, CloudFileResolve::Pass, &mut container);
+    let a = insert_on(&mut container, new_parent, leaf, CloudFileResolve::Pass);
 
     println!("{a:?}");
-    println!("{}", Fmt(|f| root.read().unwrap().display(f, &container)));
+    println!("{}", Fmt(|f| container.nodes[root].display(f, &container)));
 
     let (leaf, result) = insert_dummy_leaf(
         &mut container,
-        root.clone(),
+        root,
         "a/$b/c",
         "a/$b/c.ts",
-        CloudFileResolve::Dynamic("a".into(), "$b".into(), "c".into()),
+        CloudFileResolve::Dynamic("a".into(), "$b".into(), "c".into(), None),
     );
     println!("{result:?}");
 
-    let OptimizedTreeNodeInsertResult::Resolve(new_parent, new_relative) = result else {
+    let OptimizedTreeNodeInsertResult::MergeNodes {
+        new_node: new_parent,
+        new_suffix: new_relative,
+    } = result
+    else {
         panic!("Insert result should be OptimizedTreeNodeInsertResult::MergeNodes");
     };
 
-    container.nodes.get_writer(leaf).unwrap().relative_pathname = new_relative;
+    container.nodes[leaf].relative_pathname = new_relative;
 
-    let b = container.nodes.get(new_parent).unwrap();
-    let result = b.write().unwrap().insert(
-        leaf,
-        CloudFileResolve::Dynamic("".into(), "$b".into(), "c".into()),
+    let result = insert_on(
         &mut container,
+        new_parent,
+        leaf,
+        CloudFileResolve::Dynamic("".into(), "$b".into(), "c".into(), None),
     );
     println!("{result:?}");
 
-    let OptimizedTreeNodeInsertResult::Resolve(new_parent, new_relative) = result else {
+    let OptimizedTreeNodeInsertResult::Resolve {
+        new_parent,
+        new_suffix: new_relative,
+    } = result
+    else {
         panic!("Insert result should be OptimizedTreeNodeInsertResult::Resolve");
     };
 
-    container.nodes.get_writer(leaf).unwrap().relative_pathname = new_relative;
+    container.nodes[leaf].relative_pathname = new_relative;
 
-    let b = container.nodes.get(new_parent).unwrap();
-    let a = b
-        .write()
-        .unwrap()
-        .insert(leaf, CloudFileResolve::Pass, &mut container);
+    let a = insert_on(&mut container, new_parent, leaf, CloudFileResolve::Pass);
 
     println!("{a:?}");
 
-    println!("{}", Fmt(|f| root.read().unwrap().display(f, &container)));
+    println!("{}", Fmt(|f| container.nodes[root].display(f, &container)));
+}
+
+#[test]
+fn catch_all_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let (dynamic_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "files/$id",
+        "files/$id.ts",
+        CloudFileResolve::Dynamic("files".into(), "$id".into(), "".into(), None),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let (catch_all_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "files/$...path",
+        "files/$...path.ts",
+        CloudFileResolve::CatchAll("files".into(), "$...path".into()),
+    );
+
+    let files_node = *container.nodes[root].dynamic_children.get("files").unwrap();
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Resolve {
+            new_parent: files_node,
+            new_suffix: "$...path".into()
+        }
+    );
+
+    let result = insert_on(
+        &mut container,
+        files_node,
+        catch_all_leaf,
+        CloudFileResolve::CatchAll("".into(), "$...path".into()),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let files_node = &container.nodes[files_node];
+    assert_eq!(files_node.dynamic, Some((dynamic_leaf, "$id".into())));
+    assert_eq!(
+        files_node.catch_all,
+        Some((catch_all_leaf, "$...path".into()))
+    );
+
+    let catch_all_leaf = &container.nodes[catch_all_leaf];
+    assert!(catch_all_leaf.is_catch_all);
+    assert_eq!(catch_all_leaf.varname, Some("$...path".into()));
+}
+
+#[test]
+fn optional_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let (optional_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "users/$id?",
+        "users/$id?.ts",
+        CloudFileResolve::Optional("users".into(), "$id".into()),
+    );
+
+    let OptimizedTreeNodeInsertResult::Resolve {
+        new_parent: users_node,
+        new_suffix,
+    } = result
+    else {
+        panic!("Insert result should be OptimizedTreeNodeInsertResult::Resolve");
+    };
+    assert_eq!(new_suffix, "$id?");
+
+    let result = insert_on(
+        &mut container,
+        users_node,
+        optional_leaf,
+        CloudFileResolve::Optional("".into(), "$id".into()),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    {
+        let users_node = &container.nodes[users_node];
+        assert_eq!(users_node.dynamic, Some((optional_leaf, "$id".into())));
+        assert!(container.nodes.get(optional_leaf).unwrap().is_optional);
+    }
+
+    // "users.ts" claims the same route as "users/$id?.ts"
+    let (_, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "users",
+        "users.ts",
+        CloudFileResolve::Pass,
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: "/users".into(),
+            file: "FILE/users.ts".into(),
+            other_file: "FILE/users/$id?.ts".into(),
+        })
+    );
+    assert!(
+        !container.nodes[root].static_children.contains_key("users"),
+        "conflicting static route shouldn't be inserted"
+    );
+
+    // The optional param can't have children, in any order
+    for files in [
+        ["users/$id?.ts", "users/$id/posts.ts"],
+        ["users/$id/posts.ts", "users/$id?.ts"],
+    ] {
+        let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+        container.create_root();
+        insert_files(&mut container, &files);
+
+        let conflicts: Vec<_> = container.get_conflicts().collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].url, "/users/$id");
+        assert_eq!(conflicts[0].file, format!("FILE/{}", files[1]));
+    }
+}
+
+#[test]
+fn constrained_dynamic_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let (int_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "users/$id:int",
+        "users/$id:int.ts",
+        CloudFileResolve::Dynamic(
+            "users".into(),
+            "$id".into(),
+            "".into(),
+            Some(ParamConstraint::Int),
+        ),
+    );
+
+    let OptimizedTreeNodeInsertResult::Resolve {
+        new_parent: users_node,
+        new_suffix,
+    } = result
+    else {
+        panic!("Insert result should be OptimizedTreeNodeInsertResult::Resolve");
+    };
+    assert_eq!(new_suffix, "$id:int");
+
+    let result = insert_on(
+        &mut container,
+        users_node,
+        int_leaf,
+        CloudFileResolve::Dynamic(
+            "".into(),
+            "$id".into(),
+            "".into(),
+            Some(ParamConstraint::Int),
+        ),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let (name_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "users/$name",
+        "users/$name.ts",
+        CloudFileResolve::Dynamic("users".into(), "$name".into(), "".into(), None),
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Resolve {
+            new_parent: users_node,
+            new_suffix: "$name".into()
+        }
+    );
+
+    let result = insert_on(
+        &mut container,
+        users_node,
+        name_leaf,
+        CloudFileResolve::Dynamic("".into(), "$name".into(), "".into(), None),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let users_node = &container.nodes[users_node];
+    assert_eq!(
+        users_node.constrained_dynamic,
+        vec![(int_leaf, "$id".into(), ParamConstraint::Int)]
+    );
+    assert_eq!(users_node.dynamic, Some((name_leaf, "$name".into())));
+
+    let int_leaf = &container.nodes[int_leaf];
+    assert_eq!(int_leaf.constraint, Some(ParamConstraint::Int));
+}
+
+#[test]
+fn partial_dynamic_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let report = SegmentPattern {
+        before: "report-".into(),
+        varname: "$year".into(),
+        after: ".csv".into(),
+        constraint: None,
+    };
+    let summary = SegmentPattern {
+        before: "summary-".into(),
+        ..report.clone()
+    };
+
+    let (report_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "report-$year.csv",
+        "report-$year.csv.ts",
+        CloudFileResolve::Partial("".into(), report.clone(), "".into()),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let (summary_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "summary-$year.csv",
+        "summary-$year.csv.ts",
+        CloudFileResolve::Partial("".into(), summary.clone(), "".into()),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    // Same static text, so it claims the same segments as "report-$year.csv"
+    let (_, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "report-$id.csv",
+        "report-$id.csv.ts",
+        CloudFileResolve::Partial(
+            "".into(),
+            SegmentPattern {
+                varname: "$id".into(),
+                ..report.clone()
+            },
+            "".into(),
+        ),
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: "/report-$id.csv".into(),
+            file: "FILE/report-$id.csv.ts".into(),
+            other_file: "FILE/report-$year.csv.ts".into(),
+        })
+    );
+
+    assert_eq!(
+        container.nodes[root].partial_dynamic,
+        vec![(report_leaf, report.clone()), (summary_leaf, summary)]
+    );
+
+    let report_leaf = &container.nodes[report_leaf];
+    assert_eq!(report_leaf.varname, Some("$year".into()));
+    assert_eq!(report_leaf.pattern, Some(report));
+}
+
+#[test]
+fn group_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+    let root_id = container.get_root_id().unwrap();
+
+    let (middleware, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "(admin)/_middleware",
+        "(admin)/_middleware.ts",
+        CloudFileResolve::SingleThorn("middleware"),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    let mut insert_grouped =
+        |path: &str, url_path: &str| -> (NodeId, OptimizedTreeNodeInsertResult) {
+            let (leaf, result) = insert_dummy_leaf(
+                &mut container,
+                root,
+                path,
+                format!("{path}.ts"),
+                CloudFileResolve::Group(url_path.into()),
+            );
+            assert_eq!(
+                result,
+                OptimizedTreeNodeInsertResult::Resolve {
+                    new_parent: root_id,
+                    new_suffix: url_path.into()
+                }
+            );
+
+            let result = insert_on(&mut container, root, leaf, CloudFileResolve::Pass);
+
+            (leaf, result)
+        };
+
+    let (users, result) = insert_grouped("(admin)/users", "users");
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+    let (login, result) = insert_grouped("(public)/login", "login");
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    // "(public)/users" defines the same URL as "(admin)/users"
+    let (_, result) = insert_grouped("(public)/users", "users");
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: "/users".into(),
+            file: "FILE/(public)/users.ts".into(),
+            other_file: "FILE/(admin)/users.ts".into(),
+        })
+    );
+
+    {
+        let root_static_children = &container.nodes[root].static_children;
+        assert_eq!(root_static_children.len(), 2);
+        assert_eq!(root_static_children.get("users"), Some(&users));
+        assert_eq!(root_static_children.get("login"), Some(&login));
+    }
+
+    // The middleware is only scoped to its group
+    let middleware_file = container
+        .nodes
+        .get(middleware)
+        .unwrap()
+        .input_path
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap();
+
+    let users = container.nodes.get(users).unwrap().into_leaf(&container);
+    assert_eq!(
+        users.single_thorns.get("middleware"),
+        Some(&vec![middleware_file])
+    );
+
+    let login = container.nodes.get(login).unwrap().into_leaf(&container);
+    assert!(!login.single_thorns.contains_key("middleware"));
+}
+
+#[test]
+fn dynamic_conflict_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let (a_leaf, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "$a",
+        "$a.ts",
+        CloudFileResolve::Dynamic("".into(), "$a".into(), "".into(), None),
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+
+    // Sibling params are ambiguous, "$b.ts" would silently replace "$a.ts"
+    let (_, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "$b",
+        "$b.ts",
+        CloudFileResolve::Dynamic("".into(), "$b".into(), "".into(), None),
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: "/$b".into(),
+            file: "FILE/$b.ts".into(),
+            other_file: "FILE/$a.ts".into(),
+        })
+    );
+
+    // The same param can have children
+    let (_, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "$a/edit",
+        "$a/edit.ts",
+        CloudFileResolve::Dynamic("".into(), "$a".into(), "edit".into(), None),
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Resolve {
+            new_parent: a_leaf,
+            new_suffix: "edit".into()
+        }
+    );
+    assert_eq!(container.nodes[root].dynamic, Some((a_leaf, "$a".into())));
+}
+
+#[test]
+fn index_conflict_insert() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    let root = container.create_root();
+
+    let (index, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "_index",
+        "_index.ts",
+        CloudFileResolve::Index,
+    );
+    assert_eq!(result, OptimizedTreeNodeInsertResult::None);
+    assert_eq!(container.nodes[root].index, Some(index));
+
+    // "(admin)/_index.ts" is the index of the root too
+    let (_, result) = insert_dummy_leaf(
+        &mut container,
+        root,
+        "(admin)",
+        "(admin)/_index.ts",
+        CloudFileResolve::Group("".into()),
+    );
+    assert_eq!(
+        result,
+        OptimizedTreeNodeInsertResult::Conflict(RouteConflict {
+            url: "/".into(),
+            file: "FILE/(admin)/_index.ts".into(),
+            other_file: "FILE/_index.ts".into(),
+        })
+    );
+}
+
+#[test]
+fn deterministic_tree() {
+    let build = || {
+        let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+        let root = container.create_root();
+
+        for path in ["users", "posts", "about", "admin/settings"] {
+            insert_dummy_leaf(
+                &mut container,
+                root,
+                path,
+                format!("{path}.ts"),
+                CloudFileResolve::Pass,
+            );
+        }
+        insert_dummy_leaf(
+            &mut container,
+            root,
+            "files/$id",
+            "files/$id.ts",
+            CloudFileResolve::Dynamic("files".into(), "$id".into(), "".into(), None),
+        );
+
+        let display = format!("{}", Fmt(|f| container.nodes[root].display(f, &container)));
+        let ids: Vec<NodeId> = container.iter().map(|node| node.id).collect();
+        (display, ids)
+    };
+
+    let (display, ids) = build();
+    assert_eq!(ids, (0..ids.len() as u32).map(NodeId).collect::<Vec<_>>());
+    assert_eq!(build(), (display, ids));
+}
+
+/// The rules of the http-router resolver (`clouds/http-router/src/file_resolve.rs`)
+fn test_resolve(file: CloudFile) -> Result<CloudFileResolve> {
+    let path = file.relative_path.replace(".ts", "");
+    let segments: Vec<&str> = path.split('/').collect();
+
+    let filename = *segments.last().unwrap();
+    if filename.starts_with('_') {
+        return Ok(match filename {
+            "_index" => CloudFileResolve::Index,
+            "_middleware" => CloudFileResolve::SingleThorn("middleware"),
+            "_fallback" => CloudFileResolve::SingleThorn("fallback"),
+            _ => CloudFileResolve::Ignore,
+        });
+    }
+
+    let is_group = |part: &&str| part.starts_with('(') && part.ends_with(')');
+    if segments.iter().any(is_group) {
+        let url_path: Vec<&str> = segments.iter().copied().filter(|s| !is_group(s)).collect();
+        return Ok(CloudFileResolve::Group(url_path.join("/")));
+    }
+
+    let Some(dynamic) = segments.iter().position(|s| s.contains('$')) else {
+        return Ok(CloudFileResolve::Pass);
+    };
+    let prefix = segments[..dynamic].join("/");
+    let suffix = segments[dynamic + 1..].join("/");
+    let varname = segments[dynamic].to_string();
+
+    if !varname.starts_with('$') {
+        let pattern = SegmentPattern::parse(&varname)?;
+        return Ok(CloudFileResolve::Partial(prefix, pattern, suffix));
+    }
+    if let Some(name) = varname.strip_prefix("$...") {
+        if name.is_empty() {
+            return Err(anyhow!("Catch-all segment {varname:?} doesn't have a name"));
+        }
+        if !suffix.is_empty() {
+            return Err(anyhow!(
+                "Catch-all segment {varname:?} should be the last part"
+            ));
+        }
+        return Ok(CloudFileResolve::CatchAll(prefix, varname));
+    }
+    if let Some(varname) = varname.strip_suffix('?') {
+        if !suffix.is_empty() {
+            return Err(anyhow!(
+                "Optional segment \"{varname}?\" should be the last part"
+            ));
+        }
+        return Ok(CloudFileResolve::Optional(prefix, varname.to_string()));
+    }
+
+    Ok(match varname.split_once(':') {
+        Some((varname, constraint)) => CloudFileResolve::Dynamic(
+            prefix,
+            varname.to_string(),
+            suffix,
+            ParamConstraint::parse(constraint)?,
+        ),
+        None => CloudFileResolve::Dynamic(prefix, varname, suffix, None),
+    })
+}
+
+/// Print what the manifest gets from the tree, in the same order, without ids
+fn snapshot(container: &OptimizedTreeContainer) -> String {
+    fn walk(id: NodeId, container: &OptimizedTreeContainer, out: &mut String) {
+        let node = &container.nodes[id];
+        let leaf = node.into_leaf(container);
+        *out += &format!(
+            "{:?} {:?} {:?} {:?} {} {} {} {:?} {:?} {:?}\n",
+            leaf.pathname,
+            leaf.relative_pathname,
+            leaf.index,
+            leaf.single_thorns,
+            leaf.is_static,
+            leaf.is_catch_all,
+            leaf.is_optional,
+            leaf.varname,
+            leaf.constraint,
+            leaf.pattern,
+        );
+
+        for id in node.children() {
+            walk(*id, container, out);
+        }
+        *out += "end\n";
+    }
+
+    let mut out = String::new();
+    walk(container.get_root_id().unwrap(), container, &mut out);
+    out
+}
+
+fn insert_files(container: &mut OptimizedTreeContainer, files: &[&str]) {
+    for file in files.iter().copied() {
+        let cloud_file = CloudFile::new(format!("FILE/{file}"), file, format!("OUTPUT/{file}"));
+        container
+            .insert_file(cloud_file, &mut test_resolve)
+            .unwrap();
+    }
+}
+
+#[test]
+fn incremental_update() {
+    let files = [
+        "_index.ts",
+        "_middleware.ts",
+        "about.ts",
+        "a/b/$c.ts",
+        "users/_index.ts",
+        "users/_middleware.ts",
+        "users/$id.ts",
+        "users/$id/edit.ts",
+        "blog/$...slug.ts",
+        "posts/$id:int.ts",
+        "posts/$slug.ts",
+        "reports/report-$year.csv.ts",
+        "docs/$page?.ts",
+        "(admin)/_middleware.ts",
+        "(admin)/dashboard.ts",
+    ];
+
+    let from_scratch = || {
+        let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+        container.create_root();
+        insert_files(&mut container, &files);
+        snapshot(&container)
+    };
+    let expected = from_scratch();
+
+    // Other insertion order
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &files.iter().copied().rev().collect::<Vec<_>>(),
+    );
+    assert_eq!(snapshot(&container), expected);
+
+    // Added and removed files: merged prefixes, middle nodes and thorns
+    let extra = [
+        "a/$d.ts",
+        "users/$id/delete.ts",
+        "users/$id/_middleware.ts",
+        "contact.ts",
+        "posts/$id:int/edit.ts",
+        "posts/$uuid:uuid.ts",
+        "reports/summary-$year.pdf.ts",
+        "reports/report-$year.csv/raw.ts",
+        "(admin)/settings.ts",
+        "(shop)/cart.ts",
+        "(shop)/_middleware.ts",
+    ];
+    insert_files(&mut container, &extra);
+    assert_ne!(snapshot(&container), expected);
+    for file in extra.iter() {
+        container.remove_file(file, &mut test_resolve).unwrap();
+    }
+    assert_eq!(snapshot(&container), expected);
+
+    // A leaf with children becomes a middle node
+    container
+        .remove_file("users/$id.ts", &mut test_resolve)
+        .unwrap();
+    insert_files(&mut container, &["users/$id.ts"]);
+    assert_eq!(snapshot(&container), expected);
+
+    // Rename
+    let about = CloudFile::new("FILE/info.ts", "info.ts", "OUTPUT/info.ts");
+    container
+        .rename_file("about.ts", about, &mut test_resolve)
+        .unwrap();
+    assert_ne!(snapshot(&container), expected);
+    let about = CloudFile::new("FILE/about.ts", "about.ts", "OUTPUT/about.ts");
+    container
+        .rename_file("info.ts", about, &mut test_resolve)
+        .unwrap();
+    assert_eq!(snapshot(&container), expected);
+}
+
+#[test]
+fn incremental_conflict_update() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(&mut container, &["users/$id.ts", "users/$name.ts"]);
+    assert_eq!(container.get_conflicts().count(), 1);
+
+    // The conflicting file takes the route when the other one is removed
+    container
+        .remove_file("users/$id.ts", &mut test_resolve)
+        .unwrap();
+    assert_eq!(container.get_conflicts().count(), 0);
+
+    let mut expected = OptimizedTreeContainer::new("OUTPUT_DIR");
+    expected.create_root();
+    insert_files(&mut expected, &["users/$name.ts"]);
+    assert_eq!(snapshot(&container), snapshot(&expected));
+}
+
+#[test]
+fn incremental_conflict_error() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &["users/$id.ts", "users/$name.ts", "users/$slug.ts"],
+    );
+    assert_eq!(container.get_conflicts().count(), 2);
+
+    // "users/$name.ts" fails, the other conflicting file is still inserted
+    let mut failing_resolve = |file: CloudFile| {
+        if file.relative_path == "users/$name.ts" {
+            return Err(anyhow!("Can't resolve {}", file.relative_path));
+        }
+        test_resolve(file)
+    };
+    assert!(container
+        .remove_file("users/$id.ts", &mut failing_resolve)
+        .is_err());
+    let conflicts: Vec<_> = container
+        .get_conflicts()
+        .map(|conflict| conflict.file.as_str())
+        .collect();
+    assert_eq!(conflicts, vec!["FILE/users/$name.ts"]);
+
+    let mut expected = OptimizedTreeContainer::new("OUTPUT_DIR");
+    expected.create_root();
+    insert_files(&mut expected, &["users/$slug.ts"]);
+    assert_eq!(snapshot(&container), snapshot(&expected));
+
+    // It's retried on the next removal
+    container
+        .remove_file("users/$slug.ts", &mut test_resolve)
+        .unwrap();
+    assert_eq!(container.get_conflicts().count(), 0);
+
+    let mut expected = OptimizedTreeContainer::new("OUTPUT_DIR");
+    expected.create_root();
+    insert_files(&mut expected, &["users/$name.ts"]);
+    assert_eq!(snapshot(&container), snapshot(&expected));
+}
+
+#[test]
+fn resolve_error() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(&mut container, &["users/$id.ts"]);
+    let expected = snapshot(&container);
+    let nodes = container.nodes.len();
+
+    // The first resolve fails, and the re-resolve of "$...rest/edit.ts"
+    for file in ["files/$...rest/edit.ts", "users/$id/$...rest/edit.ts"] {
+        let cloud_file = CloudFile::new(format!("FILE/{file}"), file, format!("OUTPUT/{file}"));
+        assert!(container
+            .insert_file(cloud_file, &mut test_resolve)
+            .is_err());
+    }
+
+    assert_eq!(snapshot(&container), expected);
+    assert_eq!(container.nodes.len(), nodes);
+    assert_eq!(container.get_conflicts().count(), 0);
+}
+
+#[test]
+fn serialized_tree() {
+    let files = [
+        "_index.ts",
+        "users/_middleware.ts",
+        "users/$id.ts",
+        "users/$id/edit.ts",
+        "a/b/$c.ts",
+        "a/$d.ts",
+    ];
+
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(&mut container, &files);
+
+    let json = serde_json::to_string(&container).unwrap();
+    let mut cached: OptimizedTreeContainer = serde_json::from_str(&json).unwrap();
+    assert_eq!(snapshot(&cached), snapshot(&container));
+
+    // The cached tree keeps working with file events
+    cached.remove_file("a/$d.ts", &mut test_resolve).unwrap();
+    insert_files(&mut cached, &["about.ts"]);
+
+    let mut expected = OptimizedTreeContainer::new("OUTPUT_DIR");
+    expected.create_root();
+    insert_files(&mut expected, &files[..5]);
+    insert_files(&mut expected, &["about.ts"]);
+    assert_eq!(snapshot(&cached), snapshot(&expected));
+}
+
+#[test]
+fn routes_table() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &[
+            "_index.ts",
+            "_middleware.ts",
+            "about.ts",
+            "users/_middleware.ts",
+            "users/$id.ts",
+            "users/$id/edit.ts",
+            "blog/$...slug.ts",
+        ],
+    );
+
+    let routes: Vec<(String, String, Vec<String>)> = container
+        .routes()
+        .into_iter()
+        .map(|route| {
+            let middlewares = route.thorns.get("middleware").cloned().unwrap_or_default();
+            (route.url, route.file, middlewares)
+        })
+        .collect();
+
+    let root_middleware = "FILE/_middleware.ts".to_string();
+    let users_middleware = "FILE/users/_middleware.ts".to_string();
+    assert_eq!(
+        routes,
+        vec![
+            (
+                "/".into(),
+                "FILE/_index.ts".into(),
+                vec![root_middleware.clone()]
+            ),
+            (
+                "/about".into(),
+                "FILE/about.ts".into(),
+                vec![root_middleware.clone()]
+            ),
+            (
+                "/blog/$...slug".into(),
+                "FILE/blog/$...slug.ts".into(),
+                vec![root_middleware.clone()]
+            ),
+            (
+                "/users/$id".into(),
+                "FILE/users/$id.ts".into(),
+                vec![users_middleware.clone(), root_middleware.clone()]
+            ),
+            (
+                "/users/$id/edit".into(),
+                "FILE/users/$id/edit.ts".into(),
+                vec![users_middleware, root_middleware]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn graph_export() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &[
+            "_index.ts",
+            "about.ts",
+            "users/_middleware.ts",
+            "users/$id.ts",
+            "a/b/$c.ts",
+            "a/$d.ts",
+        ],
+    );
+
+    let mermaid = container.to_mermaid();
+    assert!(mermaid.starts_with("flowchart TD\n"));
+    assert!(mermaid.contains("[\"about\"]"));
+    assert!(mermaid.contains("[\\\"a\"\\]"));
+    assert!(mermaid.contains("{{\"$id\"}}"));
+    assert!(mermaid.contains(">\"middleware: users/_middleware.ts\"]"));
+    assert!(mermaid.contains("([\"users/$id.ts\"])"));
+
+    let dot = container.to_dot();
+    assert!(dot.starts_with("digraph OptimizedTree {\n"));
+    assert!(dot.contains("[label=\"a\", shape=parallelogram, style=bold];"));
+    assert!(dot.contains("[style=dashed];"));
+    assert_eq!(
+        dot.matches(" -> ").count(),
+        mermaid.matches("-->").count() + mermaid.matches("-.->").count()
+    );
+}
+
+#[test]
+fn match_path() {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &[
+            "_index.ts",
+            "_middleware.ts",
+            "about.ts",
+            "users/_index.ts",
+            "users/_middleware.ts",
+            "users/$id:int.ts",
+            "users/$id:int/edit.ts",
+            "users/$name.ts",
+            "blog/$...slug.ts",
+            "docs/$page?.ts",
+            "a/b/$c.ts",
+            "a/$d.ts",
+        ],
+    );
+
+    // (url, expected (file, params) of the match)
+    type Case = (
+        &'static str,
+        Option<(&'static str, &'static [(&'static str, &'static str)])>,
+    );
+
+    let expected: &[Case] = &[
+        ("/", Some(("_index.ts", &[]))),
+        ("/about", Some(("about.ts", &[]))),
+        ("/about/", Some(("about.ts", &[]))),
+        ("/users", Some(("users/_index.ts", &[]))),
+        ("/users/42", Some(("users/$id:int.ts", &[("id", "42")]))),
+        (
+            "/users/42/edit",
+            Some(("users/$id:int/edit.ts", &[("id", "42")])),
+        ),
+        ("/users/ana", Some(("users/$name.ts", &[("name", "ana")]))),
+        ("/users/ana/edit", None),
+        (
+            "/blog/2024/post",
+            Some(("blog/$...slug.ts", &[("slug", "2024/post")])),
+        ),
+        ("/blog", None),
+        ("/docs", Some(("docs/$page?.ts", &[]))),
+        (
+            "/docs/intro",
+            Some(("docs/$page?.ts", &[("page", "intro")])),
+        ),
+        ("/docs/intro/more", None),
+        ("/a/b/1", Some(("a/b/$c.ts", &[("c", "1")]))),
+        ("/a/2", Some(("a/$d.ts", &[("d", "2")]))),
+        ("/missing", None),
+    ];
+
+    for (path, route) in expected {
+        let found = container.match_path(path);
+        let found = found
+            .as_ref()
+            .map(|found| (found.file.as_str(), found.params.clone()));
+        let route = route.map(|(file, params)| {
+            let params = params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            (file, params)
+        });
+
+        assert_eq!(
+            found.map(|(file, params)| (file.strip_prefix("FILE/").unwrap(), params)),
+            route,
+            "{path}"
+        );
+    }
+
+    let users = container.match_path("/users/42").unwrap();
+    assert_eq!(
+        users.thorns.get("middleware"),
+        Some(&vec![
+            "FILE/users/_middleware.ts".to_string(),
+            "FILE/_middleware.ts".to_string()
+        ])
+    );
+}
+
+/// Random route sets for the property tests. The params are named by depth
+/// and only one file per route is kept, so the sets never conflict.
+fn route_set() -> impl Strategy<Value = Vec<String>> {
+    let dir = prop_oneof![
+        Just(Some("a")),
+        Just(Some("b")),
+        Just(Some("users")),
+        Just(None),
+    ];
+    let file = prop_oneof![
+        Just(Some("a")),
+        Just(Some("about")),
+        Just(Some("_index")),
+        Just(Some("_middleware")),
+        Just(None),
+    ];
+
+    let route = (proptest::collection::vec(dir, 0..4), file).prop_map(|(dirs, file)| {
+        let mut segments: Vec<String> = dirs
+            .iter()
+            .enumerate()
+            .map(|(depth, dir)| match dir {
+                Some(dir) => dir.to_string(),
+                None => format!("$p{depth}"),
+            })
+            .collect();
+        segments.push(match file {
+            Some(file) => file.to_string(),
+            None => format!("$p{}", dirs.len()),
+        });
+        segments.join("/") + ".ts"
+    });
+
+    proptest::collection::vec(route, 1..16).prop_map(|files| {
+        // "a.ts" and "a/_index.ts" are the same route
+        let mut routes = std::collections::BTreeMap::new();
+        for file in files {
+            let route = file.trim_end_matches(".ts");
+            let route = route.strip_suffix("/_index").unwrap_or(route);
+            let route = if route == "_index" { "" } else { route };
+            routes.entry(route.to_string()).or_insert(file);
+        }
+        routes.into_values().collect()
+    })
+}
+
+/// Check the tree built from `files`, returns the failed invariant
+fn check_tree(container: &OptimizedTreeContainer, files: &[String]) -> Result<(), String> {
+    if let Some(conflict) = container.get_conflicts().next() {
+        return Err(format!("Unexpected conflict: {conflict:?}"));
+    }
+
+    // Thorns aren't linked on the tree, they're found by their path
+    let mut pending = vec![container.get_root_id().unwrap()];
+    for file in files {
+        let id = *container
+            .files
+            .get(file)
+            .ok_or_else(|| format!("{file} isn't on the tree"))?;
+
+        let dir = match file.rsplit_once('/') {
+            Some((dir, "_middleware.ts")) => dir,
+            None if file == "_middleware.ts" => "",
+            _ => continue,
+        };
+        let thorn = container
+            .single_thorn
+            .get(&"middleware".to_string(), &dir.to_string());
+        if thorn != Some(id) {
+            return Err(format!("{file} ({id}) is the thorn {thorn:?} of {dir:?}"));
+        }
+        pending.push(id);
+    }
+
+    let mut reachable = std::collections::BTreeSet::new();
+    while let Some(id) = pending.pop() {
+        if !reachable.insert(id) {
+            return Err(format!("Node {id} has two parents"));
+        }
+        let node = container
+            .nodes
+            .get(id)
+            .ok_or_else(|| format!("Node {id} is linked but not stored"))?;
+        pending.extend(node.children().copied());
+        pending.extend(node.index);
+    }
+
+    for file in files {
+        let id = container.files[file];
+        if !reachable.contains(&id) {
+            return Err(format!("{file} ({id}) isn't reachable"));
+        }
+    }
+
+    for id in container.nodes.ids() {
+        if !reachable.contains(&id) {
+            return Err(format!("Node {id} is orphaned"));
+        }
+    }
+
+    Ok(())
+}
+
+fn build_tree(files: &[String]) -> OptimizedTreeContainer {
+    let mut container = OptimizedTreeContainer::new("OUTPUT_DIR");
+    container.create_root();
+    insert_files(
+        &mut container,
+        &files.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+    container
+}
+
+proptest! {
+    #[test]
+    fn insert_invariants(files in route_set()) {
+        let container = build_tree(&files);
+        if let Err(err) = check_tree(&container, &files) {
+            return Err(TestCaseError::fail(format!("{err}\n{}", snapshot(&container))));
+        }
+    }
+
+    #[test]
+    fn insert_order_independent(
+        (files, shuffled) in route_set().prop_flat_map(|files| {
+            (Just(files.clone()), Just(files).prop_shuffle())
+        })
+    ) {
+        let expected = build_tree(&files);
+        let container = build_tree(&shuffled);
+        prop_assert_eq!(snapshot(&container), snapshot(&expected));
+    }
 }
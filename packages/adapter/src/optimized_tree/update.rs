@@ -0,0 +1,291 @@
+use std::path::PathBuf;
+
+use crate::{anyhow, log::PathDebugDisplay, log_trace, CloudFile, CloudFileResolve, Result};
+
+use super::{NodeId, OptimizedTreeContainer, OptimizedTreeNode, OptimizedTreeNodeInsertResult};
+
+struct InsertContext<'a, R> {
+    resolve: &'a mut R,
+    file: &'a CloudFile,
+    extension: String,
+}
+
+impl OptimizedTreeContainer {
+    /// Insert a file of the cloud folder. `resolve` is the cloud resolver,
+    /// it's called for the file and for each suffix that needs a re-resolve.
+    /// When it fails, the file isn't inserted and the error is returned.
+    ///
+    /// When the file defines a route that's already defined, the conflict is
+    /// saved (see [`OptimizedTreeContainer::get_conflicts`]) and the file is
+    /// inserted again when the other file is removed.
+    pub fn insert_file<R>(&mut self, file: CloudFile, resolve: &mut R) -> Result<()>
+    where
+        R: FnMut(CloudFile) -> Result<CloudFileResolve>,
+    {
+        let root = self
+            .get_root_id()
+            .ok_or_else(|| anyhow!("The tree doesn't have root"))?;
+
+        log_trace!(["OTreeUpdate"] "Resolving file: {}", file.file_path.display_debug());
+        let resolved_file = resolve(file.clone())?;
+        log_trace!(["OTreeUpdate"] "Resolved as {resolved_file:?}");
+
+        if resolved_file == CloudFileResolve::Ignore {
+            return Ok(());
+        }
+
+        let relative = PathBuf::from(&file.relative_path);
+        let extension = relative
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let path = relative.with_extension("").display().to_string();
+
+        let leaf = OptimizedTreeNode::new_leaf(
+            path,
+            Some(file.file_path.clone()),
+            file.output_path.clone(),
+        );
+        let leaf = self.add_node(leaf);
+
+        let next_iter = self.with_node(root, |root, tree| root.insert(leaf, resolved_file, tree));
+
+        let inserted = self.perform_insert_action(
+            leaf,
+            next_iter,
+            &mut InsertContext {
+                resolve,
+                file: &file,
+                extension,
+            },
+        )?;
+
+        if let Some(node) = inserted {
+            self.files.insert(file.relative_path, node);
+        }
+
+        Ok(())
+    }
+
+    /// Remove a file of the cloud folder, and all the nodes that only
+    /// existed for it. Merged nodes are split again.
+    ///
+    /// The conflicting files are inserted again. When some of them fail, the
+    /// other ones are still inserted and the first error is returned.
+    pub fn remove_file<R>(&mut self, relative_path: impl AsRef<str>, resolve: &mut R) -> Result<()>
+    where
+        R: FnMut(CloudFile) -> Result<CloudFileResolve>,
+    {
+        let relative_path = relative_path.as_ref();
+
+        let conflict = self
+            .conflicts
+            .iter()
+            .position(|(file, _)| file.relative_path == relative_path);
+        if let Some(conflict) = conflict {
+            self.conflicts.remove(conflict);
+            return Ok(());
+        }
+
+        let Some(node) = self.files.remove(relative_path) else {
+            return Ok(());
+        };
+        log_trace!(["OTreeUpdate"] "Removing file: {relative_path} ({node})");
+
+        if self.single_thorn.remove(node) {
+            self.nodes.remove(node);
+        } else if let Some(parent) = self.parent_of(node) {
+            let is_index = self.nodes[parent].index == Some(node);
+            let has_children = self.nodes[node].children().next().is_some();
+
+            if !is_index && has_children {
+                // Keep it as a middle node of its children
+                let leaf = &mut self.nodes[node];
+                leaf.input_path = None;
+                leaf.output_path = None;
+                self.prune(node);
+            } else {
+                self.nodes[parent].remove_child(node);
+                self.nodes.remove(node);
+                self.prune(parent);
+            }
+        }
+
+        // The conflicting files can be inserted now, the ones that still
+        // conflict are saved again by `insert_file`. The ones that fail keep
+        // their conflict, so they're retried on the next removal.
+        let mut error = None;
+        for (file, conflict) in std::mem::take(&mut self.conflicts) {
+            if let Err(err) = self.insert_file(file.clone(), resolve) {
+                self.conflicts.push((file, conflict));
+                error.get_or_insert(err);
+            }
+        }
+
+        match error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Move a file of the cloud folder
+    pub fn rename_file<R>(
+        &mut self,
+        old_relative_path: impl AsRef<str>,
+        file: CloudFile,
+        resolve: &mut R,
+    ) -> Result<()>
+    where
+        R: FnMut(CloudFile) -> Result<CloudFileResolve>,
+    {
+        self.remove_file(old_relative_path, resolve)?;
+        self.insert_file(file, resolve)
+    }
+
+    /// Conflicting routes of the inserted files, the conflicting file isn't
+    /// on the tree.
+    pub fn get_conflicts(&self) -> impl Iterator<Item = &super::RouteConflict> {
+        self.conflicts.iter().map(|(_, conflict)| conflict)
+    }
+
+    /// # Returns
+    /// + Some(node) -> The node that represents the file
+    /// + None -> The file isn't on the tree
+    fn perform_insert_action<R>(
+        &mut self,
+        node: NodeId,
+        action: OptimizedTreeNodeInsertResult,
+        context: &mut InsertContext<'_, R>,
+    ) -> Result<Option<NodeId>>
+    where
+        R: FnMut(CloudFile) -> Result<CloudFileResolve>,
+    {
+        match action {
+            OptimizedTreeNodeInsertResult::Resolve {
+                new_parent: new_node,
+                new_suffix,
+            }
+            | OptimizedTreeNodeInsertResult::MergeNodes {
+                new_node,
+                new_suffix,
+            } => {
+                let resolved_file = match self.resolve_file(node, &new_suffix, context) {
+                    Ok(resolved_file) => resolved_file,
+                    Err(err) => {
+                        self.nodes.remove(node);
+                        return Err(err);
+                    }
+                };
+
+                if !self.nodes.contains(new_node) {
+                    return Err(anyhow!("Node not found. {new_node}"));
+                }
+                let next_iter = self.with_node(new_node, |new_node, tree| {
+                    new_node.insert(node, resolved_file, tree)
+                });
+
+                self.perform_insert_action(node, next_iter, context)
+            }
+            OptimizedTreeNodeInsertResult::Conflict(conflict) => {
+                log_trace!(["OTreeUpdate"] "Removing conflicting {node}");
+                self.nodes.remove(node);
+                self.conflicts.push((context.file.clone(), conflict));
+                Ok(None)
+            }
+            OptimizedTreeNodeInsertResult::None => Ok(Some(node)),
+        }
+    }
+
+    fn resolve_file<R>(
+        &mut self,
+        leaf: NodeId,
+        suffix: &String,
+        context: &mut InsertContext<'_, R>,
+    ) -> Result<CloudFileResolve>
+    where
+        R: FnMut(CloudFile) -> Result<CloudFileResolve>,
+    {
+        log_trace!(["OTreeUpdate"] "Semi-Inserted to /{suffix}");
+
+        self.nodes[leaf].relative_pathname = suffix.to_owned();
+
+        // Not `with_extension`, "$...slug" would lose its name
+        let suffix = if context.extension.is_empty() {
+            PathBuf::from(suffix)
+        } else {
+            PathBuf::from(format!("{suffix}.{}", context.extension))
+        };
+        log_trace!(["OTreeUpdate"] "Resolving file: {}", suffix.display_debug());
+
+        let cloud_file = CloudFile {
+            relative_path: suffix.display().to_string(),
+            ..context.file.clone()
+        };
+        let resolved_file = (context.resolve)(cloud_file)?;
+        log_trace!(["OTreeUpdate"] "Resolved as {resolved_file:?}");
+
+        Ok(resolved_file)
+    }
+
+    /// Search the node that has `id` as child or index
+    fn parent_of(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes.iter().find_map(|(parent_id, parent)| {
+            (parent.index == Some(id) || parent.children().any(|child| *child == id))
+                .then_some(parent_id)
+        })
+    }
+
+    /// Remove the nodes without routes from `id` to the root, and undo the
+    /// merges that only have one child left.
+    fn prune(&mut self, mut id: NodeId) {
+        loop {
+            let Some(parent) = self.parent_of(id) else {
+                return;
+            };
+
+            let node = &self.nodes[id];
+            if node.is_empty() {
+                log_trace!(["OTreeUpdate"] "Pruning {id}");
+
+                self.nodes[parent].remove_child(id);
+                self.nodes.remove(id);
+                id = parent;
+                continue;
+            }
+
+            // A merged node with only one child: "a" -> "b" goes back to "a/b"
+            let is_merge_node = node.input_path.is_none()
+                && node.index.is_none()
+                && node.dynamic_children.len() == 1
+                && node.children().count() == 1;
+            let child = node
+                .dynamic_children
+                .iter()
+                .next()
+                .map(|(path, child)| (path.clone(), *child));
+
+            if let (true, Some((child_path, child))) = (is_merge_node, child) {
+                let parent_node = &mut self.nodes[parent];
+                let key = parent_node
+                    .dynamic_children
+                    .iter()
+                    .find(|(_, node)| **node == id)
+                    .map(|(path, _)| path.clone());
+
+                if let Some(key) = key {
+                    log_trace!(["OTreeUpdate"] "Splitting merged {key:?}");
+                    let new_key = key.clone() + "/" + &child_path;
+
+                    parent_node.dynamic_children.remove(&key);
+                    parent_node.dynamic_children.insert(new_key.clone(), child);
+
+                    self.nodes[child].relative_pathname = new_key;
+                    self.nodes.remove(id);
+                }
+            }
+
+            return;
+        }
+    }
+}
@@ -5,6 +5,9 @@ pub const MATCHER_PREFIX: &str = "__matcher_matcher_";
 pub enum UrlMatcherSegment {
     Static(String),
     Var(String),
+    /// Var with static text around it: `report-$year.csv`
+    /// -> `Partial("report-", "year", ".csv")`
+    Partial(String, String, String),
 }
 
 impl UrlMatcherSegment {
@@ -17,7 +20,7 @@ impl UrlMatcherSegment {
 
     pub fn is_var(&self) -> bool {
         match self {
-            Self::Var(_) => true,
+            Self::Var(_) | Self::Partial(..) => true,
             _ => false,
         }
     }
@@ -28,11 +31,16 @@ impl UrlMatcherSegment {
             match self {
                 Self::Static(raw) => raw.clone(),
                 Self::Var(varname) => format!("${}", varname),
+                Self::Partial(before, varname, after) => format!("{}${}{}", before, varname, after),
             },
             self.is_var(),
             match self {
                 Self::Static(_) => "".to_owned(),
                 Self::Var(varname) => format!(", varname: \"{}\"", varname),
+                Self::Partial(before, varname, after) => format!(
+                    ", varname: \"{}\", before: \"{}\", after: \"{}\"",
+                    varname, before, after
+                ),
             }
         )
     }
@@ -54,6 +62,16 @@ impl UrlMatcher {
                 if segment.len() != 0 && &segment[0..1] == "$" {
                     has_variables = true;
                     UrlMatcherSegment::Var(segment[1..].to_string())
+                } else if let Some((before, rest)) = segment.split_once('$') {
+                    has_variables = true;
+                    let name_len = rest
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                        .unwrap_or(rest.len());
+                    UrlMatcherSegment::Partial(
+                        before.to_string(),
+                        rest[..name_len].to_string(),
+                        rest[name_len..].to_string(),
+                    )
                 } else {
                     UrlMatcherSegment::Static(segment.to_string())
                 }
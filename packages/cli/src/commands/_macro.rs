@@ -108,6 +108,7 @@ macro_rules! def_command {
 
                 if let Err(err) = r {
                     eprintln!("{err:?}");
+                    std::process::exit(1);
                 }
             }
         }
@@ -2,10 +2,13 @@ use super::_macro::def_command;
 use std::path::PathBuf;
 
 use clap::{value_parser, ValueHint};
-use densky_core::{http::http_discover, utils::join_paths, CompileContext};
+use densky_core::{
+    densky_adapter::utils::join_paths, CompileContext, CompileMode, ConfigFile, ErrorContext,
+    Manifest, Result,
+};
 
 use crate::{
-    compiler::{process_http, write_aux_files},
+    compiler::{load_clouds, write_aux_files},
     progress,
 };
 
@@ -19,38 +22,37 @@ def_command!(BuildCommand("build") {
     process: process
 });
 
-fn process(matches: &clap::ArgMatches) {
+fn process(matches: &clap::ArgMatches) -> Result<()> {
     let folder = matches.get_one::<PathBuf>("folder").unwrap();
-    let cwd = std::env::current_dir().unwrap();
+    let cwd = std::env::current_dir()?;
     let target_path: PathBuf = join_paths(folder, cwd).into();
 
     println!("Building {}", target_path.display());
 
+    let config_file = ConfigFile::discover(&target_path)?;
     let compile_context = CompileContext {
-        output_dir: join_paths(".densky", &target_path),
-        routes_path: join_paths("src/routes", &target_path),
-        views_path: join_paths("src/views", &target_path),
-        static_path: join_paths("src/static", &target_path),
+        output_dir: config_file.output.display().to_string(),
+        cwd: target_path.display().to_string(),
         verbose: true,
-        static_prefix: "static/".to_owned(),
+        mode: CompileMode::Production,
     };
+    let output_mode = config_file.output_mode(compile_context.mode);
 
-    let progress = progress::create_spinner(Some("Discovering"));
+    let loaded_clouds = load_clouds(&config_file, &target_path)?;
 
-    match write_aux_files(&compile_context) {
-        Ok(_) => (),
-        Err(_) => {
-            // let _ = first_build_tx.send(false);
-            return;
-        }
-    };
+    let progress = progress::create_spinner(Some("Building"));
+
+    write_aux_files(&compile_context, &config_file).context("Can't write the aux files")?;
     progress.tick();
 
-    let (mut http_container, http_tree) = http_discover(&compile_context);
+    // Unlike `densky dev`, any error stops the build
+    for cloud in loaded_clouds.iter() {
+        let container = cloud.resolve_optimized_tree(&compile_context)?;
+        Manifest::update(&container, cloud, &compile_context, output_mode)?;
+        progress.tick();
+    }
 
     progress.finish();
 
-    let progress = progress::create_bar(http_container.id_tree(), "Compiling");
-
-    process_http(http_tree.clone(), &mut http_container, Some(progress));
+    Ok(())
 }
@@ -0,0 +1,107 @@
+use super::_macro::def_command;
+use std::{fs, path::PathBuf};
+
+use clap::{value_parser, ValueHint};
+use densky_core::{
+    anyhow,
+    densky_adapter::{utils::join_paths, CloudFilesStrategy},
+    CompileContext, CompileMode, ConfigFile, Manifest, Result,
+};
+
+use crate::compiler::{aux_files, load_clouds};
+
+def_command!(CheckCommand("check") {
+    [folder]("Proyect folder") {
+        default_value: ".",
+        value_hint: ValueHint::DirPath,
+        value_parser: value_parser!(PathBuf),
+    },
+    --production("Check the output of `densky build` instead of `densky dev`") {},
+
+    process: process
+});
+
+/// Build everything in memory and compare it with the output folder. Nothing
+/// is written, so it can run on CI.
+fn process(matches: &clap::ArgMatches) -> Result<()> {
+    let folder = matches.get_one::<PathBuf>("folder").unwrap();
+    let cwd = std::env::current_dir()?;
+    let target_path: PathBuf = join_paths(folder, cwd).into();
+
+    let config_file = ConfigFile::discover(&target_path)?;
+    let mode = if matches.get_flag("production") {
+        CompileMode::Production
+    } else {
+        CompileMode::Development
+    };
+    let compile_context = CompileContext {
+        output_dir: config_file.output.display().to_string(),
+        cwd: target_path.display().to_string(),
+        verbose: false,
+        mode,
+    };
+    let output_mode = config_file.output_mode(mode);
+
+    let loaded_clouds = load_clouds(&config_file, &target_path)?;
+
+    let mut problems = Vec::new();
+    let mut files = aux_files(&compile_context, &config_file);
+    for cloud in loaded_clouds.iter() {
+        if cloud.get_setup()?.file_strategy != CloudFilesStrategy::OptimizedTree {
+            continue;
+        }
+
+        let (container, ignored) = cloud.resolve_optimized_tree_uncached(&compile_context)?;
+        for conflict in container.get_conflicts() {
+            problems.push(format!("[{}] {conflict}", cloud.name));
+        }
+        for file in ignored {
+            problems.push(format!("[{}] Ignored file: {file}", cloud.name));
+        }
+
+        files.extend(Manifest::files(
+            &container,
+            cloud,
+            &compile_context,
+            output_mode,
+        )?);
+    }
+
+    let mut stale = Vec::new();
+    for (path, contents) in files {
+        let relative = path
+            .strip_prefix(&target_path)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+
+        match fs::read(&path) {
+            Ok(current) if current == contents.as_bytes() => {}
+            Ok(_) => stale.push(format!("Outdated: {relative}")),
+            Err(_) => stale.push(format!("Missing: {relative}")),
+        }
+    }
+
+    for problem in problems.iter().chain(stale.iter()) {
+        println!("{problem}");
+    }
+
+    if problems.is_empty() && stale.is_empty() {
+        println!("Everything is up to date");
+        return Ok(());
+    }
+
+    if !stale.is_empty() {
+        let command = match mode {
+            CompileMode::Development => "densky dev",
+            CompileMode::Production => "densky build",
+        };
+        println!("Run `{command}` to update the output");
+    }
+
+    Err(anyhow!(
+        "Found {} route problems and {} stale files",
+        problems.len(),
+        stale.len()
+    ))
+}
@@ -1,5 +1,4 @@
 use super::_macro::def_command;
-use std::env;
 use std::{
     path::{Path, PathBuf},
     process,
@@ -12,18 +11,16 @@ use std::{
 };
 
 use crate::{
-    compiler::write_aux_files,
+    compiler::{load_clouds, write_aux_files},
     progress,
     watcher::{PollWatcher, WatchKind},
 };
 use clap::{value_parser, ValueHint};
-use densky_core::densky_adapter::{log_warn, CloudVersion};
-use densky_core::sky::search_cloud;
+use densky_core::optimized_tree::check_conflicts;
 use densky_core::{
     anyhow,
     densky_adapter::{log_error, utils::join_paths},
-    sky::CloudPlugin,
-    CompileContext, ConfigFile, Manifest, Result,
+    CompileContext, CompileMode, ConfigFile, Manifest, Result,
 };
 
 def_command!(DevCommand("dev") {
@@ -52,43 +49,11 @@ fn process(matches: &clap::ArgMatches) -> Result<()> {
         output_dir: config_file.output.display().to_string(),
         cwd: target_path.display().to_string(),
         verbose: true,
+        mode: CompileMode::Development,
     };
+    let output_mode = config_file.output_mode(compile_context.mode);
 
-    let clouds = &config_file.dependencies;
-    let progress = progress::create_bar(clouds.len(), "Loading clouds");
-    let mut loaded_clouds: Vec<CloudPlugin> = Vec::new();
-
-    let densky_installation = env::var("DENSKY_INSTALL").unwrap_or_else(|_| {
-        env::var("HOME")
-            .map(|x| format!("{x}/.densky"))
-            .unwrap_or_default()
-    });
-    let densky_installation: PathBuf = densky_installation.into();
-    let cloud_search_entries = [vec![densky_installation], config_file.vendor.clone()].concat();
-
-    for cloud in clouds.values() {
-        progress.set_message(cloud.name.clone());
-        let cloud_libname = format!("cloud_{}", cloud.name.replace("-", "_"));
-
-        let cloud_path = match &cloud.version {
-            CloudVersion::Path(p) => join_paths(&p, &target_path).into(),
-            CloudVersion::Semver(_) => {
-                // TODO: Implement version requirement
-                log_warn!(["TODO"] "Ignoring version requirements");
-                search_cloud(&cloud.name, &cloud_search_entries)
-                    .ok_or(anyhow!("Can't find cloud"))?
-            }
-            CloudVersion::Unknown(_) => unreachable!(),
-        };
-
-        let mut cloud = CloudPlugin::new(cloud_libname, cloud_path)?;
-        cloud.setup()?;
-        loaded_clouds.push(cloud);
-
-        progress.tick();
-    }
-
-    progress.finish();
+    let loaded_clouds = load_clouds(&config_file, &target_path)?;
 
     let progress = progress::create_spinner(Some("Discovering"));
 
@@ -100,18 +65,28 @@ fn process(matches: &clap::ArgMatches) -> Result<()> {
     };
     progress.tick();
 
+    // Kept between updates, the file events are applied over them
+    let mut containers = Vec::with_capacity(loaded_clouds.len());
     for cloud in loaded_clouds.iter() {
         let http_container = cloud.resolve_optimized_tree(&compile_context)?;
 
-        Manifest::update(&http_container, &cloud, &compile_context)?;
+        // The manifest is written only when it's built, so a broken one
+        // doesn't stop the dev server and is retried on the next change
+        if let Err(err) = Manifest::update(&http_container, cloud, &compile_context, output_mode) {
+            log_error!([cloud.name] "Error building manifest: {err:#}");
+        }
+        containers.push(http_container);
         progress.tick();
     }
 
     progress.finish();
 
+    // The output folder can be changed on the config file, so the runtime
+    // gets the path of the manifest to know when it's updated
     let Ok(mut deno) = process::Command::new("deno")
         .args(["run", "-A"])
-        .arg(format!("{}/.densky/dev.ts", target_path.display()))
+        .arg(join_paths("dev.ts", &compile_context.output_dir))
+        .env("DENSKY_MANIFEST", Manifest::path(&compile_context))
         .spawn()
     else {
         log_error!(["RUNTIME"] "Deno command failed to run.\nCheck your Deno installation:\n > deno --version");
@@ -125,18 +100,49 @@ fn process(matches: &clap::ArgMatches) -> Result<()> {
     '_loop: loop {
         let event = watching_poll.poll();
         if event.len() != 0 {
-            for cloud in loaded_clouds.iter() {
-                let http_container = cloud.resolve_optimized_tree(&compile_context)?;
-
-                match Manifest::update(&http_container, &cloud, &compile_context) {
-                    Ok(_) => {}
-                    Err(err) => {
-                        eprintln!("Error updating manifest: {err}")
+            // Generated files that were written, the unchanged ones aren't
+            let mut generated = Vec::new();
+            for (cloud, http_container) in loaded_clouds.iter().zip(containers.iter_mut()) {
+                let mut changed = false;
+                for e in event.iter() {
+                    let update = match e.kind {
+                        WatchKind::Create => {
+                            cloud.insert_optimized_tree_file(http_container, &e.path)
+                        }
+                        WatchKind::Remove => {
+                            cloud.remove_optimized_tree_file(http_container, &e.path)
+                        }
+                        // The routes only depend on the file paths
+                        WatchKind::Modify => Ok(false),
+                    };
+
+                    match update {
+                        Ok(update) => changed |= update,
+                        Err(err) => log_error!([cloud.name] "Error updating tree: {err}"),
                     }
                 }
+
+                if !changed {
+                    continue;
+                }
+
+                // The conflicting files stay out of the tree until they're fixed
+                let _ = check_conflicts(cloud, http_container);
+
+                // On error the last good manifest keeps being served
+                match Manifest::update(http_container, cloud, &compile_context, output_mode) {
+                    Ok(files) => generated.extend(files),
+                    Err(err) => log_error!([cloud.name] "Error updating manifest: {err:#}"),
+                }
             }
 
-            send_update(event.iter().map(|e| (e.kind.clone(), &e.path)));
+            let generated = generated.into_iter().map(|path| (WatchKind::Modify, path));
+            send_update(
+                event
+                    .iter()
+                    .map(|e| (e.kind.clone(), e.path.clone()))
+                    .chain(generated),
+            );
         }
 
         // wait to interrupt
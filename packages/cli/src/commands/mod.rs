@@ -1,8 +1,12 @@
 pub(super) mod _macro;
-// mod build;
+mod build;
+mod check;
 mod dev;
 // mod plugin_test;
+mod routes;
 
-// pub use build::BuildCommand;
+pub use self::build::BuildCommand;
+pub use self::check::CheckCommand;
 pub use self::dev::DevCommand;
 // pub use self::plugin_test::PluginTestCommand;
+pub use self::routes::RoutesCommand;
@@ -1,7 +1,9 @@
 use std::path::PathBuf;
 
 use densky_core::{
-    densky_adapter::utils::join_paths, optimized_tree::OptimizedTreeContainer, sky::CloudPlugin,
+    densky_adapter::utils::join_paths,
+    optimized_tree::{NodeId, OptimizedTreeContainer},
+    sky::CloudPlugin,
     CompileContext,
 };
 
@@ -54,8 +56,12 @@ fn process(matches: &clap::ArgMatches) {
     plugin.close();
 }
 
-fn process_node(id: u64, plugin: &mut CloudPlugin, container: &OptimizedTreeContainer) -> String {
-    let node = container.nodes.get_reader(id).unwrap();
+fn process_node(
+    id: NodeId,
+    plugin: &mut CloudPlugin,
+    container: &OptimizedTreeContainer,
+) -> String {
+    let node = &container.nodes[id];
 
     let mut static_children = String::new();
     let mut children = String::new();
@@ -74,11 +80,7 @@ fn process_node(id: u64, plugin: &mut CloudPlugin, container: &OptimizedTreeCont
         children += &process_node(*id, plugin, container);
     }
 
-    let dynamic_child = if let Some((id, varname)) = node.dynamic.as_ref() {
-        let mut child = container.nodes.get_writer(*id).unwrap();
-        child.varname = Some(varname.clone());
-        drop(child);
-
+    let dynamic_child = if let Some((id, _)) = node.dynamic.as_ref() {
         process_node(*id, plugin, container)
     } else {
         String::new()
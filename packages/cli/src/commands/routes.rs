@@ -0,0 +1,266 @@
+use super::_macro::def_command;
+use std::{collections::BTreeSet, path::PathBuf};
+
+use clap::{value_parser, ValueHint};
+use densky_core::{
+    anyhow,
+    densky_adapter::{
+        optimized_tree::{OptimizedTreeContainer, OptimizedTreeMatch, OptimizedTreeRoute},
+        utils::join_paths,
+        CloudFilesStrategy,
+    },
+    sky::CloudPlugin,
+    CompileContext, CompileMode, ConfigFile, Result,
+};
+use serde::Serialize;
+
+use crate::compiler::load_clouds;
+
+def_command!(RoutesCommand("routes") {
+    [folder]("Proyect folder") {
+        default_value: ".",
+        value_hint: ValueHint::DirPath,
+        value_parser: value_parser!(PathBuf),
+    },
+    --format(=FORMAT, "Output format") {
+        default_value: "table",
+        value_parser: ["table", "json", "mermaid", "dot"],
+    },
+    --json("Print the routes as JSON, same as `--format json`") {},
+    command(cmd) {
+        cmd.subcommand(RoutesMatchCommand::command())
+    },
+
+    process: process
+});
+
+def_command!(RoutesMatchCommand("match") {
+    <path>("URL path, like /users/42") {},
+    [folder]("Proyect folder") {
+        default_value: ".",
+        value_hint: ValueHint::DirPath,
+        value_parser: value_parser!(PathBuf),
+    },
+    --json("Print the match as JSON") {},
+
+    process: process_match
+});
+
+#[derive(Serialize)]
+struct RouteRow {
+    cloud: String,
+    #[serde(flatten)]
+    route: OptimizedTreeRoute,
+}
+
+#[derive(Serialize)]
+struct MatchRow {
+    cloud: String,
+    #[serde(flatten)]
+    route: OptimizedTreeMatch,
+}
+
+fn process(matches: &clap::ArgMatches) -> Result<()> {
+    if let Some(("match", sub_matches)) = matches.subcommand() {
+        RoutesMatchCommand::process(sub_matches);
+        return Ok(());
+    }
+
+    let (target_path, compile_context, config_file) = project(matches)?;
+    let clouds = load_clouds(&config_file, &target_path)?;
+    let trees = resolve_trees(&clouds, &compile_context)?;
+
+    let format = if matches.get_flag("json") {
+        "json"
+    } else {
+        matches.get_one::<String>("format").unwrap().as_str()
+    };
+
+    match format {
+        "json" => {
+            let rows = route_rows(&trees, &target_path);
+            println!("{}", serde_json::to_string_pretty(&rows)?);
+        }
+        "mermaid" => {
+            for (cloud, container) in &trees {
+                println!("%% {}\n{}", cloud.name, container.to_mermaid());
+            }
+        }
+        "dot" => {
+            for (cloud, container) in &trees {
+                println!("// {}\n{}", cloud.name, container.to_dot());
+            }
+        }
+        _ => print_table(&route_rows(&trees, &target_path)),
+    }
+
+    Ok(())
+}
+
+fn process_match(matches: &clap::ArgMatches) -> Result<()> {
+    let path = matches.get_one::<String>("path").unwrap();
+
+    let (target_path, compile_context, config_file) = project(matches)?;
+    let clouds = load_clouds(&config_file, &target_path)?;
+    let trees = resolve_trees(&clouds, &compile_context)?;
+
+    // The first cloud that resolves the path handles it
+    let found = trees.iter().find_map(|(cloud, container)| {
+        container
+            .match_path(path)
+            .map(|found| (cloud.name.clone(), found))
+    });
+
+    if matches.get_flag("json") {
+        let found = found.map(|(cloud, route)| MatchRow { cloud, route });
+        println!("{}", serde_json::to_string_pretty(&found)?);
+        return Ok(());
+    }
+
+    let Some((cloud, route)) = found else {
+        return Err(anyhow!("No route matches {path:?}"));
+    };
+
+    // Labels are aligned with the longest thorn name
+    let width = route
+        .thorns
+        .keys()
+        .map(|name| name.len() + 2)
+        .fold("Cloud: ".len() + 1, usize::max);
+
+    println!(
+        "{:<width$}{}",
+        "File:",
+        relative_to(&target_path, route.file)
+    );
+    println!("{:<width$}{cloud}", "Cloud:");
+    for (name, value) in &route.params {
+        println!("{:<width$}{name} = {value:?}", "Param:");
+    }
+    for (name, files) in route.thorns {
+        for file in files {
+            println!(
+                "{:<width$}{}",
+                format!("{name}:"),
+                relative_to(&target_path, file)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Project folder, context and config of the command
+fn project(matches: &clap::ArgMatches) -> Result<(PathBuf, CompileContext, ConfigFile)> {
+    let folder = matches.get_one::<PathBuf>("folder").unwrap();
+    let cwd = std::env::current_dir()?;
+    let target_path: PathBuf = join_paths(folder, cwd).into();
+
+    let config_file = ConfigFile::discover(&target_path)?;
+    let compile_context = CompileContext {
+        output_dir: config_file.output.display().to_string(),
+        cwd: target_path.display().to_string(),
+        verbose: false,
+        mode: CompileMode::Development,
+    };
+
+    Ok((target_path, compile_context, config_file))
+}
+
+fn relative_to(target_path: &PathBuf, file: String) -> String {
+    PathBuf::from(&file)
+        .strip_prefix(target_path)
+        .map(|file| file.display().to_string())
+        .unwrap_or(file)
+}
+
+/// Resolve the trees of all the clouds with optimized tree
+fn resolve_trees<'a>(
+    clouds: &'a [CloudPlugin],
+    compile_context: &CompileContext,
+) -> Result<Vec<(&'a CloudPlugin, OptimizedTreeContainer)>> {
+    let mut trees = Vec::new();
+    for cloud in clouds {
+        if cloud.get_setup()?.file_strategy != CloudFilesStrategy::OptimizedTree {
+            continue;
+        }
+
+        trees.push((cloud, cloud.resolve_optimized_tree(compile_context)?));
+    }
+
+    Ok(trees)
+}
+
+/// One row for each route, with the files relative to the project
+fn route_rows(
+    trees: &[(&CloudPlugin, OptimizedTreeContainer)],
+    target_path: &PathBuf,
+) -> Vec<RouteRow> {
+    let relative = |file| relative_to(target_path, file);
+
+    let mut rows = Vec::new();
+    for (cloud, container) in trees {
+        for mut route in container.routes() {
+            route.file = relative(route.file);
+            for files in route.thorns.values_mut() {
+                *files = files.drain(..).map(relative).collect();
+            }
+
+            rows.push(RouteRow {
+                cloud: cloud.name.clone(),
+                route,
+            });
+        }
+    }
+
+    rows
+}
+
+fn print_table(rows: &[RouteRow]) {
+    // One column for each thorn (middleware, fallback...)
+    let thorns: BTreeSet<&String> = rows
+        .iter()
+        .flat_map(|row| row.route.thorns.keys())
+        .collect();
+
+    let mut table: Vec<Vec<String>> = vec![vec!["URL".to_string(), "FILE".to_string()]
+        .into_iter()
+        .chain(thorns.iter().map(|name| name.to_uppercase()))
+        .chain(vec!["CLOUD".to_string()])
+        .collect()];
+
+    for row in rows {
+        let thorn_cells = thorns.iter().map(|name| {
+            row.route
+                .thorns
+                .get(*name)
+                .map(|files| files.join(", "))
+                .filter(|files| !files.is_empty())
+                .unwrap_or_else(|| "-".into())
+        });
+
+        table.push(
+            vec![row.route.url.clone(), row.route.file.clone()]
+                .into_iter()
+                .chain(thorn_cells)
+                .chain(vec![row.cloud.clone()])
+                .collect(),
+        );
+    }
+
+    let mut widths = vec![0; table[0].len()];
+    for row in &table {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    for row in &table {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{cell:width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
@@ -1,28 +1,30 @@
-use std::{fs, io};
+use std::{env, fs, path::PathBuf};
 
 use densky_core::{
-    densky_adapter::utils::join_paths,
+    anyhow,
+    densky_adapter::{log_warn, utils::join_paths, CloudVersion},
+    sky::{search_cloud, CloudPlugin},
     // http::{HttpLeaf, HttpTree},
-    utils::import_filename,
+    utils::{import_filename, write_if_changed},
     // views::ViewLeaf,
     // walker::{WalkerContainer, WalkerLeaf, WalkerTree},
     CompileContext,
     ConfigFile,
+    Result,
 };
+
+use crate::progress;
 // use indicatif::ProgressBar;
 
-pub fn write_aux_files(
+/// Entry files of the runtime, without writing them
+pub fn aux_files(
     compile_context: &CompileContext,
     config_file: &ConfigFile,
-) -> io::Result<()> {
-    fs::create_dir_all(&compile_context.output_dir)?;
-
+) -> Vec<(PathBuf, String)> {
     let header = "// THIS FILE WAS GENERATED BY DENSKY-BACKEND (By Apika Luca)";
     // dev.ts
-    fs::write(
-        join_paths("dev.ts", &compile_context.output_dir),
-        format!(
-            "{header}
+    let dev = format!(
+        "{header}
 import {{ DevServer }} from \"densky/cloud.ts\";
 import \"{config}\";
 import \"{sky_load}\"
@@ -31,23 +33,82 @@ const server = new DevServer({{ port: 8000, verbose: true }});
 
 server.start();
 ",
-            config = import_filename("../config.ts"),
-            sky_load = import_filename("./sky.load.ts")
-        ),
-    )?;
+        config = import_filename("../config.ts"),
+        sky_load = import_filename("./sky.load.ts")
+    );
 
-    let clouds = config_file
+    // Sorted, the map order changes between runs and the file would too
+    let mut clouds = config_file
         .dependencies
         .values()
         .map(|cloud| format!("import \"densky/{}.ts\";", cloud.name))
-        .collect::<Vec<String>>()
-        .join("\n");
+        .collect::<Vec<String>>();
+    clouds.sort();
+    let clouds = clouds.join("\n");
 
     // sky.load.ts
-    fs::write(
-        join_paths("sky.load.ts", &compile_context.output_dir),
-        format!("{header}\n{clouds}"),
-    )?;
+    let sky_load = format!("{header}\n{clouds}");
+
+    let path = |name: &str| PathBuf::from(join_paths(name, &compile_context.output_dir));
+    vec![(path("dev.ts"), dev), (path("sky.load.ts"), sky_load)]
+}
+
+/// Write the entry files of the runtime, returns the ones that changed
+pub fn write_aux_files(
+    compile_context: &CompileContext,
+    config_file: &ConfigFile,
+) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(&compile_context.output_dir)?;
+
+    let mut changed = Vec::new();
+    for (path, contents) in aux_files(compile_context, config_file) {
+        if write_if_changed(&path, contents)? {
+            changed.push(path);
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Load and setup all the clouds of the project
+pub fn load_clouds(config_file: &ConfigFile, target_path: &PathBuf) -> Result<Vec<CloudPlugin>> {
+    let clouds = &config_file.dependencies;
+    let progress = progress::create_bar(clouds.len(), "Loading clouds");
+    let mut loaded_clouds: Vec<CloudPlugin> = Vec::new();
+
+    let densky_installation = env::var("DENSKY_INSTALL").unwrap_or_else(|_| {
+        env::var("HOME")
+            .map(|x| format!("{x}/.densky"))
+            .unwrap_or_default()
+    });
+    let densky_installation: PathBuf = densky_installation.into();
+    let cloud_search_entries = [vec![densky_installation], config_file.vendor.clone()].concat();
+
+    for cloud in clouds.values() {
+        progress.set_message(cloud.name.clone());
+        let cloud_libname = format!("cloud_{}", cloud.name.replace("-", "_"));
+
+        let cloud_path = match &cloud.version {
+            CloudVersion::Path(p) => join_paths(p, target_path).into(),
+            CloudVersion::Semver(_) => {
+                // TODO: Implement version requirement
+                log_warn!(["TODO"] "Ignoring version requirements");
+                search_cloud(&cloud.name, &cloud_search_entries)
+                    .ok_or(anyhow!("Can't find cloud"))?
+            }
+            CloudVersion::Unknown(_) => unreachable!(),
+        };
+
+        let options = cloud.options.clone();
+        let mut cloud = CloudPlugin::new(cloud_libname, cloud_path)?;
+        cloud.set_options(options);
+        cloud.setup()?;
+        loaded_clouds.push(cloud);
+
+        progress.tick();
+    }
+
+    progress.finish();
 
-    Ok(())
+    Ok(loaded_clouds)
 }
@@ -3,6 +3,8 @@ extern crate anstyle;
 extern crate clap;
 extern crate densky_core;
 extern crate indicatif;
+extern crate serde;
+extern crate serde_json;
 extern crate ureq;
 
 pub mod commands;
@@ -10,7 +12,7 @@ pub mod compiler;
 pub mod progress;
 pub mod watcher;
 
-use self::commands::DevCommand;
+use self::commands::{BuildCommand, CheckCommand, DevCommand, RoutesCommand};
 use anstyle::{AnsiColor, Color, Style};
 use clap::{builder::Styles, command};
 
@@ -41,8 +43,10 @@ fn main() {
                 .valid(Style::new().fg_color(Some(Color::Ansi(AnsiColor::BrightGreen))))
                 .invalid(Style::new().fg_color(Some(Color::Ansi(AnsiColor::BrightRed)))),
         )
-        .subcommand(DevCommand::command());
-    // .subcommand(BuildCommand::command())
+        .subcommand(DevCommand::command())
+        .subcommand(RoutesCommand::command())
+        .subcommand(BuildCommand::command())
+        .subcommand(CheckCommand::command());
     // .subcommand(PluginTestCommand::command());
 
     #[cfg(not(debug_assertions))]
@@ -54,7 +58,9 @@ fn main() {
 
     match matches.subcommand() {
         Some(("dev", sub_matches)) => DevCommand::process(sub_matches),
-        // Some(("build", sub_matches)) => BuildCommand::process(sub_matches),
+        Some(("routes", sub_matches)) => RoutesCommand::process(sub_matches),
+        Some(("build", sub_matches)) => BuildCommand::process(sub_matches),
+        Some(("check", sub_matches)) => CheckCommand::process(sub_matches),
         // Some(("plugin-test", sub_matches)) => PluginTestCommand::process(sub_matches),
         Some((cmd_name, _)) => println!("Unknown command: {cmd_name}"),
         None => todo!("Main entry"),
@@ -0,0 +1,239 @@
+#[macro_use]
+extern crate criterion;
+extern crate densky_core;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use criterion::{BatchSize, BenchmarkId, Criterion};
+use densky_core::{
+    densky_adapter::{utils::join_paths, CloudDependencyOption, CloudOptions},
+    optimized_tree::optimized_tree_strategy,
+    sky::CloudPlugin,
+    utils::format_js,
+    CompileContext, CompileMode, Manifest,
+};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 50_000];
+
+/// `strategy` option of the http router
+const ROUTER_STRATEGIES: [&str; 4] = ["tree", "switch", "map", "regex"];
+
+/// A synthetic project with `n` route files on `src/http`
+struct Project {
+    input_path: PathBuf,
+    ctx: CompileContext,
+}
+
+impl Project {
+    fn new(n: usize) -> Project {
+        let target_path = std::env::temp_dir().join(format!("densky-bench/{n}"));
+        let input_path = target_path.join("src/http");
+
+        if !input_path.exists() {
+            for route in routes(n) {
+                let file = input_path.join(&route);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(
+                    file,
+                    format!("export default function () {{ return {route:?}; }}\n"),
+                )
+                .unwrap();
+            }
+        }
+
+        let ctx = CompileContext {
+            output_dir: join_paths(".densky", &target_path),
+            cwd: target_path.display().to_string(),
+            verbose: false,
+            mode: CompileMode::Development,
+        };
+
+        Project { input_path, ctx }
+    }
+
+    fn clear_cache(&self, plugin: &CloudPlugin) {
+        let cache = join_paths(format!("cache/{}.json", plugin.name), &self.ctx.output_dir);
+        let _ = fs::remove_file(cache);
+    }
+}
+
+/// `n` routes split in sections of 100, with indexes, thorns, params,
+/// nested params and catch-alls.
+fn routes(n: usize) -> Vec<String> {
+    (0..n)
+        .map(|i| {
+            let (section, j) = (i / 100, i % 100);
+            match j {
+                0 => format!("s{section}/_index.ts"),
+                1 => format!("s{section}/_middleware.ts"),
+                2 => format!("s{section}/_fallback.ts"),
+                3 => format!("s{section}/$id.ts"),
+                4 => format!("s{section}/$id/edit.ts"),
+                5 => format!("s{section}/$id/$repo/settings.ts"),
+                6 => format!("s{section}/files/$...path.ts"),
+                _ if j % 3 == 0 => format!("s{section}/page{j}.ts"),
+                _ if j % 3 == 1 => format!("s{section}/g{j}/$slug.ts"),
+                _ => format!("s{section}/g{j}/$slug/view.ts"),
+            }
+        })
+        .collect()
+}
+
+/// The http router cloud, built with `just build-cloud http-router`.
+/// `just bench` builds it before running the benchmarks.
+fn http_router() -> CloudPlugin {
+    let target_dir = std::env::var("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target"));
+
+    let mut plugin = CloudPlugin::new("cloud_http_router".into(), target_dir.join("release"))
+        .expect("Build the http router first: just build-cloud http-router");
+    plugin.setup().unwrap();
+    plugin
+}
+
+fn strategy(c: &mut Criterion) {
+    let plugin = http_router();
+    let mut group = c.benchmark_group("optimized_tree_strategy");
+    group.sample_size(10);
+
+    for n in SIZES {
+        let project = Project::new(n);
+
+        group.bench_with_input(BenchmarkId::new("cold", n), &project, |b, project| {
+            b.iter_batched(
+                || project.clear_cache(&plugin),
+                |_| optimized_tree_strategy(&project.input_path, &plugin, &project.ctx).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+
+        group.bench_with_input(BenchmarkId::new("cached", n), &project, |b, project| {
+            optimized_tree_strategy(&project.input_path, &plugin, &project.ctx).unwrap();
+            b.iter(|| optimized_tree_strategy(&project.input_path, &plugin, &project.ctx).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn manifest(c: &mut Criterion) {
+    let plugin = http_router();
+    let mut build_group = c.benchmark_group("manifest_build");
+    build_group.sample_size(10);
+
+    let mut manifests = Vec::new();
+    for n in SIZES {
+        let project = Project::new(n);
+        let (container, _) =
+            optimized_tree_strategy(&project.input_path, &plugin, &project.ctx).unwrap();
+
+        build_group.bench_with_input(
+            BenchmarkId::from_parameter(n),
+            &container,
+            |b, container| {
+                b.iter(|| Manifest::build_raw(&plugin, container, &project.ctx).unwrap())
+            },
+        );
+
+        manifests.push((
+            n,
+            Manifest::build_raw(&plugin, &container, &project.ctx).unwrap(),
+        ));
+    }
+    build_group.finish();
+
+    let mut format_group = c.benchmark_group("format_js");
+    format_group.sample_size(10);
+    for (n, manifest) in manifests {
+        format_group.bench_with_input(BenchmarkId::from_parameter(n), &manifest, |b, manifest| {
+            b.iter(|| format_js(manifest.as_str()).unwrap())
+        });
+    }
+    format_group.finish();
+}
+
+/// Size and nesting of the emitted code, they don't depend on the machine
+struct Complexity {
+    bytes: usize,
+    lines: usize,
+    branches: usize,
+    max_depth: usize,
+}
+
+impl Complexity {
+    fn new(code: &str) -> Complexity {
+        // The comments include the source map markers
+        let lines = code.lines().filter(|line| {
+            let trimmed = line.trim();
+            !trimmed.is_empty() && !trimmed.starts_with("//")
+        });
+        let mut complexity = Complexity {
+            bytes: code.len(),
+            lines: 0,
+            branches: 0,
+            max_depth: 0,
+        };
+
+        for line in lines {
+            let trimmed = line.trim_start();
+            // `print` indents with two spaces
+            let depth = (line.len() - trimmed.len()) / 2;
+
+            complexity.lines += 1;
+            complexity.max_depth = complexity.max_depth.max(depth);
+            if trimmed.starts_with("if (") || trimmed.starts_with("case ") {
+                complexity.branches += 1;
+            }
+        }
+
+        complexity
+    }
+}
+
+fn router_strategies(c: &mut Criterion) {
+    let mut plugin = http_router();
+    let mut group = c.benchmark_group("router_strategies");
+    group.sample_size(10);
+
+    let mut report = Vec::new();
+    for n in SIZES {
+        let project = Project::new(n);
+        let (container, _) =
+            optimized_tree_strategy(&project.input_path, &plugin, &project.ctx).unwrap();
+
+        for strategy in ROUTER_STRATEGIES {
+            let mut options = CloudOptions::default();
+            options.insert(
+                "strategy".into(),
+                CloudDependencyOption::String(strategy.into()),
+            );
+            plugin.set_options(options);
+
+            group.bench_with_input(BenchmarkId::new(strategy, n), &container, |b, container| {
+                b.iter(|| Manifest::build_raw(&plugin, container, &project.ctx).unwrap())
+            });
+
+            let manifest = Manifest::build_raw(&plugin, &container, &project.ctx).unwrap();
+            report.push((strategy, n, Complexity::new(&manifest)));
+        }
+    }
+    group.finish();
+
+    println!(
+        "\n{:<8} {:>7} {:>12} {:>9} {:>9} {:>6}",
+        "strategy", "routes", "bytes", "lines", "branches", "depth"
+    );
+    for (strategy, n, complexity) in report {
+        println!(
+            "{strategy:<8} {n:>7} {:>12} {:>9} {:>9} {:>6}",
+            complexity.bytes, complexity.lines, complexity.branches, complexity.max_depth
+        );
+    }
+}
+
+criterion_group!(benches, strategy, manifest, router_strategies);
+criterion_main!(benches);
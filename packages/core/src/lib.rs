@@ -1,12 +1,17 @@
+extern crate ahash;
 pub extern crate densky_adapter;
 pub extern crate dprint_plugin_typescript;
 extern crate dynamic_html;
 pub extern crate jsonc_parser;
 extern crate libloading;
 extern crate pathdiff;
+extern crate serde;
+extern crate serde_json;
 extern crate walkdir;
 
-pub use densky_adapter::{anyhow, AHashMap, AHashSet, CompileContext, Error, ErrorContext, Result};
+pub use densky_adapter::{
+    anyhow, AHashMap, AHashSet, CompileContext, CompileMode, Error, ErrorContext, Result,
+};
 
 // pub mod http;
 mod manifest;
@@ -1,126 +1,205 @@
 #[allow(non_snake_case)]
 pub mod Manifest {
-    use std::{fs, io};
+    use std::path::PathBuf;
 
-    use crate::optimized_tree::OptimizedTreeContainer;
+    use crate::optimized_tree::{NodeId, OptimizedTreeContainer};
     use crate::sky::CloudPlugin;
-    use crate::utils::format_js;
-    use densky_adapter::utils::join_paths;
-    use densky_adapter::{CloudManifestUpdate, CompileContext};
+    use crate::utils::{write_if_changed, OutputMode};
+    use densky_adapter::js::{Origin, SourceMap};
+    use densky_adapter::utils::{join_paths, relative_path};
+    use densky_adapter::{anyhow, CloudManifestUpdate, CompileContext, ErrorContext, Result};
 
     /// Generate TS code for this node and children
     fn build_node(
-        id: u64,
+        id: NodeId,
         plugin: &CloudPlugin,
         container: &OptimizedTreeContainer,
-    ) -> CloudManifestUpdate {
+        context: &CompileContext,
+    ) -> Result<CloudManifestUpdate> {
         let mut updates = CloudManifestUpdate::new();
-        let node = container.nodes.get_reader(id).unwrap(); // NOTE: This uses container
+        let node = &container.nodes[id];
 
-        let mut static_children = String::new();
-        let mut children = String::new();
+        let mut static_children = Vec::new();
+        let mut children = Vec::new();
 
         for (pathname, id) in node.static_children.iter() {
-            let children_update = build_node(*id, plugin, container); // NOTE: This uses container
-            let children_content = &String::new();
-            let children_content = match children_update.content() {
-                Some(c) => c,
-                None => children_content,
-            };
-
-            const EXTRA_LEN: usize = "\"".len() + "\": () => {".len() + "},".len();
-
-            static_children.reserve_exact(pathname.len() + children_content.len() + EXTRA_LEN);
-            static_children.push('"');
-            static_children += pathname;
-            static_children.push_str("\": () => {");
-            static_children += children_content;
-            static_children.push('}');
-            static_children.push(',');
-
-            updates += children_update;
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            let children_content = children_update.take_statements().unwrap_or_default();
+            static_children.push((pathname.clone(), children_content));
+
+            updates.merge(&children_update)?;
         }
 
         for (_, id) in node.dynamic_children.iter() {
-            let children_update = build_node(*id, plugin, container); // NOTE: This uses container
-            children += &children_update.content().unwrap_or(&String::new());
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            children.extend(children_update.take_statements().unwrap_or_default());
 
-            updates += children_update;
+            updates.merge(&children_update)?;
         }
 
-        let dynamic_child = if let Some((id, varname)) = node.dynamic.as_ref() {
-            let mut child = container.nodes.get_writer(*id).unwrap(); // NOTE: This uses container
-            child.varname = Some(varname.clone());
-            drop(child);
+        // Params mixed with static text go first, then the constrained ones
+        // and the unconstrained one. On mismatch they fall through to the
+        // next candidate
+        let mut dynamic_child = Vec::new();
+        for (id, _) in node.partial_dynamic.iter() {
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            dynamic_child.extend(children_update.take_statements().unwrap_or_default());
 
-            build_node(*id, plugin, container) // NOTE: This uses container
-        } else {
-            CloudManifestUpdate::new()
-        };
+            updates.merge(&children_update)?;
+        }
 
-        updates += &dynamic_child;
+        for (id, _, _) in node.constrained_dynamic.iter() {
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            dynamic_child.extend(children_update.take_statements().unwrap_or_default());
+
+            updates.merge(&children_update)?;
+        }
 
-        let binding = String::new();
-        let dynamic_child = dynamic_child.content().unwrap_or(&binding);
+        if let Some((id, _)) = node.dynamic.as_ref() {
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            dynamic_child.extend(children_update.take_statements().unwrap_or_default());
+
+            updates.merge(&children_update)?;
+        }
+
+        // Catch-all goes after the dynamic child, so it's only reached when
+        // static and single-segment dynamic children don't match
+        if let Some((id, _)) = node.catch_all.as_ref() {
+            let mut children_update = build_node(*id, plugin, container, context)?;
+            dynamic_child.extend(children_update.take_statements().unwrap_or_default());
+
+            updates.merge(&children_update)?;
+        }
+
+        let leaf = node.into_leaf(container);
+        // The map is next to the manifest, so the route file is relative to it
+        let file = leaf.index.as_ref().map(|index| {
+            relative_path(index, container.get_output_dir())
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| index.clone())
+        });
+        let origin = Origin::leaf(&plugin.name, &leaf.pathname, file);
 
-        let leaf = node.into_leaf(container); // NOTE: This uses container
         let plugin_updates = unsafe {
-            plugin
-                .cloud_optimized_manifest_call(
-                    leaf,
-                    static_children,
-                    children,
-                    dynamic_child.to_string(),
-                )
-                .unwrap()
-        };
-
-        updates += &plugin_updates;
-
-        updates = if let Some(content) = &plugin_updates.content() {
-            updates.set_content(content.to_string())
-        } else {
-            updates
-        };
-
-        updates
+            plugin.cloud_optimized_manifest_call(
+                leaf,
+                static_children,
+                children,
+                dynamic_child,
+                context,
+            )
+        }
+        .with_context(|| format!("Can't generate the manifest of {origin}"))?;
+        let mut plugin_updates = plugin_updates.set_origin(origin);
+
+        let content = plugin_updates.take_statements();
+        updates.merge(&plugin_updates)?;
+
+        Ok(match content {
+            Some(content) => updates.set_statements(content),
+            None => updates,
+        })
     }
 
-    /// Generate a manifest file from a container.
-    fn build(plugin: &CloudPlugin, container: &OptimizedTreeContainer) -> String {
+    /// Generate a manifest file from a container, without formatting it. The
+    /// origin markers are kept, see [`SourceMap::extract`].
+    pub fn build_raw(
+        plugin: &CloudPlugin,
+        container: &OptimizedTreeContainer,
+        context: &CompileContext,
+    ) -> Result<String> {
         let mut imports = String::new();
+        let mut hoisted = String::new();
         let mut args = String::new();
         let mut content = String::new();
 
-        let before_updates = unsafe { plugin.cloud_before_manifest() };
+        // The imports of the whole manifest are merged, so each module is
+        // imported once
+        let mut manifest = unsafe { plugin.cloud_before_manifest(context) }?
+            .set_origin(Origin::cloud(&plugin.name));
 
-        if let Ok(before_updates) = before_updates {
-            before_updates.build_update(&mut imports, &mut args, &mut content)
-        }
+        let root_id = container
+            .get_root_id()
+            .ok_or_else(|| anyhow!("The tree of {} doesn't have root", plugin.name))?;
+        let mut root = build_node(root_id, plugin, container, context)?;
+        let root_content = root.take_statements().unwrap_or_default();
+        manifest.merge(&root)?;
+        manifest = manifest.append_statements(root_content);
 
-        let root = build_node(container.get_root_id().unwrap(), plugin, &container);
-        root.build_update(&mut imports, &mut args, &mut content);
+        manifest.build_update(&mut imports, &mut hoisted, &mut args, &mut content)?;
 
-        format_js(format!(
+        Ok(format!(
             "
 // This file is generated by Densky-Framework
 // manifest.ts
 {imports}
-
+{hoisted}
 export default function({args}) {{
     {content}
 }}"
         ))
     }
 
-    /// Generate and write a manifest file from a container
+    /// Generate a manifest file from a container, with the source map of
+    /// its lines.
+    pub fn build(
+        plugin: &CloudPlugin,
+        container: &OptimizedTreeContainer,
+        context: &CompileContext,
+        output: OutputMode,
+    ) -> Result<(String, SourceMap)> {
+        let manifest = build_raw(plugin, container, context)?;
+        let manifest = output
+            .apply(manifest)
+            .with_context(|| format!("The manifest of {} isn't valid TS", plugin.name))?;
+
+        Ok(SourceMap::extract(&manifest, "manifest.ts"))
+    }
+
+    /// Path of the manifest file. It's sent to the dev runtime, that reloads
+    /// the manifest when the CLI reports this path.
+    pub fn path(context: &CompileContext) -> PathBuf {
+        join_paths("manifest.ts", &context.output_dir).into()
+    }
+
+    /// The manifest file of a container and its source map on
+    /// `manifest.ts.map`, without writing them.
+    pub fn files(
+        container: &OptimizedTreeContainer,
+        plugin: &CloudPlugin,
+        context: &CompileContext,
+        output: OutputMode,
+    ) -> Result<Vec<(PathBuf, String)>> {
+        let (mut manifest, source_map) = build(plugin, container, context, output)?;
+        if !manifest.ends_with('\n') {
+            manifest.push('\n');
+        }
+        manifest += "//# sourceMappingURL=manifest.ts.map\n";
+
+        Ok(vec![
+            (path(context), manifest),
+            (
+                join_paths("manifest.ts.map", &context.output_dir).into(),
+                source_map.to_json(),
+            ),
+        ])
+    }
+
+    /// Generate and write a manifest file from a container, and its source
+    /// map. Returns the files that changed, the others aren't written.
     pub fn update(
         container: &OptimizedTreeContainer,
         plugin: &CloudPlugin,
         context: &CompileContext,
-    ) -> io::Result<()> {
-        let manifest = build(plugin, container);
+        output: OutputMode,
+    ) -> Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        for (path, contents) in files(container, plugin, context, output)? {
+            if write_if_changed(&path, contents)? {
+                changed.push(path);
+            }
+        }
 
-        fs::write(join_paths("manifest.ts", &context.output_dir), manifest)
+        Ok(changed)
     }
 }
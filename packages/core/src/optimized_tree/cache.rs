@@ -0,0 +1,230 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use ahash::RandomState;
+use densky_adapter::{
+    log_debug, optimized_tree::OptimizedTreeContainer, utils::join_paths, CloudFile,
+    CloudFileResolve, CompileContext, ParamConstraint, Result, SegmentPattern,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::sky::CloudPlugin;
+
+/// Resolved tree of a cloud, saved on `.densky/cache/<cloud>.json`.
+/// It's only valid for the same cloud version, and each file resolution is
+/// only reused while the file hash doesn't change.
+#[derive(Default, Serialize, Deserialize)]
+pub struct OptimizedTreeCache {
+    cloud_version: String,
+    /// Map<RelativePath, CachedFile>
+    files: BTreeMap<String, CachedFile>,
+    /// Tree built with exactly `files`
+    tree: Option<OptimizedTreeContainer>,
+}
+
+/// Same as `OptimizedTreeCache`, without owning the tree
+#[derive(Serialize)]
+struct SavedCache<'a> {
+    cloud_version: &'a String,
+    files: &'a BTreeMap<String, CachedFile>,
+    tree: &'a OptimizedTreeContainer,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFile {
+    hash: u64,
+    /// Map<ResolvedPath, Resolve>. The file is resolved again for each
+    /// suffix while it's inserted.
+    resolves: BTreeMap<String, CachedResolve>,
+}
+
+/// Owned copy of a [`CloudFileResolve`]. The thorns aren't cached, their
+/// names are `&'static str` of the cloud, so they're resolved again.
+#[derive(Clone, Serialize, Deserialize)]
+enum CachedResolve {
+    Pass,
+    Ignore,
+    Index,
+    Dynamic(String, String, String, Option<ParamConstraint>),
+    CatchAll(String, String),
+    Partial(String, SegmentPattern, String),
+    Optional(String, String),
+    Group(String),
+}
+
+impl CachedResolve {
+    fn new(resolve: &CloudFileResolve) -> Option<CachedResolve> {
+        Some(match resolve.clone() {
+            CloudFileResolve::Pass => CachedResolve::Pass,
+            CloudFileResolve::Ignore => CachedResolve::Ignore,
+            CloudFileResolve::Index => CachedResolve::Index,
+            CloudFileResolve::Dynamic(prefix, varname, suffix, constraint) => {
+                CachedResolve::Dynamic(prefix, varname, suffix, constraint)
+            }
+            CloudFileResolve::CatchAll(prefix, varname) => CachedResolve::CatchAll(prefix, varname),
+            CloudFileResolve::Partial(prefix, pattern, suffix) => {
+                CachedResolve::Partial(prefix, pattern, suffix)
+            }
+            CloudFileResolve::Optional(prefix, varname) => CachedResolve::Optional(prefix, varname),
+            CloudFileResolve::Group(url_path) => CachedResolve::Group(url_path),
+            CloudFileResolve::SingleThorn(_) | CloudFileResolve::MultiThorn(_) => return None,
+        })
+    }
+
+    fn to_resolve(&self) -> CloudFileResolve {
+        match self.clone() {
+            CachedResolve::Pass => CloudFileResolve::Pass,
+            CachedResolve::Ignore => CloudFileResolve::Ignore,
+            CachedResolve::Index => CloudFileResolve::Index,
+            CachedResolve::Dynamic(prefix, varname, suffix, constraint) => {
+                CloudFileResolve::Dynamic(prefix, varname, suffix, constraint)
+            }
+            CachedResolve::CatchAll(prefix, varname) => CloudFileResolve::CatchAll(prefix, varname),
+            CachedResolve::Partial(prefix, pattern, suffix) => {
+                CloudFileResolve::Partial(prefix, pattern, suffix)
+            }
+            CachedResolve::Optional(prefix, varname) => CloudFileResolve::Optional(prefix, varname),
+            CachedResolve::Group(url_path) => CloudFileResolve::Group(url_path),
+        }
+    }
+}
+
+impl OptimizedTreeCache {
+    /// Load the cache of the cloud. If it doesn't exist or it's from other
+    /// version, an empty cache is returned.
+    pub fn load(plugin: &CloudPlugin, ctx: &CompileContext) -> Result<OptimizedTreeCache> {
+        let cloud_version = plugin.get_setup()?.version.clone();
+
+        let cache = fs::read_to_string(Self::path(plugin, ctx))
+            .ok()
+            .and_then(|cache| serde_json::from_str::<OptimizedTreeCache>(&cache).ok())
+            .filter(|cache| cache.cloud_version == cloud_version);
+
+        Ok(cache.unwrap_or_else(|| {
+            log_debug!([plugin.name] "Tree cache is empty or outdated");
+            OptimizedTreeCache {
+                cloud_version,
+                ..Default::default()
+            }
+        }))
+    }
+
+    /// Save the cache with the tree built from its files
+    pub fn save(
+        &self,
+        tree: &OptimizedTreeContainer,
+        plugin: &CloudPlugin,
+        ctx: &CompileContext,
+    ) -> Result<()> {
+        let path = Self::path(plugin, ctx);
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let cache = SavedCache {
+            cloud_version: &self.cloud_version,
+            files: &self.files,
+            tree,
+        };
+        fs::write(path, serde_json::to_string(&cache)?)?;
+        Ok(())
+    }
+
+    fn path(plugin: &CloudPlugin, ctx: &CompileContext) -> String {
+        join_paths(format!("cache/{}.json", plugin.name), &ctx.output_dir)
+    }
+
+    /// Take the cached tree, only if it was built with the same files
+    pub fn take_tree(&mut self, files: &BTreeMap<String, u64>) -> Option<OptimizedTreeContainer> {
+        let is_same = self.files.len() == files.len()
+            && self
+                .files
+                .iter()
+                .all(|(path, file)| files.get(path) == Some(&file.hash));
+
+        self.tree.take().filter(|_| is_same)
+    }
+
+    /// Remove the files that aren't on `files`
+    pub fn retain_files(&mut self, files: &BTreeMap<String, u64>) {
+        self.files.retain(|path, _| files.contains_key(path));
+    }
+
+    /// Resolve `query` (the file or one of its suffixes) with the cached
+    /// result, or call the cloud and save the result. The errors of the
+    /// cloud aren't cached.
+    pub fn resolve(
+        &mut self,
+        plugin: &CloudPlugin,
+        relative_path: &str,
+        hash: u64,
+        query: CloudFile,
+    ) -> Result<CloudFileResolve> {
+        let file = self
+            .files
+            .entry(relative_path.to_string())
+            .or_insert_with(|| CachedFile {
+                hash,
+                resolves: BTreeMap::new(),
+            });
+
+        if file.hash != hash {
+            file.hash = hash;
+            file.resolves.clear();
+        }
+
+        if let Some(resolve) = file.resolves.get(&query.relative_path) {
+            return Ok(resolve.to_resolve());
+        }
+
+        let query_path = query.relative_path.clone();
+        let resolve = unsafe { plugin.cloud_file_resolve(query) }?;
+        if let Some(cached) = CachedResolve::new(&resolve) {
+            file.resolves.insert(query_path, cached);
+        }
+        Ok(resolve)
+    }
+
+    /// Hash of the file content
+    pub fn hash_file(path: impl AsRef<Path>) -> u64 {
+        let content = fs::read(path).unwrap_or_default();
+        RandomState::with_seeds(0, 0, 0, 0).hash_one(content)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use densky_adapter::{CloudFileResolve, ParamConstraint};
+
+    use super::{CachedFile, CachedResolve, OptimizedTreeCache};
+
+    #[test]
+    fn read_back_resolves() {
+        let resolve = CloudFileResolve::Dynamic(
+            "users".into(),
+            "$id".into(),
+            "posts".into(),
+            Some(ParamConstraint::Int),
+        );
+        let mut resolves = BTreeMap::new();
+        resolves.insert(
+            "users/$id:int/posts.ts".to_string(),
+            CachedResolve::new(&resolve).unwrap(),
+        );
+
+        let mut cache = OptimizedTreeCache::default();
+        cache.files.insert(
+            "users/$id:int/posts.ts".into(),
+            CachedFile { hash: 1, resolves },
+        );
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let cache: OptimizedTreeCache = serde_json::from_str(&json).unwrap();
+        let cached = &cache.files["users/$id:int/posts.ts"].resolves["users/$id:int/posts.ts"];
+        assert_eq!(cached.to_resolve(), resolve);
+
+        // The thorns are resolved again by the cloud
+        assert!(CachedResolve::new(&CloudFileResolve::SingleThorn("middleware")).is_none());
+    }
+}
@@ -1,10 +1,10 @@
-mod container;
-mod node;
+mod cache;
 mod strategy;
 
-#[cfg(test)]
-mod test;
+pub use densky_adapter::optimized_tree::{NodeId, OptimizedTreeContainer, OptimizedTreeNode};
 
-pub use self::container::OptimizedTreeContainer;
-pub use self::node::OptimizedTreeNode;
-pub use self::strategy::optimized_tree_strategy;
+pub use self::cache::OptimizedTreeCache;
+pub use self::strategy::{
+    check_conflicts, optimized_tree_file, optimized_tree_resolver, optimized_tree_strategy,
+    optimized_tree_uncached,
+};
@@ -1,15 +1,8 @@
-use std::{
-    path::{Path, PathBuf},
-    sync::{Arc, RwLock},
-};
+use std::{collections::BTreeMap, path::Path};
 
 use densky_adapter::{
-    anyhow,
-    log::PathDebugDisplay,
-    log_trace,
-    optimized_tree::{
-        node::OptimizedTreeNodeInsertResult, OptimizedTreeContainer, OptimizedTreeNode,
-    },
+    anyhow, log_debug, log_error, log_trace, log_warn,
+    optimized_tree::{NodeId, OptimizedTreeContainer},
     utils::join_paths,
     CloudFile, CloudFileResolve, CompileContext, Result,
 };
@@ -18,174 +11,156 @@ use walkdir::WalkDir;
 
 use crate::sky::CloudPlugin;
 
+use super::OptimizedTreeCache;
+
 pub fn optimized_tree_strategy(
     input_path: impl AsRef<Path>,
     plugin: &CloudPlugin,
     ctx: &CompileContext,
-) -> Result<(OptimizedTreeContainer, Arc<RwLock<OptimizedTreeNode>>)> {
+) -> Result<(OptimizedTreeContainer, NodeId)> {
     let output_dir = join_paths(&plugin.get_setup()?.source_folder, &ctx.output_dir);
 
+    log_trace!([plugin.name] "WALKING: {}", input_path.as_ref().display());
+    let files = tree_files(&input_path, &output_dir);
+    let hashes: BTreeMap<String, u64> = files
+        .iter()
+        .map(|file| {
+            let hash = OptimizedTreeCache::hash_file(&file.file_path);
+            (file.relative_path.clone(), hash)
+        })
+        .collect();
+
+    let mut cache = OptimizedTreeCache::load(plugin, ctx)?;
+    let cached_tree = cache
+        .take_tree(&hashes)
+        .filter(|tree| tree.get_output_dir() == output_dir);
+    if let Some(container) = cached_tree {
+        log_debug!([plugin.name] "Using the cached tree");
+        check_conflicts(plugin, &container)?;
+
+        let root = container
+            .get_root_id()
+            .ok_or_else(|| anyhow!("The cached tree doesn't have root"))?;
+        return Ok((container, root));
+    }
+
     let mut container = OptimizedTreeContainer::new(output_dir.clone());
     let root = container.create_root();
 
-    log_trace!([plugin.name] "WALKING: {}", input_path.as_ref().display());
-    let walk_dir = WalkDir::new(&input_path).into_iter().filter_map(Result::ok);
-    for entry in walk_dir {
-        let file_path = entry.path();
+    cache.retain_files(&hashes);
+    for cloud_file in files {
+        let relative_path = cloud_file.relative_path.clone();
+        let hash = hashes[&relative_path];
 
-        if let Some(ext) = file_path.extension() {
-            if ext != "ts" {
-                continue;
-            }
-        } else {
-            continue;
-        }
-
-        let relative = match diff_paths(file_path, &input_path) {
-            Some(path) => path,
-            None => continue,
-        };
-        let relative_path = relative.display().to_string();
-        let extension = relative
-            .extension()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-        let path = format!("{}", &relative.with_extension("").display());
-        let file_path_debug = file_path.display_debug();
-        let file_path = file_path.display().to_string();
-        let output_path = join_paths(relative, &output_dir);
-
-        log_trace!([plugin.name] "Resolving file: {file_path_debug}");
-
-        let cloud_file = CloudFile::new(&file_path, relative_path, &output_path);
-        let resolved_file = unsafe { plugin.cloud_file_resolve(cloud_file) };
-        let resolved_file = resolved_file.unwrap_or_default();
-        log_trace!([plugin.name] "Resolved as {resolved_file:?}");
-
-        let dummy_leaf = OptimizedTreeNode::new_leaf(
-            path.clone() + ".dummy",
-            Some(file_path.clone().into()),
-            output_path.clone().into(),
-        );
-        let mut leaf = OptimizedTreeNode::new_leaf(
-            path,
-            Some(file_path.clone().into()),
-            output_path.clone().into(),
-        );
-        let dummy_leaf = container.nodes.add(dummy_leaf);
-        leaf.index = Some(dummy_leaf);
-
-        let leaf = container.nodes.add(leaf);
-
-        let next_iter = root
-            .write()
-            .unwrap()
-            .insert(leaf, resolved_file, &mut container);
-
-        perform_insert_action(
-            leaf,
-            next_iter,
-            &mut InsertContext {
-                container: &mut container,
-                plugin,
-                file_path,
-                output_path,
-                extension,
-            },
-        )?;
-
-        // println!(
-        //     "{:#?}",
-        //     Fmt(|f| root.read().unwrap().display(f, &container))
-        // )
+        container.insert_file(cloud_file, &mut |query| {
+            cache.resolve(plugin, &relative_path, hash, query)
+        })?;
+    }
+
+    if let Err(err) = cache.save(&container, plugin, ctx) {
+        log_warn!([plugin.name] "Can't save the tree cache: {err}");
     }
 
+    check_conflicts(plugin, &container)?;
+
     Ok((container, root))
 }
 
-struct InsertContext<'a> {
-    container: &'a mut OptimizedTreeContainer,
-    plugin: &'a CloudPlugin,
-    file_path: String,
-    output_path: String,
-    extension: String,
+/// Build the tree without the cache, so nothing is read or written on the
+/// output dir. Returns the files that the cloud ignored too, they aren't part
+/// of the tree.
+pub fn optimized_tree_uncached(
+    input_path: impl AsRef<Path>,
+    plugin: &CloudPlugin,
+    ctx: &CompileContext,
+) -> Result<(OptimizedTreeContainer, Vec<String>)> {
+    let output_dir = join_paths(&plugin.get_setup()?.source_folder, &ctx.output_dir);
+
+    let mut container = OptimizedTreeContainer::new(output_dir.clone());
+    container.create_root();
+
+    let mut resolve = optimized_tree_resolver(plugin);
+    let mut ignored = Vec::new();
+    for cloud_file in tree_files(&input_path, &output_dir) {
+        let relative_path = cloud_file.relative_path.clone();
+        // The next calls are the re-resolves of its suffixes
+        let mut is_first = true;
+
+        container.insert_file(cloud_file, &mut |query| {
+            let resolved = resolve(query)?;
+            if is_first && resolved == CloudFileResolve::Ignore {
+                ignored.push(relative_path.clone());
+            }
+            is_first = false;
+            Ok(resolved)
+        })?;
+    }
+
+    Ok((container, ignored))
+}
+
+/// The files of `input_path` that can be part of the tree. Sorted, so the
+/// tree is built in the same order on every run
+fn tree_files(input_path: impl AsRef<Path>, output_dir: &str) -> Vec<CloudFile> {
+    WalkDir::new(&input_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| optimized_tree_file(&input_path, entry.path(), output_dir))
+        .collect()
 }
 
-fn perform_insert_action(
-    node: u64,
-    action: OptimizedTreeNodeInsertResult,
-    context: &mut InsertContext<'_>,
-) -> Result<()> {
-    match action {
-        OptimizedTreeNodeInsertResult::Resolve(new_parent, suffix) => {
-            let resolved_file = resolve_file(node, &suffix, context);
-
-            let root = context
-                .container
-                .nodes
-                .get(new_parent)
-                .ok_or_else(|| anyhow!("Node not found. {new_parent}"))?
-                .clone();
-            let next_iter = root
-                .write()
-                .map_err(|err| anyhow!("Poisoned write: {err}"))?
-                .insert(node, resolved_file, &mut context.container);
-
-            perform_insert_action(node, next_iter, context)
-        }
-        OptimizedTreeNodeInsertResult::RemoveNode => {
-            log_trace!(["OTreeStrategy"] "Removing {node}");
-            context.container.nodes.remove(node);
-            Ok(())
-        }
-        OptimizedTreeNodeInsertResult::MergeNodes(new_node, node_b_suffix) => {
-            log_trace!(["OTreeStrategy"] "Merging ({})", node_b_suffix);
-
-            let root = context
-                .container
-                .nodes
-                .get(new_node)
-                .ok_or_else(|| anyhow!("Node not found. {new_node}"))?
-                .clone();
-
-            let resolved_node_b = resolve_file(node, &node_b_suffix, context);
-            let next_iter_b = root
-                .write()
-                .map_err(|err| anyhow!("Poisoned write: {err}"))?
-                .insert(node, resolved_node_b, &mut context.container);
-
-            perform_insert_action(node, next_iter_b, context)
-        }
-        OptimizedTreeNodeInsertResult::None => Ok(()),
+/// Get the cloud file of a path inside `input_path`.
+/// Returns `None` if the file isn't part of the tree
+pub fn optimized_tree_file(
+    input_path: impl AsRef<Path>,
+    file_path: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+) -> Option<CloudFile> {
+    let file_path = file_path.as_ref();
+    if file_path.extension()? != "ts" {
+        return None;
+    }
+
+    let relative = diff_paths(file_path, &input_path)?;
+    if relative.starts_with("..") {
+        return None;
     }
+
+    let output_path = join_paths(&relative, output_dir);
+    Some(CloudFile::new(
+        file_path.display().to_string(),
+        relative.display().to_string(),
+        output_path,
+    ))
+}
+
+/// The cloud resolver used to insert files on the tree
+pub fn optimized_tree_resolver(
+    plugin: &CloudPlugin,
+) -> impl FnMut(CloudFile) -> Result<CloudFileResolve> + '_ {
+    move |cloud_file| unsafe { plugin.cloud_file_resolve(cloud_file) }
 }
 
-fn resolve_file<'a>(
-    leaf: u64,
-    suffix: &String,
-    context: &mut InsertContext<'a>,
-) -> CloudFileResolve {
-    log_trace!([context.plugin.name] "Semi-Inserted to /{suffix}");
-
-    context
-        .container
-        .nodes
-        .get_writer(leaf)
-        .unwrap()
-        .relative_pathname = suffix.to_owned();
-
-    let suffix = PathBuf::from(suffix).with_extension(&context.extension);
-    log_trace!([context.plugin.name] "Resolving file: {}", suffix.display_debug());
-
-    let cloud_file = CloudFile::new(
-        &context.file_path,
-        suffix.display().to_string(),
-        &context.output_path,
-    );
-    let resolved_file = unsafe { context.plugin.cloud_file_resolve(cloud_file) };
-    let resolved_file = resolved_file.unwrap_or_default();
-    log_trace!([context.plugin.name] "Resolved as {resolved_file:?}");
-
-    resolved_file
+/// Fail with all the route conflicts of the tree
+pub fn check_conflicts(plugin: &CloudPlugin, container: &OptimizedTreeContainer) -> Result<()> {
+    let conflicts: Vec<_> = container.get_conflicts().collect();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    for conflict in &conflicts {
+        log_error!([plugin.name] "{conflict}");
+    }
+
+    Err(anyhow!(
+        "Found {} route conflicts on {}:\n{}",
+        conflicts.len(),
+        plugin.name,
+        conflicts
+            .iter()
+            .map(|conflict| format!("  - {conflict}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    ))
 }
@@ -1,10 +1,10 @@
 use std::{fmt::Debug, path::PathBuf};
 
-use densky_adapter::{utils::join_paths, AHashMap, CloudDependency};
-use densky_adapter::{CloudVersion, ErrorContext};
+use densky_adapter::{utils::join_paths, AHashMap, CloudDependency, CloudDependencyOption};
+use densky_adapter::{CloudVersion, CompileMode, ErrorContext};
 use jsonc_parser::{JsonObject, JsonValue};
 
-use crate::utils::{discover_file, read_file};
+use crate::utils::{discover_file, read_file, OutputMode};
 
 pub struct CompileOptions {
     pub verbose: bool,
@@ -15,6 +15,8 @@ pub struct ConfigFile {
     pub verbose: bool,
     pub output: PathBuf,
     pub vendor: Vec<PathBuf>,
+    /// `None` to use the default of the compile mode
+    pub format: Option<OutputMode>,
     pub dependencies: AHashMap<String, CloudDependency>,
 }
 
@@ -23,6 +25,7 @@ impl Debug for ConfigFile {
         f.debug_struct("ConfigFile")
             .field("verbose", &self.verbose)
             .field("output_dir", &self.output.display())
+            .field("format", &self.format)
             .field("dependencies", &self.dependencies)
             .finish()
     }
@@ -72,6 +75,14 @@ impl ConfigFile {
             Vec::new()
         };
 
+        let format = densky.get_string("format").and_then(|format| {
+            let mode = OutputMode::parse(format);
+            if mode.is_none() {
+                eprintln!("Invalid format '{format}', it should be 'pretty', 'raw' or 'minified'");
+            }
+            mode
+        });
+
         let dependencies = if let Some(clouds) = densky.get_object("clouds") {
             let mut dependencies = AHashMap::new();
 
@@ -108,32 +119,20 @@ impl ConfigFile {
                             continue;
                         }
 
-                        // let options = if let Some(options) = cloud.as_table_like() {
-                        //     let mut opts = AHashMap::new();
-
-                        // for (name, opt) in options.iter() {
-                        //     // Reserved options
-                        //     if matches!(name, "version") {
-                        //         continue;
-                        //     }
-                        //
-                        //     let Some(opt) = opt.as_value() else {
-                        //         eprint!("Invalid option type '{name}' in '{cloud_name}'");
-                        //         continue;
-                        //     };
-                        //
-                        //     let Some(opt) = parse_opt(opt) else {
-                        //         eprint!("Invalid option type '{name}' in '{cloud_name}'");
-                        //         continue;
-                        //     };
-                        //
-                        //     opts.insert(name.to_string(), opt);
-                        // }
-
-                        //     opts
-                        // } else {
-                        let options = Default::default();
-                        // };
+                        let mut options = AHashMap::new();
+                        for (name, opt) in cloud.clone().into_iter() {
+                            // Reserved options
+                            if name == "version" {
+                                continue;
+                            }
+
+                            let Some(opt) = parse_opt(&opt) else {
+                                eprintln!("Invalid option type '{name}' in '{cloud_name}'");
+                                continue;
+                            };
+
+                            options.insert(name, opt);
+                        }
 
                         let dependency = CloudDependency {
                             name: cloud_name.clone(),
@@ -161,22 +160,31 @@ impl ConfigFile {
             verbose,
             output,
             vendor,
+            format,
             dependencies,
         })
     }
+
+    /// Output mode of the generated files
+    pub fn output_mode(&self, mode: CompileMode) -> OutputMode {
+        self.format.unwrap_or_else(|| OutputMode::default_for(mode))
+    }
 }
 
-// TODO: use jsonc_parser to parse CloudDependencyOption
-//
-// fn parse_opt(opt: &JsonValue<'_>) -> Option<CloudDependencyOption> {
-//     match opt {
-//         JsonValue::Number(ref v) => Some(CloudDependencyOption::Float(v.value().clone())),
-//         JsonValue::String(ref v) => Some(CloudDependencyOption::String(v.value().clone())),
-//         JsonValue::Object(ref v) => Some(CloudDependencyOption::String(v.value().clone())),
-//         JsonValue::Boolean(ref v) => Some(CloudDependencyOption::Boolean(v.value().clone())),
-//         JsonValue::Array(ref v) => Some(CloudDependencyOption::Array(
-//             v.iter().map_while(|opt| parse_opt(opt)).collect(),
-//         )),
-//         _ => None,
-//     }
-// }
+fn parse_opt(opt: &JsonValue<'_>) -> Option<CloudDependencyOption> {
+    match opt {
+        JsonValue::String(v) => Some(CloudDependencyOption::String(v.to_string())),
+        JsonValue::Number(v) => v
+            .parse::<i64>()
+            .map(CloudDependencyOption::Integer)
+            .or_else(|_| v.parse::<f64>().map(CloudDependencyOption::Float))
+            .ok(),
+        JsonValue::Boolean(v) => Some(CloudDependencyOption::Boolean(*v)),
+        JsonValue::Array(v) => v
+            .iter()
+            .map(parse_opt)
+            .collect::<Option<Vec<_>>>()
+            .map(CloudDependencyOption::Array),
+        JsonValue::Object(_) | JsonValue::Null => None,
+    }
+}
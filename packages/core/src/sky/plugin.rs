@@ -1,6 +1,6 @@
 use densky_adapter::{
-    anyhow, log_info, CloudBeforeManifestCall, CloudFilesStrategy, CloudManifestUpdate,
-    CloudOptimizedManifestCall, ErrorContext, OptimizedTreeLeaf, Result,
+    anyhow, js::Stmt, log_info, CloudBeforeManifestCall, CloudFilesStrategy, CloudManifestUpdate,
+    CloudOptimizedManifestCall, CloudOptions, ErrorContext, OptimizedTreeLeaf, Result,
 };
 use libloading::{Library, Symbol};
 use std::path::{Path, PathBuf};
@@ -10,7 +10,10 @@ use densky_adapter::{
     CloudFile, CloudFileResolve, CloudFileResolveCall, CloudSetup, CloudSetupCall,
 };
 
-use crate::optimized_tree::{optimized_tree_strategy, OptimizedTreeContainer};
+use crate::optimized_tree::{
+    optimized_tree_file, optimized_tree_resolver, optimized_tree_strategy, optimized_tree_uncached,
+    OptimizedTreeContainer,
+};
 use crate::CompileContext;
 
 use super::open_cloud;
@@ -36,6 +39,8 @@ pub struct CloudPlugin {
     lib: Library,
     setup: Option<CloudSetup>,
     context: Option<CloudContextRaw>,
+    /// Options of the config file, they're passed to the manifest calls
+    options: CloudOptions,
 }
 
 impl CloudPlugin {
@@ -56,10 +61,19 @@ impl CloudPlugin {
                 lib,
                 setup: None,
                 context: None,
+                options: CloudOptions::default(),
             })
         }
     }
 
+    pub fn set_options(&mut self, options: CloudOptions) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> &CloudOptions {
+        &self.options
+    }
+
     pub fn get_setup(&self) -> Result<&CloudSetup> {
         match self.setup.as_ref() {
             Some(s) => Ok(s),
@@ -148,20 +162,43 @@ impl CloudPlugin {
         lib_call(file, self.context.unwrap_or_else(CloudContextRaw::null))
     }
 
-    pub unsafe fn cloud_before_manifest(&self) -> Result<CloudManifestUpdate> {
-        let lib_call = get_cloud_call!(self, CloudBeforeManifestCall)?;
-        lib_call()
+    /// Update of the manifest that goes before the routes. The call is
+    /// optional, a cloud without it doesn't add anything, but its errors are
+    /// returned.
+    ///
+    /// # Safety
+    ///
+    /// The `cloud_before_manifest` symbol of the dylib must have the
+    /// signature of [`CloudBeforeManifestCall`], and the cloud must be built
+    /// with the same compiler and `densky-adapter` version of the CLI, since
+    /// the Rust ABI of the arguments isn't stable.
+    pub unsafe fn cloud_before_manifest(
+        &self,
+        ctx: &CompileContext,
+    ) -> Result<CloudManifestUpdate> {
+        let Ok(lib_call) = get_cloud_call!(self, CloudBeforeManifestCall) else {
+            return Ok(CloudManifestUpdate::new());
+        };
+        lib_call(ctx, &self.options)
     }
 
     pub unsafe fn cloud_optimized_manifest_call(
         &self,
         leaf: OptimizedTreeLeaf,
-        static_children: String,
-        children: String,
-        dynamic_child: String,
+        static_children: Vec<(String, Vec<Stmt>)>,
+        children: Vec<Stmt>,
+        dynamic_child: Vec<Stmt>,
+        ctx: &CompileContext,
     ) -> Result<CloudManifestUpdate> {
         let lib_call = get_cloud_call!(self, CloudOptimizedManifestCall)?;
-        lib_call(leaf, static_children, children, dynamic_child)
+        lib_call(
+            leaf,
+            static_children,
+            children,
+            dynamic_child,
+            ctx,
+            &self.options,
+        )
     }
 
     pub fn setup(&mut self) -> Result<()> {
@@ -188,6 +225,65 @@ impl CloudPlugin {
     // }
 
     pub fn resolve_optimized_tree(&self, ctx: &CompileContext) -> Result<OptimizedTreeContainer> {
+        let input_paths = self.optimized_tree_input()?;
+        let (container, _) = optimized_tree_strategy(input_paths, self, ctx)?;
+
+        // println!(
+        //     "{:#?}",
+        //     Fmt(|f| tree.read().unwrap().display(f, &container))
+        // );
+
+        Ok(container)
+    }
+
+    /// Resolve the tree without the cache, see [`optimized_tree_uncached`].
+    /// Returns the files that were ignored too.
+    pub fn resolve_optimized_tree_uncached(
+        &self,
+        ctx: &CompileContext,
+    ) -> Result<(OptimizedTreeContainer, Vec<String>)> {
+        let input_paths = self.optimized_tree_input()?;
+        optimized_tree_uncached(input_paths, self, ctx)
+    }
+
+    /// Insert a created file on the tree, without rebuilding it.
+    /// Returns `false` if the file isn't part of the tree.
+    pub fn insert_optimized_tree_file(
+        &self,
+        container: &mut OptimizedTreeContainer,
+        file_path: impl AsRef<Path>,
+    ) -> Result<bool> {
+        let input_path = self.optimized_tree_input()?;
+        let Some(cloud_file) =
+            optimized_tree_file(input_path, file_path, container.get_output_dir())
+        else {
+            return Ok(false);
+        };
+
+        container.insert_file(cloud_file, &mut optimized_tree_resolver(self))?;
+        Ok(true)
+    }
+
+    /// Remove a deleted file from the tree, without rebuilding it.
+    /// Returns `false` if the file isn't part of the tree.
+    pub fn remove_optimized_tree_file(
+        &self,
+        container: &mut OptimizedTreeContainer,
+        file_path: impl AsRef<Path>,
+    ) -> Result<bool> {
+        let input_path = self.optimized_tree_input()?;
+        let Some(cloud_file) =
+            optimized_tree_file(input_path, file_path, container.get_output_dir())
+        else {
+            return Ok(false);
+        };
+
+        container.remove_file(cloud_file.relative_path, &mut optimized_tree_resolver(self))?;
+        Ok(true)
+    }
+
+    /// Folder with the files of the optimized tree
+    fn optimized_tree_input(&self) -> Result<PathBuf> {
         let setup = self.get_setup()?;
         if setup.file_strategy != CloudFilesStrategy::OptimizedTree {
             return Err(anyhow!(
@@ -198,14 +294,6 @@ impl CloudPlugin {
         }
 
         let input_paths = std::env::current_dir()?.join("src");
-        let input_paths = input_paths.join(setup.source_folder.clone());
-        let (container, _) = optimized_tree_strategy(input_paths, self, ctx)?;
-
-        // println!(
-        //     "{:#?}",
-        //     Fmt(|f| tree.read().unwrap().display(f, &container))
-        // );
-
-        Ok(container)
+        Ok(input_paths.join(setup.source_folder.clone()))
     }
 }
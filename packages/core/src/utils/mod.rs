@@ -1,19 +1,73 @@
 mod importer;
 mod url_to_matcher;
+mod write;
 
 use std::path::PathBuf;
+use std::sync::OnceLock;
 
+use densky_adapter::{anyhow, js::minify, CompileMode, Result};
 use dprint_plugin_typescript::{configuration as dprint_config, format_text};
 
 pub use self::importer::*;
 pub use self::url_to_matcher::*;
+pub use self::write::write_if_changed;
 
-pub fn format_js(txt: impl Into<String>) -> String {
-    let config = dprint_config::ConfigurationBuilder::new()
-        .line_width(80)
-        .build();
+/// Format TS code, it fails if the code isn't valid
+pub fn format_js(txt: impl Into<String>) -> Result<String> {
+    // It's the same on each rebuild
+    static CONFIG: OnceLock<dprint_config::Configuration> = OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        dprint_config::ConfigurationBuilder::new()
+            .line_width(80)
+            .build()
+    });
 
-    format_text(&PathBuf::from("/tmp/file.ts"), &txt.into(), &config)
-        .unwrap()
-        .unwrap()
+    let txt = txt.into();
+    let formatted = format_text(&PathBuf::from("/tmp/file.ts"), &txt, config)
+        .map_err(|err| anyhow!("Invalid TS code: {err}"))?;
+
+    // `None` when it's already formatted
+    Ok(formatted.unwrap_or(txt))
+}
+
+/// How the generated code is written. It's the `format` option of the
+/// `densky` section on the config file: `"pretty"`, `"raw"` or `"minified"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Formatted with dprint, to debug the generated code
+    Pretty,
+    /// As it's printed, nothing runs after the codegen
+    Raw,
+    /// Without comments and indentation, and with short internal identifiers
+    Minified,
+}
+
+impl OutputMode {
+    pub fn parse(mode: &str) -> Option<OutputMode> {
+        match mode {
+            "pretty" => Some(OutputMode::Pretty),
+            "raw" => Some(OutputMode::Raw),
+            "minified" => Some(OutputMode::Minified),
+            _ => None,
+        }
+    }
+
+    /// `raw` on `densky dev`, it's rebuilt on each change, and `minified` on
+    /// `densky build`
+    pub fn default_for(mode: CompileMode) -> OutputMode {
+        match mode {
+            CompileMode::Development => OutputMode::Raw,
+            CompileMode::Production => OutputMode::Minified,
+        }
+    }
+
+    /// Write the TS code in this mode. The source map markers are kept, and
+    /// only `pretty` checks that the code is valid.
+    pub fn apply(self, code: String) -> Result<String> {
+        match self {
+            OutputMode::Pretty => format_js(code),
+            OutputMode::Raw => Ok(code),
+            OutputMode::Minified => Ok(minify(&code)),
+        }
+    }
 }
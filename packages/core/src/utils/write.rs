@@ -0,0 +1,59 @@
+use std::fs;
+use std::path::Path;
+
+use densky_adapter::{ErrorContext, Result};
+
+/// Write a generated file only when its content changes, so the runtime
+/// doesn't reload what's the same. Returns whether the file was written.
+///
+/// The content goes to a temp file next to it that's renamed over the file,
+/// so a reader never sees a half-written file.
+pub fn write_if_changed(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Result<bool> {
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+
+    // Cheaper than the write, and the file isn't touched
+    if fs::read(path).is_ok_and(|current| current == contents) {
+        return Ok(false);
+    }
+
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Can't write {}, it isn't a file", path.display()))?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let written = fs::write(&temp_path, contents).and_then(|_| fs::rename(&temp_path, path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err).with_context(|| format!("Can't write {}", path.display()));
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::write_if_changed;
+
+    #[test]
+    fn write_only_changes() {
+        let dir = std::env::temp_dir().join(format!("densky-write-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("manifest.ts");
+
+        assert!(write_if_changed(&path, "a").unwrap());
+        assert!(!write_if_changed(&path, "a").unwrap());
+        assert!(write_if_changed(&path, "b").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b");
+
+        // The temp file was renamed
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}